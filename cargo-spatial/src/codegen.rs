@@ -1,7 +1,10 @@
 use crate::config::Config;
 use crate::format_arg;
 use log::*;
-use spatialos_sdk_code_generator::{generator, schema_bundle};
+use spatialos_sdk_code_generator::{
+    generator::{self, CodegenOptions},
+    schema_bundle,
+};
 use std::fs::{self, File};
 use std::io::prelude::*;
 use std::path::*;
@@ -79,7 +82,10 @@ pub fn run_codegen(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     // Run code generation.
     let bundle = schema_bundle::load_bundle(&contents)
         .map_err(|_| "Failed to parse contents of bundle.json")?;
-    let generated_file = generator::generate_code(bundle);
+    let options = CodegenOptions {
+        serde: config.codegen_serde,
+    };
+    let generated_file = generator::generate_code_with_options(bundle, options);
 
     // Write the generated code to the output file.
     File::create(&config.codegen_out)
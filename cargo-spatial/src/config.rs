@@ -22,6 +22,11 @@ pub struct Config {
     /// Defaults to `src/generated.rs`.
     pub codegen_out: String,
 
+    /// Whether generated types should derive serde's `Serialize` and `Deserialize`.
+    ///
+    /// Requires the `serde` feature of `spatialos-sdk`. Defaults to `false`.
+    pub codegen_serde: bool,
+
     /// The directories containing schema files for the project.
     ///
     /// Defaults to `./schema`.
@@ -56,6 +61,7 @@ impl Default for Config {
             spatial_sdk_version: "14.0.0".into(),
             workers: vec![".".into()],
             codegen_out: "src/generated.rs".into(),
+            codegen_serde: false,
             schema_paths: vec![],
             build_dir: "./build".into(),
             schema_build_dir: None,
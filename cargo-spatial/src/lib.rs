@@ -5,6 +5,8 @@ pub mod config;
 pub mod download;
 pub mod local;
 pub mod opt;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
 
 /// Generates a random, valid component ID.
 ///
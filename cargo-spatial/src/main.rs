@@ -32,6 +32,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Download::Cli => download::download_cli()?,
             Download::Sdk(options) => download::download_sdk(Config::load(), options)?,
         },
+
+        #[cfg(feature = "snapshot")]
+        Command::Snapshot { command } => match command {
+            Snapshot::Convert(options) => {
+                cargo_spatial::snapshot::convert(&Config::load()?, options)?
+            }
+            Snapshot::Validate(options) => {
+                cargo_spatial::snapshot::validate(&Config::load()?, options)?
+            }
+            Snapshot::Diff(options) => {
+                cargo_spatial::snapshot::diff(&Config::load()?, options, opt.json_output)?
+            }
+        },
     }
 
     Ok(())
@@ -54,6 +54,14 @@ pub enum Command {
         #[structopt(subcommand)]
         command: Download,
     },
+
+    /// Commands for inspecting and editing snapshots
+    #[cfg(feature = "snapshot")]
+    #[structopt(name = "snapshot")]
+    Snapshot {
+        #[structopt(subcommand)]
+        command: Snapshot,
+    },
 }
 
 #[derive(StructOpt)]
@@ -107,3 +115,63 @@ pub struct DownloadSdk {
     #[structopt(long, short)]
     pub with_test_schema: bool,
 }
+
+#[cfg(feature = "snapshot")]
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub enum Snapshot {
+    /// Convert a snapshot to or from JSON, using the project's schema bundle
+    #[structopt(name = "convert")]
+    Convert(SnapshotConvert),
+
+    /// Check a snapshot for problems that would stop the runtime from loading it
+    #[structopt(name = "validate")]
+    Validate(SnapshotValidate),
+
+    /// Show the entities, components and fields that differ between two snapshots
+    #[structopt(name = "diff")]
+    Diff(SnapshotDiff),
+}
+
+#[cfg(feature = "snapshot")]
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct SnapshotConvert {
+    /// Convert a snapshot to JSON
+    #[structopt(long, conflicts_with = "from-json", required_unless = "from-json")]
+    pub to_json: bool,
+
+    /// Convert JSON to a snapshot
+    #[structopt(long)]
+    pub from_json: bool,
+
+    /// The file to convert
+    #[structopt(parse(from_os_str))]
+    pub input: PathBuf,
+
+    /// The file to write the converted snapshot to
+    #[structopt(parse(from_os_str))]
+    pub output: PathBuf,
+}
+
+#[cfg(feature = "snapshot")]
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct SnapshotValidate {
+    /// The snapshot to validate
+    #[structopt(parse(from_os_str))]
+    pub snapshot: PathBuf,
+}
+
+#[cfg(feature = "snapshot")]
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct SnapshotDiff {
+    /// The snapshot to compare against
+    #[structopt(parse(from_os_str))]
+    pub old: PathBuf,
+
+    /// The snapshot to compare
+    #[structopt(parse(from_os_str))]
+    pub new: PathBuf,
+}
@@ -0,0 +1,155 @@
+use crate::config::Config;
+use crate::opt::{SnapshotConvert, SnapshotDiff, SnapshotValidate};
+use log::*;
+use spatialos_sdk::worker::{
+    component::ComponentRegistry,
+    schema_value::SchemaDefinitions,
+    snapshot::{
+        diff, json, validation::Validator, SnapshotError, SnapshotInputStream, SnapshotOutputStream,
+    },
+};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+/// Converts a snapshot to or from JSON, as selected by `options`.
+///
+/// Components are rendered using the schema bundle generated by `cargo spatial codegen`, so
+/// code generation must have been run for the project first.
+pub fn convert(
+    config: &Config,
+    options: &SnapshotConvert,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let definitions = load_definitions(config)?;
+
+    if options.to_json {
+        to_json(&definitions, &options.input, &options.output)
+    } else {
+        from_json(&definitions, &options.input, &options.output)
+    }
+}
+
+/// Checks the snapshot selected by `options`, printing every problem that's found.
+///
+/// Components are known if they're defined in the project's schema bundle.
+pub fn validate(
+    config: &Config,
+    options: &SnapshotValidate,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let definitions = load_definitions(config)?;
+    let validator = Validator::with_known_components(
+        definitions
+            .components()
+            .map(|component| component.component_id),
+    );
+    let report = validator
+        .check_snapshot(&options.snapshot)
+        .map_err(|err| snapshot_error("Failed to read snapshot", err))?;
+
+    for error in &report.errors {
+        println!("{}", error);
+    }
+
+    if !report.is_valid() {
+        return Err(format!(
+            "Found {} problem(s) in {} entities",
+            report.errors.len(),
+            report.entity_count
+        )
+        .into());
+    }
+
+    info!(
+        "{} is valid ({} entities)",
+        options.snapshot.display(),
+        report.entity_count
+    );
+    Ok(())
+}
+
+/// Prints the differences between the snapshots selected by `options`, as JSON if
+/// `json_output` is set.
+pub fn diff(
+    config: &Config,
+    options: &SnapshotDiff,
+    json_output: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let definitions = load_definitions(config)?;
+    let diff = diff::diff(&definitions, &options.old, &options.new)
+        .map_err(|err| snapshot_error("Failed to compare snapshots", err))?;
+
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&diff.to_json())?);
+    } else if diff.is_empty() {
+        info!("The snapshots are identical");
+    } else {
+        print!("{}", diff);
+    }
+
+    Ok(())
+}
+
+fn to_json(
+    definitions: &SchemaDefinitions,
+    input: &PathBuf,
+    output: &PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // With an empty registry, components are read in their serialized form regardless of
+    // whether this binary knows about them.
+    let mut stream = SnapshotInputStream::with_registry(input, &ComponentRegistry::new())
+        .map_err(|err| snapshot_error("Failed to open snapshot", err))?;
+    let entities = stream.serialized_entities();
+    let document = json::snapshot_to_json(definitions, entities)
+        .map_err(|err| snapshot_error("Failed to convert snapshot", err))?;
+
+    let mut writer = BufWriter::new(
+        File::create(output).map_err(|_| format!("Failed to create {}", output.display()))?,
+    );
+    serde_json::to_writer_pretty(&mut writer, &document)?;
+    writer.flush()?;
+
+    info!("Converted {} to {}", input.display(), output.display());
+    Ok(())
+}
+
+fn from_json(
+    definitions: &SchemaDefinitions,
+    input: &PathBuf,
+    output: &PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = BufReader::new(
+        File::open(input).map_err(|_| format!("Failed to open {}", input.display()))?,
+    );
+    let document: serde_json::Value = serde_json::from_reader(reader)
+        .map_err(|err| format!("Failed to parse {}: {}", input.display(), err))?;
+    let entities = json::snapshot_from_json(definitions, &document)
+        .map_err(|err| snapshot_error("Failed to convert JSON", err))?;
+
+    let mut stream = SnapshotOutputStream::with_registry(output, &ComponentRegistry::new())
+        .map_err(|err| snapshot_error("Failed to create snapshot", err))?;
+    for entity in &entities {
+        stream
+            .write_serialized(entity)
+            .map_err(|err| snapshot_error("Failed to write snapshot", err))?;
+    }
+
+    info!("Converted {} to {}", input.display(), output.display());
+    Ok(())
+}
+
+/// Loads the schema bundle written to the schema build directory by `run_codegen`.
+pub fn load_definitions(config: &Config) -> Result<SchemaDefinitions, Box<dyn std::error::Error>> {
+    let bundle_json_path = PathBuf::from(config.schema_build_dir()).join("bundle.json");
+    let contents = fs::read_to_string(&bundle_json_path).map_err(|_| {
+        format!(
+            "Failed to read {}, run `cargo spatial codegen` first",
+            bundle_json_path.display()
+        )
+    })?;
+    SchemaDefinitions::from_json(&contents)
+        .map_err(|err| format!("Failed to parse contents of bundle.json: {}", err).into())
+}
+
+fn snapshot_error(context: &str, error: SnapshotError) -> String {
+    format!("{}: {:?}", context, error)
+}
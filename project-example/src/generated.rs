@@ -125,6 +125,185 @@ impl TypeConversion for Vector3d {
 }
 
 /* Components. */ 
+#[derive(Debug, Clone)]
+pub struct CollectionTest {
+    pub maybe_value: Option<i32>,
+    pub values: Vec<i32>,
+    pub names: BTreeMap<i32, String>,
+}
+impl TypeConversion for CollectionTest {
+    fn from_type(input: &SchemaObject) -> Result<Self, String> {
+        Ok(Self {
+            maybe_value: if let Some(data) = input.field::<SchemaInt32>(1).get() { Some(data) } else { None },
+            values: { let size = input.field::<SchemaInt32>(2).count(); let mut l = Vec::with_capacity(size); for i in 0..size { l.push(input.field::<SchemaInt32>(2).index(i)); }; l },
+            names: { let size = input.field::<SchemaObject>(3).count(); let mut m = BTreeMap::new(); for i in 0..size { let kv = input.field::<SchemaObject>(3).index(i); m.insert(kv.field::<SchemaInt32>(1).get_or_default(), kv.field::<SchemaString>(2).get_or_default()); }; m },
+        })
+    }
+    fn to_type(input: &Self, output: &mut SchemaObject) -> Result<(), String> {
+        if let Some(data) = input.maybe_value { output.field::<SchemaInt32>(1).add(data); };
+        output.field::<SchemaInt32>(2).add_list(&&input.values[..]);
+        for (k, v) in &input.names { let object = output.field::<SchemaObject>(3).add(); object.field::<SchemaInt32>(1).add(*k); object.field::<SchemaString>(2).add(&v); };
+        Ok(())
+    }
+}
+impl ComponentData<CollectionTest> for CollectionTest {
+    fn merge(&mut self, update: CollectionTestUpdate) {
+        if let Some(value) = update.maybe_value { self.maybe_value = value; }
+        if let Some(value) = update.values { self.values = value; }
+        if let Some(value) = update.names { self.names = value; }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CollectionTestUpdate {
+    pub maybe_value: Option<Option<i32>>,
+    pub values: Option<Vec<i32>>,
+    pub names: Option<BTreeMap<i32, String>>,
+}
+impl TypeConversion for CollectionTestUpdate {
+    fn from_type(input: &SchemaObject) -> Result<Self, String> {
+        let mut output = Self {
+            maybe_value: None,
+            values: None,
+            names: None,
+        };
+        let _field_maybe_value = input.field::<SchemaInt32>(1);
+        if _field_maybe_value.count() > 0 {
+            let field = &_field_maybe_value;
+            output.maybe_value = Some(if let Some(data) = field.get() { Some(data) } else { None });
+        }
+        let _field_values = input.field::<SchemaInt32>(2);
+        if _field_values.count() > 0 {
+            let field = &_field_values;
+            output.values = Some({ let size = field.count(); let mut l = Vec::with_capacity(size); for i in 0..size { l.push(field.index(i)); }; l });
+        }
+        let _field_names = input.field::<SchemaObject>(3);
+        if _field_names.count() > 0 {
+            let field = &_field_names;
+            output.names = Some({ let size = field.count(); let mut m = BTreeMap::new(); for i in 0..size { let kv = field.index(i); m.insert(kv.field::<SchemaInt32>(1).get_or_default(), kv.field::<SchemaString>(2).get_or_default()); }; m });
+        }
+        Ok(output)
+    }
+    fn to_type(input: &Self, output: &mut SchemaObject) -> Result<(), String> {
+        if let Some(value) = input.maybe_value {
+            if let Some(data) = value { output.field::<SchemaInt32>(1).add(data); };
+        }
+        if let Some(ref value) = input.values {
+            output.field::<SchemaInt32>(2).add_list(&value[..]);
+        }
+        if let Some(ref value) = input.names {
+            for (k, v) in value { let object = output.field::<SchemaObject>(3).add(); object.field::<SchemaInt32>(1).add(*k); object.field::<SchemaString>(2).add(&v); };
+        }
+        Ok(())
+    }
+}
+impl ComponentUpdate<CollectionTest> for CollectionTestUpdate {
+    fn merge(&mut self, update: CollectionTestUpdate) {
+        if update.maybe_value.is_some() { self.maybe_value = update.maybe_value; }
+        if update.values.is_some() { self.values = update.values; }
+        if update.names.is_some() { self.names = update.names; }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CollectionTestCommandRequest {
+}
+
+#[derive(Debug, Clone)]
+pub enum CollectionTestCommandResponse {
+}
+
+impl Component for CollectionTest {
+    type Update = generated::example::CollectionTestUpdate;
+    type CommandRequest = generated::example::CollectionTestCommandRequest;
+    type CommandResponse = generated::example::CollectionTestCommandResponse;
+
+    const ID: ComponentId = 2003;
+
+    fn from_data(data: &SchemaComponentData) -> Result<generated::example::CollectionTest, String> {
+        <generated::example::CollectionTest as TypeConversion>::from_type(&data.fields())
+    }
+
+    fn from_update(update: &SchemaComponentUpdate) -> Result<generated::example::CollectionTestUpdate, String> {
+        let mut output = <generated::example::CollectionTestUpdate as TypeConversion>::from_type(&update.fields())?;
+        if update.is_field_cleared(1) {
+            output.maybe_value = Some(Default::default());
+        }
+        if update.is_field_cleared(2) {
+            output.values = Some(Default::default());
+        }
+        if update.is_field_cleared(3) {
+            output.names = Some(Default::default());
+        }
+        let events = update.events();
+        Ok(output)
+    }
+
+    fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::example::CollectionTestCommandRequest, String> {
+        match command_index {
+            _ => Err(format!("Attempted to deserialize an unrecognised command request with index {} in component CollectionTest.", command_index))
+        }
+    }
+
+    fn from_response(command_index: CommandIndex, response: &SchemaCommandResponse) -> Result<generated::example::CollectionTestCommandResponse, String> {
+        match command_index {
+            _ => Err(format!("Attempted to deserialize an unrecognised command response with index {} in component CollectionTest.", command_index))
+        }
+    }
+
+    fn to_data(data: &generated::example::CollectionTest) -> Result<SchemaComponentData, String> {
+        let mut serialized_data = SchemaComponentData::new();
+        <generated::example::CollectionTest as TypeConversion>::to_type(data, &mut serialized_data.fields_mut())?;
+        Ok(serialized_data)
+    }
+
+    fn to_update(update: &generated::example::CollectionTestUpdate) -> Result<SchemaComponentUpdate, String> {
+        let mut serialized_update = SchemaComponentUpdate::new();
+        <generated::example::CollectionTestUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        if update.maybe_value.as_ref().map_or(false, |value| value.is_none()) {
+            serialized_update.add_cleared_field(1);
+        }
+        if update.values.as_ref().map_or(false, |value| value.is_empty()) {
+            serialized_update.add_cleared_field(2);
+        }
+        if update.names.as_ref().map_or(false, |value| value.is_empty()) {
+            serialized_update.add_cleared_field(3);
+        }
+        let events = serialized_update.events_mut();
+        Ok(serialized_update)
+    }
+
+    fn to_request(request: &generated::example::CollectionTestCommandRequest) -> Result<SchemaCommandRequest, String> {
+        let mut serialized_request = SchemaCommandRequest::new();
+        match request {
+            _ => unreachable!()
+        }
+        Ok(serialized_request)
+    }
+
+    fn to_response(response: &generated::example::CollectionTestCommandResponse) -> Result<SchemaCommandResponse, String> {
+        let mut serialized_response = SchemaCommandResponse::new();
+        match response {
+            _ => unreachable!()
+        }
+        Ok(serialized_response)
+    }
+
+    fn get_request_command_index(request: &generated::example::CollectionTestCommandRequest) -> u32 {
+        match request {
+            _ => unreachable!(),
+        }
+    }
+
+    fn get_response_command_index(response: &generated::example::CollectionTestCommandResponse) -> u32 {
+        match response {
+            _ => unreachable!(),
+        }
+    }
+}
+
+inventory::submit!(VTable::new::<CollectionTest>());
+
 #[derive(Debug, Clone)]
 pub struct EntityIdTest {
     pub eid: spatialos_sdk::worker::EntityId,
@@ -195,7 +374,9 @@ impl Component for EntityIdTest {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::example::EntityIdTestUpdate, String> {
-        <generated::example::EntityIdTestUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::example::EntityIdTestUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::example::EntityIdTestCommandRequest, String> {
@@ -219,6 +400,7 @@ impl Component for EntityIdTest {
     fn to_update(update: &generated::example::EntityIdTestUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::example::EntityIdTestUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -323,7 +505,9 @@ impl Component for EnumTestComponent {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::example::EnumTestComponentUpdate, String> {
-        <generated::example::EnumTestComponentUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::example::EnumTestComponentUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::example::EnumTestComponentCommandRequest, String> {
@@ -347,6 +531,7 @@ impl Component for EnumTestComponent {
     fn to_update(update: &generated::example::EnumTestComponentUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::example::EnumTestComponentUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -405,11 +590,13 @@ impl ComponentData<Example> for Example {
 #[derive(Debug, Clone, Default)]
 pub struct ExampleUpdate {
     pub x: Option<f32>,
+    pub test_event: Vec<generated::example::CommandData>,
 }
 impl TypeConversion for ExampleUpdate {
     fn from_type(input: &SchemaObject) -> Result<Self, String> {
         let mut output = Self {
             x: None,
+            test_event: Vec::new(),
         };
         let _field_x = input.field::<SchemaFloat>(1);
         if _field_x.count() > 0 {
@@ -428,6 +615,7 @@ impl TypeConversion for ExampleUpdate {
 impl ComponentUpdate<Example> for ExampleUpdate {
     fn merge(&mut self, update: ExampleUpdate) {
         if update.x.is_some() { self.x = update.x; }
+        self.test_event.extend(update.test_event);
     }
 }
 
@@ -441,6 +629,53 @@ pub enum ExampleCommandResponse {
     TestCommand(generated::example::CommandData),
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct ExampleTestCommand;
+
+impl Command for ExampleTestCommand {
+    type Component = generated::example::Example;
+    type Request = generated::example::CommandData;
+    type Response = generated::example::CommandData;
+
+    const INDEX: CommandIndex = 1;
+
+    fn into_request(request: generated::example::CommandData) -> generated::example::ExampleCommandRequest {
+        generated::example::ExampleCommandRequest::TestCommand(request)
+    }
+
+    fn into_response(response: generated::example::CommandData) -> generated::example::ExampleCommandResponse {
+        generated::example::ExampleCommandResponse::TestCommand(response)
+    }
+
+    fn get_request(request: &generated::example::ExampleCommandRequest) -> Option<&generated::example::CommandData> {
+        match request {
+            generated::example::ExampleCommandRequest::TestCommand(ref data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn get_response(response: &generated::example::ExampleCommandResponse) -> Option<&generated::example::CommandData> {
+        match response {
+            generated::example::ExampleCommandResponse::TestCommand(ref data) => Some(data),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExampleTestEvent;
+
+impl Event for ExampleTestEvent {
+    type Component = generated::example::Example;
+    type Data = generated::example::CommandData;
+
+    const INDEX: EventIndex = 1;
+
+    fn get(update: &generated::example::ExampleUpdate) -> &[generated::example::CommandData] {
+        &update.test_event
+    }
+}
+
 impl Component for Example {
     type Update = generated::example::ExampleUpdate;
     type CommandRequest = generated::example::ExampleCommandRequest;
@@ -453,7 +688,13 @@ impl Component for Example {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::example::ExampleUpdate, String> {
-        <generated::example::ExampleUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::example::ExampleUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        let _event_test_event = events.field::<SchemaObject>(1);
+        for i in 0.._event_test_event.count() {
+            output.test_event.push(<generated::example::CommandData as TypeConversion>::from_type(&_event_test_event.index(i))?);
+        }
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::example::ExampleCommandRequest, String> {
@@ -485,6 +726,10 @@ impl Component for Example {
     fn to_update(update: &generated::example::ExampleUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::example::ExampleUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
+        for event in &update.test_event {
+            <generated::example::CommandData as TypeConversion>::to_type(event, &mut events.field::<SchemaObject>(1).add())?;
+        }
         Ok(serialized_update)
     }
 
@@ -627,7 +872,9 @@ impl Component for Rotate {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::example::RotateUpdate, String> {
-        <generated::example::RotateUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::example::RotateUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::example::RotateCommandRequest, String> {
@@ -651,6 +898,7 @@ impl Component for Rotate {
     fn to_update(update: &generated::example::RotateUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::example::RotateUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -1048,7 +1296,12 @@ impl Component for EntityAcl {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::improbable::EntityAclUpdate, String> {
-        <generated::improbable::EntityAclUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::improbable::EntityAclUpdate as TypeConversion>::from_type(&update.fields())?;
+        if update.is_field_cleared(2) {
+            output.component_write_acl = Some(Default::default());
+        }
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::improbable::EntityAclCommandRequest, String> {
@@ -1072,6 +1325,10 @@ impl Component for EntityAcl {
     fn to_update(update: &generated::improbable::EntityAclUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::improbable::EntityAclUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        if update.component_write_acl.as_ref().map_or(false, |value| value.is_empty()) {
+            serialized_update.add_cleared_field(2);
+        }
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -1176,7 +1433,12 @@ impl Component for Interest {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::improbable::InterestUpdate, String> {
-        <generated::improbable::InterestUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::improbable::InterestUpdate as TypeConversion>::from_type(&update.fields())?;
+        if update.is_field_cleared(1) {
+            output.component_interest = Some(Default::default());
+        }
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::improbable::InterestCommandRequest, String> {
@@ -1200,6 +1462,10 @@ impl Component for Interest {
     fn to_update(update: &generated::improbable::InterestUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::improbable::InterestUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        if update.component_interest.as_ref().map_or(false, |value| value.is_empty()) {
+            serialized_update.add_cleared_field(1);
+        }
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -1304,7 +1570,9 @@ impl Component for Metadata {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::improbable::MetadataUpdate, String> {
-        <generated::improbable::MetadataUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::improbable::MetadataUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::improbable::MetadataCommandRequest, String> {
@@ -1328,6 +1596,7 @@ impl Component for Metadata {
     fn to_update(update: &generated::improbable::MetadataUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::improbable::MetadataUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -1417,7 +1686,9 @@ impl Component for Persistence {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::improbable::PersistenceUpdate, String> {
-        <generated::improbable::PersistenceUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::improbable::PersistenceUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::improbable::PersistenceCommandRequest, String> {
@@ -1441,6 +1712,7 @@ impl Component for Persistence {
     fn to_update(update: &generated::improbable::PersistenceUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::improbable::PersistenceUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -1545,7 +1817,9 @@ impl Component for Position {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::improbable::PositionUpdate, String> {
-        <generated::improbable::PositionUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::improbable::PositionUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::improbable::PositionCommandRequest, String> {
@@ -1569,6 +1843,7 @@ impl Component for Position {
     fn to_update(update: &generated::improbable::PositionUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::improbable::PositionUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -1789,7 +2064,9 @@ impl Component for PlayerClient {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::improbable::restricted::PlayerClientUpdate, String> {
-        <generated::improbable::restricted::PlayerClientUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::improbable::restricted::PlayerClientUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::improbable::restricted::PlayerClientCommandRequest, String> {
@@ -1813,6 +2090,7 @@ impl Component for PlayerClient {
     fn to_update(update: &generated::improbable::restricted::PlayerClientUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::improbable::restricted::PlayerClientUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -1902,7 +2180,9 @@ impl Component for System {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::improbable::restricted::SystemUpdate, String> {
-        <generated::improbable::restricted::SystemUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::improbable::restricted::SystemUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::improbable::restricted::SystemCommandRequest, String> {
@@ -1926,6 +2206,7 @@ impl Component for System {
     fn to_update(update: &generated::improbable::restricted::SystemUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::improbable::restricted::SystemUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -2050,6 +2331,39 @@ pub enum WorkerCommandResponse {
     Disconnect(generated::improbable::restricted::DisconnectResponse),
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct WorkerDisconnect;
+
+impl Command for WorkerDisconnect {
+    type Component = generated::improbable::restricted::Worker;
+    type Request = generated::improbable::restricted::DisconnectRequest;
+    type Response = generated::improbable::restricted::DisconnectResponse;
+
+    const INDEX: CommandIndex = 1;
+
+    fn into_request(request: generated::improbable::restricted::DisconnectRequest) -> generated::improbable::restricted::WorkerCommandRequest {
+        generated::improbable::restricted::WorkerCommandRequest::Disconnect(request)
+    }
+
+    fn into_response(response: generated::improbable::restricted::DisconnectResponse) -> generated::improbable::restricted::WorkerCommandResponse {
+        generated::improbable::restricted::WorkerCommandResponse::Disconnect(response)
+    }
+
+    fn get_request(request: &generated::improbable::restricted::WorkerCommandRequest) -> Option<&generated::improbable::restricted::DisconnectRequest> {
+        match request {
+            generated::improbable::restricted::WorkerCommandRequest::Disconnect(ref data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn get_response(response: &generated::improbable::restricted::WorkerCommandResponse) -> Option<&generated::improbable::restricted::DisconnectResponse> {
+        match response {
+            generated::improbable::restricted::WorkerCommandResponse::Disconnect(ref data) => Some(data),
+            _ => None,
+        }
+    }
+}
+
 impl Component for Worker {
     type Update = generated::improbable::restricted::WorkerUpdate;
     type CommandRequest = generated::improbable::restricted::WorkerCommandRequest;
@@ -2062,7 +2376,9 @@ impl Component for Worker {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<generated::improbable::restricted::WorkerUpdate, String> {
-        <generated::improbable::restricted::WorkerUpdate as TypeConversion>::from_type(&update.fields())
+        let mut output = <generated::improbable::restricted::WorkerUpdate as TypeConversion>::from_type(&update.fields())?;
+        let events = update.events();
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<generated::improbable::restricted::WorkerCommandRequest, String> {
@@ -2094,6 +2410,7 @@ impl Component for Worker {
     fn to_update(update: &generated::improbable::restricted::WorkerUpdate) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
         <generated::improbable::restricted::WorkerUpdate as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        let events = serialized_update.events_mut();
         Ok(serialized_update)
     }
 
@@ -8,7 +8,7 @@ use spatialos_sdk::worker::{
     entity_builder::EntityBuilder,
     metrics::{HistogramMetric, Metrics},
     op::{StatusCode, WorkerOp},
-    query::{EntityQuery, QueryConstraint, ResultType},
+    query::{EntityQuery, Query, ResultType},
     {EntityId, InterestOverride, LogLevel},
 };
 use std::{collections::HashMap, f64};
@@ -242,17 +242,10 @@ fn check_for_flag(connection: &mut WorkerConnection, flag_name: &str) {
 
 fn send_query(c: &mut WorkerConnection) {
     let query = EntityQuery::new(
-        QueryConstraint::And(vec![
-            QueryConstraint::Or(vec![
-                QueryConstraint::Component(0),
-                QueryConstraint::Component(1),
-            ]),
-            QueryConstraint::And(vec![
-                QueryConstraint::Sphere(10.0, 10.0, 10.0, 250.0),
-                QueryConstraint::Not(Box::new(QueryConstraint::Component(2))),
-            ]),
-            QueryConstraint::EntityId(EntityId::new(10)),
-        ]),
+        (Query::has_component(0) | Query::has_component(1))
+            & Query::within_sphere((10.0, 10.0, 10.0), 250.0)
+            & !Query::has_component(2)
+            & Query::entity(EntityId::new(10)),
         ResultType::Count,
     );
 
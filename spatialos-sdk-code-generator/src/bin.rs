@@ -1,6 +1,6 @@
 extern crate spatialos_sdk_code_generator;
 
-use spatialos_sdk_code_generator::generator;
+use spatialos_sdk_code_generator::generator::{self, CodegenOptions};
 use spatialos_sdk_code_generator::schema_bundle;
 use std::env::args;
 use std::fs::File;
@@ -8,8 +8,14 @@ use std::io::Read;
 use std::io::Write;
 
 pub fn main() {
-    let input_filename = args().nth(1).unwrap();
-    let output_filename = args().nth(2).unwrap();
+    // Usage: generator [--serde] <input> <output>
+    let (flags, positional): (Vec<String>, Vec<String>) =
+        args().skip(1).partition(|arg| arg.starts_with("--"));
+    let input_filename = &positional[0];
+    let output_filename = &positional[1];
+    let options = CodegenOptions {
+        serde: flags.iter().any(|flag| flag == "--serde"),
+    };
 
     let mut input_file =
         File::open(input_filename).expect("Unable to open the test schema bundle.");
@@ -17,7 +23,10 @@ pub fn main() {
     input_file
         .read_to_string(&mut contents)
         .expect("Unable to read the test schema bundle");
-    let generated_file = generator::generate_code(schema_bundle::load_bundle(&contents).unwrap());
+    let generated_file = generator::generate_code_with_options(
+        schema_bundle::load_bundle(&contents).unwrap(),
+        options,
+    );
     let mut output_file = File::create(output_filename).unwrap();
     output_file.write_all(generated_file.as_bytes()).unwrap();
 }
@@ -8,7 +8,7 @@ use <#= vec!["super".to_string(); self.depth() + 1].join("::") #>::generated as
 let enum_def = self.get_enum_definition(enum_name);
 let enum_rust_name = self.rust_name(&enum_def.qualified_name);
 #>
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+<#= self.derive_attribute(&["Debug", "Clone", "Copy", "PartialEq", "Eq", "Hash"]) #>
 pub enum <#= enum_rust_name #> {
 <# for enum_value in &enum_def.values { #>
     <#= enum_value.name #>,<# } #>
@@ -34,11 +34,11 @@ impl <#= enum_rust_name #> {
 }
 <# } #>
 /* Types. */<# for type_name in &self.types { let type_def = self.get_type_definition(type_name); #>
-#[derive(Debug, Clone)]
+<#= self.derive_attribute(&["Debug", "Clone"]) #>
 pub struct <#= self.rust_name(&type_def.qualified_name) #> {<#
     for field in &type_def.fields {
     #>
-    pub <#= field.name #>: <#= self.generate_field_type(field) #>,<# } #>
+    <#= self.field_serde_attributes(field) #>pub <#= field.name #>: <#= self.generate_field_type(field) #>,<# } #>
 }
 impl TypeConversion for <#= self.rust_name(&type_def.qualified_name) #> {
     fn from_type(input: &SchemaObject) -> Result<Self, String> {
@@ -65,11 +65,11 @@ impl TypeConversion for <#= self.rust_name(&type_def.qualified_name) #> {
 /* Components. */ <# for component_name in &self.components {
     let component = self.get_component_definition(component_name);
     let component_fields = self.get_component_fields(&component); #>
-#[derive(Debug, Clone)]
+<#= self.derive_attribute(&["Debug", "Clone"]) #>
 pub struct <#= self.rust_name(&component.qualified_name) #> {<#
     for field in &component_fields {
     #>
-    pub <#= field.name #>: <#= self.generate_field_type(field) #>,<# } #>
+    <#= self.field_serde_attributes(field) #>pub <#= field.name #>: <#= self.generate_field_type(field) #>,<# } #>
 }
 impl TypeConversion for <#= self.rust_name(&component.qualified_name) #> {
     fn from_type(input: &SchemaObject) -> Result<Self, String> {
@@ -100,18 +100,24 @@ impl ComponentData<<#= self.rust_name(&component.qualified_name) #>> for <#= sel
     }
 }
 
-#[derive(Debug, Clone, Default)]
+<#= self.derive_attribute(&["Debug", "Clone", "Default"]) #>
 pub struct <#= self.rust_name(&component.qualified_name) #>Update {<#
     for field in &component_fields {
     #>
-    pub <#= field.name #>: Option<<#= self.generate_field_type(field) #>>,<# } #>
+    <#= self.update_field_serde_attributes(field) #>pub <#= field.name #>: Option<<#= self.generate_field_type(field) #>>,<# } #><#
+    for event in &component.events {
+    #>
+    <#= self.update_event_serde_attributes() #>pub <#= event.name #>: Vec<<#= self.rust_fqname(&event.type_reference) #>>,<# } #>
 }
 impl TypeConversion for <#= self.rust_name(&component.qualified_name) #>Update {
     fn from_type(input: &SchemaObject) -> Result<Self, String> {
         let mut output = Self {<#
             for field in &component_fields {
             #>
-            <#= field.name #>: None,<# } #>
+            <#= field.name #>: None,<# } #><#
+            for event in &component.events {
+            #>
+            <#= event.name #>: Vec::new(),<# } #>
         };<#
         for field in &component_fields {
         #>
@@ -140,24 +146,76 @@ impl ComponentUpdate<<#= self.rust_name(&component.qualified_name) #>> for <#= s
     fn merge(&mut self, update: <#= self.rust_name(&component.qualified_name) #>Update) {<#
         for field in &self.get_component_fields(&component) {
         #>
-        if update.<#= field.name #>.is_some() { self.<#= field.name #> = update.<#= field.name #>; }<# } #>
+        if update.<#= field.name #>.is_some() { self.<#= field.name #> = update.<#= field.name #>; }<# } #><#
+        for event in &component.events {
+        #>
+        self.<#= event.name #>.extend(update.<#= event.name #>);<# } #>
     }
 }
 
-#[derive(Debug, Clone)]
+<#= self.derive_attribute(&["Debug", "Clone"]) #>
 pub enum <#= self.rust_name(&component.qualified_name) #>CommandRequest {<#
     for command in &component.commands {
     #>
     <#= command.name.to_camel_case() #>(<#= self.rust_fqname(&command.request_type) #>),<# } #>
 }
 
-#[derive(Debug, Clone)]
+<#= self.derive_attribute(&["Debug", "Clone"]) #>
 pub enum <#= self.rust_name(&component.qualified_name) #>CommandResponse {<#
     for command in &component.commands {
     #>
     <#= command.name.to_camel_case() #>(<#= self.rust_fqname(&command.response_type) #>),<# } #>
 }
+<# for command in &component.commands {
+    let command_rust_name = format!("{}{}", self.rust_name(&component.qualified_name), command.name.to_camel_case()); #>
+#[derive(Debug, Clone, Copy)]
+pub struct <#= command_rust_name #>;
+
+impl Command for <#= command_rust_name #> {
+    type Component = <#= self.rust_fqname(&component.qualified_name) #>;
+    type Request = <#= self.rust_fqname(&command.request_type) #>;
+    type Response = <#= self.rust_fqname(&command.response_type) #>;
+
+    const INDEX: CommandIndex = <#= command.command_index #>;
+
+    fn into_request(request: <#= self.rust_fqname(&command.request_type) #>) -> <#= self.rust_fqname(&component.qualified_name) #>CommandRequest {
+        <#= self.rust_fqname(&component.qualified_name) #>CommandRequest::<#= command.name.to_camel_case() #>(request)
+    }
+
+    fn into_response(response: <#= self.rust_fqname(&command.response_type) #>) -> <#= self.rust_fqname(&component.qualified_name) #>CommandResponse {
+        <#= self.rust_fqname(&component.qualified_name) #>CommandResponse::<#= command.name.to_camel_case() #>(response)
+    }
+
+    fn get_request(request: &<#= self.rust_fqname(&component.qualified_name) #>CommandRequest) -> Option<&<#= self.rust_fqname(&command.request_type) #>> {
+        match request {
+            <#= self.rust_fqname(&component.qualified_name) #>CommandRequest::<#= command.name.to_camel_case() #>(ref data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn get_response(response: &<#= self.rust_fqname(&component.qualified_name) #>CommandResponse) -> Option<&<#= self.rust_fqname(&command.response_type) #>> {
+        match response {
+            <#= self.rust_fqname(&component.qualified_name) #>CommandResponse::<#= command.name.to_camel_case() #>(ref data) => Some(data),
+            _ => None,
+        }
+    }
+}
+<# } #><# for event in &component.events {
+    let event_rust_name = format!("{}{}", self.rust_name(&component.qualified_name), event.name.to_camel_case()); #>
+#[derive(Debug, Clone, Copy)]
+pub struct <#= event_rust_name #>;
+
+impl Event for <#= event_rust_name #> {
+    type Component = <#= self.rust_fqname(&component.qualified_name) #>;
+    type Data = <#= self.rust_fqname(&event.type_reference) #>;
 
+    const INDEX: EventIndex = <#= event.event_index #>;
+
+    fn get(update: &<#= self.rust_fqname(&component.qualified_name) #>Update) -> &[<#= self.rust_fqname(&event.type_reference) #>] {
+        &update.<#= event.name #>
+    }
+}
+<# } #>
 impl Component for <#= self.rust_name(&component.qualified_name) #> {
     type Update = <#= self.rust_fqname(&component.qualified_name) #>Update;
     type CommandRequest = <#= self.rust_fqname(&component.qualified_name) #>CommandRequest;
@@ -170,7 +228,21 @@ impl Component for <#= self.rust_name(&component.qualified_name) #> {
     }
 
     fn from_update(update: &SchemaComponentUpdate) -> Result<<#= self.rust_fqname(&component.qualified_name) #>Update, String> {
-        <<#= self.rust_fqname(&component.qualified_name) #>Update as TypeConversion>::from_type(&update.fields())
+        let mut output = <<#= self.rust_fqname(&component.qualified_name) #>Update as TypeConversion>::from_type(&update.fields())?;<#
+        for field in &component_fields {
+            if self.cleared_field_condition(field, "").is_some() {
+        #>
+        if update.is_field_cleared(<#= field.field_id #>) {
+            output.<#= field.name #> = Some(Default::default());
+        }<# } } #>
+        let events = update.events();<#
+        for event in &component.events {
+        #>
+        let _event_<#= event.name #> = events.field::<SchemaObject>(<#= event.event_index #>);
+        for i in 0.._event_<#= event.name #>.count() {
+            output.<#= event.name #>.push(<<#= self.rust_fqname(&event.type_reference) #> as TypeConversion>::from_type(&_event_<#= event.name #>.index(i))?);
+        }<# } #>
+        Ok(output)
     }
 
     fn from_request(command_index: CommandIndex, request: &SchemaCommandRequest) -> Result<<#= self.rust_fqname(&component.qualified_name) #>CommandRequest, String> {
@@ -205,7 +277,19 @@ impl Component for <#= self.rust_name(&component.qualified_name) #> {
 
     fn to_update(update: &<#= self.rust_fqname(&component.qualified_name) #>Update) -> Result<SchemaComponentUpdate, String> {
         let mut serialized_update = SchemaComponentUpdate::new();
-        <<#= self.rust_fqname(&component.qualified_name) #>Update as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;
+        <<#= self.rust_fqname(&component.qualified_name) #>Update as TypeConversion>::to_type(update, &mut serialized_update.fields_mut())?;<#
+        for field in &component_fields {
+            if let Some(condition) = self.cleared_field_condition(field, &format!("update.{}", field.name)) {
+        #>
+        if <#= condition #> {
+            serialized_update.add_cleared_field(<#= field.field_id #>);
+        }<# } } #>
+        let events = serialized_update.events_mut();<#
+        for event in &component.events {
+        #>
+        for event in &update.<#= event.name #> {
+            <<#= self.rust_fqname(&event.type_reference) #> as TypeConversion>::to_type(event, &mut events.field::<SchemaObject>(<#= event.event_index #>).add())?;
+        }<# } #>
         Ok(serialized_update)
     }
 
@@ -46,6 +46,14 @@ fn get_field_schema_type(field: &FieldDefinition) -> &str {
     }
 }
 
+/// Options which control the shape of the generated code.
+#[derive(Debug, Clone, Default)]
+pub struct CodegenOptions {
+    /// Derive `Serialize` and `Deserialize` for generated types, enums, component updates and command
+    /// enums. Code generated with this option requires the `serde` feature of `spatialos-sdk`.
+    pub serde: bool,
+}
+
 #[derive(Debug, Template)]
 #[TemplatePath = "./src/generated_code_mod.tt.rs"]
 struct Package {
@@ -195,6 +203,85 @@ impl Package {
         }
     }
 
+    fn serde_enabled(&self) -> bool {
+        self.generated_code.borrow().options.serde
+    }
+
+    // Generates the derive attribute for a generated type, adding the serde traits if they have been
+    // enabled in the codegen options.
+    fn derive_attribute(&self, traits: &[&str]) -> String {
+        let mut traits = traits.to_vec();
+        if !self.serde_enabled() {
+            return format!("#[derive({})]", traits.join(", "));
+        }
+        traits.push("spatialos_sdk::serde::Serialize");
+        traits.push("spatialos_sdk::serde::Deserialize");
+        format!(
+            "#[derive({})]\n#[serde(crate = \"spatialos_sdk::serde\")]",
+            traits.join(", ")
+        )
+    }
+
+    // Maps are serialized as a sequence of key-value pairs if their keys can't be represented as
+    // strings, as many formats (such as JSON) only support string keys.
+    fn map_needs_pairs(&self, field: &FieldDefinition) -> bool {
+        match field.field_type {
+            FieldDefinition_FieldType::Map { ref key_type, .. } => match key_type {
+                TypeReference::Primitive(ref primitive) => matches!(
+                    primitive,
+                    PrimitiveType::Float
+                        | PrimitiveType::Double
+                        | PrimitiveType::Bytes
+                        | PrimitiveType::Entity
+                ),
+                TypeReference::Enum(_) => false,
+                TypeReference::Type(_) => true,
+            },
+            _ => false,
+        }
+    }
+
+    // Generates the serde attributes (followed by indentation) for a field of a type or component.
+    fn field_serde_attributes(&self, field: &FieldDefinition) -> String {
+        if self.serde_enabled() && self.map_needs_pairs(field) {
+            "#[serde(with = \"spatialos_sdk::worker::serde_support::map_as_pairs\")]\n    "
+                .to_string()
+        } else {
+            "".to_string()
+        }
+    }
+
+    // Generates the serde attributes (followed by indentation) for a field of a component update.
+    // Fields which are not set are omitted, and a `null` option field is distinguished from an
+    // option field which has not been set.
+    fn update_field_serde_attributes(&self, field: &FieldDefinition) -> String {
+        if !self.serde_enabled() {
+            return "".to_string();
+        }
+        let with = match field.field_type {
+            FieldDefinition_FieldType::Option { .. } => {
+                ", with = \"spatialos_sdk::worker::serde_support::double_option\""
+            }
+            FieldDefinition_FieldType::Map { .. } if self.map_needs_pairs(field) => {
+                ", with = \"spatialos_sdk::worker::serde_support::option_map_as_pairs\""
+            }
+            _ => "",
+        };
+        format!(
+            "#[serde(default, skip_serializing_if = \"Option::is_none\"{})]\n    ",
+            with
+        )
+    }
+
+    // Generates the serde attributes (followed by indentation) for an event of a component update.
+    fn update_event_serde_attributes(&self) -> String {
+        if self.serde_enabled() {
+            "#[serde(default, skip_serializing_if = \"Vec::is_empty\")]\n    ".to_string()
+        } else {
+            "".to_string()
+        }
+    }
+
     // Some fields need to be borrowed when serializing (such as strings or objects). This helper function returns true
     // if this is required.
     fn field_needs_borrow(&self, field: &FieldDefinition) -> bool {
@@ -348,6 +435,23 @@ impl Package {
         }
     }
 
+    // Option, list and map fields can't be set to the empty value through the fields object of a component
+    // update, and are instead added to the update's list of cleared fields. For these fields, this helper generates
+    // a condition on an update field expression (of type Option<T>) that is true if the field is being cleared.
+    fn cleared_field_condition(&self, field: &FieldDefinition, expression: &str) -> Option<String> {
+        let empty_check = match field.field_type {
+            FieldDefinition_FieldType::Singular { .. } => return None,
+            FieldDefinition_FieldType::Option { .. } => "is_none",
+            FieldDefinition_FieldType::List { .. } | FieldDefinition_FieldType::Map { .. } => {
+                "is_empty"
+            }
+        };
+        Some(format!(
+            "{}.as_ref().map_or(false, |value| value.{}())",
+            expression, empty_check
+        ))
+    }
+
     // Generates an expression which deserializes a field from a schema field 'schema_field'.
     fn deserialize_field(&self, field: &FieldDefinition, schema_field: &str) -> String {
         match field.field_type {
@@ -428,6 +532,7 @@ impl Package {
 
 #[derive(Debug)]
 struct GeneratedCode {
+    options: CodegenOptions,
     root_package: Option<Package>,
     packages: BTreeSet<String>,
     enums: BTreeMap<String, EnumDefinition>,
@@ -523,8 +628,13 @@ fn generate_module(package: &Package) -> String {
 }
 
 pub fn generate_code(bundle: SchemaBundle) -> String {
+    generate_code_with_options(bundle, CodegenOptions::default())
+}
+
+pub fn generate_code_with_options(bundle: SchemaBundle, options: CodegenOptions) -> String {
     // Set up the root package.
     let generated_code = Rc::new(RefCell::new(GeneratedCode {
+        options,
         root_package: None,
         packages: BTreeSet::new(),
         enums: BTreeMap::new(),
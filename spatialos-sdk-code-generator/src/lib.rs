@@ -1,17 +1,10 @@
-#[macro_use]
-extern crate serde_derive;
-
-extern crate serde;
-extern crate serde_json;
-
 #[macro_use]
 extern crate t4rust_derive;
 
 extern crate heck;
+pub extern crate spatialos_sdk_schema_bundle as schema_bundle;
 
 pub mod generator;
-#[allow(non_camel_case_types)]
-pub mod schema_bundle;
 
 #[cfg(test)]
 mod tests {
@@ -40,4 +33,23 @@ mod tests {
             generator::generate_code(bundle.unwrap())
         );
     }
+
+    #[test]
+    fn serde_derives_are_opt_in() {
+        let mut file =
+            File::open("data/test.sb.json").expect("Unable to open the test schema bundle.");
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)
+            .expect("Unable to read the test schema bundle");
+
+        let default_code = generator::generate_code(schema_bundle::load_bundle(&contents).unwrap());
+        assert!(!default_code.contains("serde"));
+
+        let serde_code = generator::generate_code_with_options(
+            schema_bundle::load_bundle(&contents).unwrap(),
+            generator::CodegenOptions { serde: true },
+        );
+        assert!(serde_code.contains("spatialos_sdk::serde::Serialize"));
+        assert!(serde_code.contains("skip_serializing_if = \"Option::is_none\""));
+    }
 }
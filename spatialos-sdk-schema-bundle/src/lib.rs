@@ -1,4 +1,11 @@
-use serde::{Deserialize, Deserializer};
+//! The types of a schema bundle, the JSON description of a set of schema files produced by
+//! the schema compiler.
+//!
+//! These are shared by the code generator and by the SDK's dynamic schema support.
+
+#![allow(non_camel_case_types)]
+
+use serde::{Deserialize, Deserializer, Serialize};
 
 fn empty_string_is_none<'de, D>(d: D) -> Result<Option<String>, D::Error>
 where
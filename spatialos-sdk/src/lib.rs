@@ -2,5 +2,9 @@
 
 extern crate spatialos_sdk_sys;
 
+// Re-exported so that generated code can derive serde traits without depending on serde directly.
+#[cfg(feature = "serde")]
+pub use serde;
+
 pub(crate) mod ptr;
 pub mod worker;
@@ -1,6 +1,14 @@
+use crate::worker::entity::Entity;
 use crate::worker::internal::schema;
+use crate::worker::EntityId;
 use spatialos_sdk_sys::worker::*;
-use std::{collections::hash_map::HashMap, mem, os::raw, ptr, sync::Arc};
+use std::{
+    collections::hash_map::HashMap,
+    fmt, mem,
+    os::raw,
+    ptr,
+    sync::{Arc, RwLock},
+};
 
 // Re-export inventory so generated code doesn't require the user to add inventory to their
 // Cargo.toml
@@ -8,6 +16,7 @@ pub use inventory;
 
 pub type ComponentId = Worker_ComponentId;
 pub type CommandIndex = Worker_CommandIndex;
+pub type EventIndex = u32;
 
 pub trait ComponentUpdate<C: Component> {
     fn merge(&mut self, update: Self);
@@ -29,11 +38,11 @@ where
 // A trait that's implemented by a component to convert to/from schema handle types.
 pub trait Component
 where
-    Self: std::marker::Sized,
+    Self: std::marker::Sized + Send + Sync,
 {
-    type Update;
-    type CommandRequest;
-    type CommandResponse;
+    type Update: Send + Sync;
+    type CommandRequest: Send + Sync;
+    type CommandResponse: Send + Sync;
 
     const ID: ComponentId;
 
@@ -59,6 +68,118 @@ where
     fn get_response_command_index(response: &Self::CommandResponse) -> u32;
 }
 
+/// A set of component types: either a single component, or a tuple of up to eight components.
+///
+/// Used to select components by type rather than by ID, e.g. with
+/// [`ResultType::snapshot_of`](../query/enum.ResultType.html#method.snapshot_of).
+pub trait ComponentSet {
+    /// Returns the IDs of the components in the set, in order.
+    fn component_ids() -> Vec<ComponentId>;
+}
+
+impl<C: Component> ComponentSet for C {
+    fn component_ids() -> Vec<ComponentId> {
+        vec![C::ID]
+    }
+}
+
+/// A [`ComponentSet`] whose components can be borrowed from an [`Entity`] together, e.g. to
+/// read the results of an entity query.
+///
+/// [`ComponentSet`]: trait.ComponentSet.html
+/// [`Entity`]: ../entity/struct.Entity.html
+pub trait ComponentRefs<'a>: ComponentSet {
+    /// The entity ID followed by a reference to each component, e.g.
+    /// `(EntityId, &'a A, &'a B)` for the set `(A, B)`.
+    type Item;
+
+    /// Borrows the components from `entity`, or returns the ID of the first one it's missing.
+    fn get(entity_id: EntityId, entity: &'a Entity) -> Result<Self::Item, ComponentId>;
+}
+
+impl<'a, C: Component + 'a> ComponentRefs<'a> for C {
+    type Item = (EntityId, &'a C);
+
+    fn get(entity_id: EntityId, entity: &'a Entity) -> Result<Self::Item, ComponentId> {
+        Ok((entity_id, entity.get::<C>().ok_or(C::ID)?))
+    }
+}
+
+macro_rules! impl_component_set_for_tuple {
+    ($($component:ident),+) => {
+        impl<$($component: Component),+> ComponentSet for ($($component,)+) {
+            fn component_ids() -> Vec<ComponentId> {
+                vec![$($component::ID),+]
+            }
+        }
+
+        impl<'a, $($component: Component + 'a),+> ComponentRefs<'a> for ($($component,)+) {
+            type Item = (EntityId, $(&'a $component),+);
+
+            fn get(entity_id: EntityId, entity: &'a Entity) -> Result<Self::Item, ComponentId> {
+                Ok((
+                    entity_id,
+                    $(entity.get::<$component>().ok_or($component::ID)?),+
+                ))
+            }
+        }
+    };
+}
+
+impl_component_set_for_tuple!(A);
+impl_component_set_for_tuple!(A, B);
+impl_component_set_for_tuple!(A, B, C);
+impl_component_set_for_tuple!(A, B, C, D);
+impl_component_set_for_tuple!(A, B, C, D, E);
+impl_component_set_for_tuple!(A, B, C, D, E, F);
+impl_component_set_for_tuple!(A, B, C, D, E, F, G);
+impl_component_set_for_tuple!(A, B, C, D, E, F, G, H);
+
+/// A single command of a component, with statically known request and response types.
+///
+/// The code generator emits a marker type implementing `Command` for every command in the
+/// schema, named after the component and the command (e.g. `ExampleTestCommand` for the
+/// `test_command` command of the `Example` component). It converts between the command's
+/// request and response types and the component's `CommandRequest` and `CommandResponse`
+/// enums, so that the variant for the command doesn't need to be matched by hand.
+pub trait Command {
+    type Component: Component + 'static;
+    type Request;
+    type Response;
+
+    const INDEX: CommandIndex;
+
+    fn into_request(request: Self::Request) -> <Self::Component as Component>::CommandRequest;
+    fn into_response(response: Self::Response) -> <Self::Component as Component>::CommandResponse;
+
+    /// Returns the request if it belongs to this command.
+    fn get_request(
+        request: &<Self::Component as Component>::CommandRequest,
+    ) -> Option<&Self::Request>;
+
+    /// Returns the response if it belongs to this command.
+    fn get_response(
+        response: &<Self::Component as Component>::CommandResponse,
+    ) -> Option<&Self::Response>;
+}
+
+/// A single event of a component.
+///
+/// The code generator emits a marker type implementing `Event` for every event in the
+/// schema, named after the component and the event (e.g. `ExampleTestEvent` for the
+/// `test_event` event of the `Example` component). Events are sent and received as part
+/// of the component's `Update` type, which has a list of triggered events for each event
+/// in the schema.
+pub trait Event {
+    type Component: Component + 'static;
+    type Data;
+
+    const INDEX: EventIndex;
+
+    /// Returns the events of this type that were triggered in `update`.
+    fn get(update: &<Self::Component as Component>::Update) -> &[Self::Data];
+}
+
 /// Additional parameters for sending component updates.
 ///
 /// Additional parameters passed to [`Connection::send_component_update`]. Note that
@@ -125,9 +246,20 @@ impl UpdateParameters {
 pub(crate) mod internal {
     use crate::worker::internal::schema::*;
     use spatialos_sdk_sys::worker::*;
-    use std::marker::PhantomData;
+    use std::{cell::OnceCell, marker::PhantomData};
 
-    use crate::worker::component::ComponentId;
+    use crate::worker::component::{
+        Component, ComponentId, ComponentRegistry, HandleKind, UserHandle,
+    };
+
+    fn deserialized_handle(
+        deserialized: &OnceCell<Result<UserHandle, String>>,
+    ) -> Option<UserHandle> {
+        deserialized
+            .get()
+            .and_then(|result| result.as_ref().ok())
+            .cloned()
+    }
 
     #[derive(Debug)]
     pub struct ComponentData<'a> {
@@ -139,10 +271,67 @@ pub(crate) mod internal {
         // type-erased pointer that may be null, so we just mark that we're borrowing
         // *something*.
         pub _marker: PhantomData<&'a ()>,
+
+        // Set the first time the data is accessed if `user_handle` is null, in which case
+        // it is deserialized from `schema_type` instead. Holds the error if that failed.
+        pub(crate) deserialized: OnceCell<Result<UserHandle, String>>,
+
+        // The vtable that the connection's registry holds for the component, which is the
+        // one the C API used to create `user_handle`.
+        pub(crate) vtable: Option<Worker_ComponentVtable>,
     }
 
-    impl<'a> From<&'a Worker_ComponentData> for ComponentData<'a> {
-        fn from(data: &Worker_ComponentData) -> Self {
+    impl<'a> ComponentData<'a> {
+        /// Returns the data as component `C`.
+        ///
+        /// If the C API didn't deserialize the object with one of our vtables, it is
+        /// deserialized from the schema object on first access and cached. Returns `None` if
+        /// that fails, as well as if the data belongs to a different component or has no
+        /// payload; use [`try_get`](#method.try_get) to tell these apart.
+        pub fn get<C: Component>(&self) -> Option<&C> {
+            self.try_get::<C>().ok().flatten()
+        }
+
+        /// Like [`get`](#method.get), but returns the error from `C::from_data` if the data
+        /// had to be deserialized from the schema object and that failed.
+        pub fn try_get<C: Component>(&self) -> Result<Option<&C>, String> {
+            if C::ID != self.component_id {
+                return Ok(None);
+            }
+
+            if !self.user_handle.is_null() {
+                return Ok(Some(unsafe { &*(self.user_handle as *const _) }));
+            }
+
+            if self.schema_type.internal.is_null() {
+                return Ok(None);
+            }
+            let deserialized = self.deserialized.get_or_init(|| {
+                C::from_data(&self.schema_type).map(UserHandle::component_data::<C>)
+            });
+            match deserialized {
+                Ok(handle) => Ok(unsafe { handle.get::<C, _>() }),
+                Err(message) => Err(message.clone()),
+            }
+        }
+
+        /// Takes a new reference to the object, either through `user_handle` or to the copy
+        /// deserialized by [`get`](#method.get). Returns `None` if neither is available.
+        pub(crate) fn to_owned_handle(&self) -> Option<UserHandle> {
+            unsafe {
+                UserHandle::copy_from(
+                    HandleKind::ComponentData,
+                    self.component_id,
+                    self.user_handle as *const _,
+                    self.vtable,
+                )
+            }
+            .or_else(|| deserialized_handle(&self.deserialized))
+        }
+
+        /// Wraps an object owned by the C API, looking up the component's vtable in
+        /// `registry`.
+        pub(crate) fn new(data: &'a Worker_ComponentData, registry: &ComponentRegistry) -> Self {
             ComponentData {
                 component_id: data.component_id,
                 schema_type: SchemaComponentData {
@@ -150,6 +339,8 @@ pub(crate) mod internal {
                 },
                 user_handle: data.user_handle,
                 _marker: PhantomData,
+                deserialized: OnceCell::new(),
+                vtable: registry.get_vtable(data.component_id).cloned(),
             }
         }
     }
@@ -164,10 +355,70 @@ pub(crate) mod internal {
         // type-erased pointer that may be null, so we just mark that we're borrowing
         // *something*.
         pub _marker: PhantomData<&'a ()>,
+
+        // Set the first time the data is accessed if `user_handle` is null, in which case
+        // it is deserialized from `schema_type` instead. Holds the error if that failed.
+        pub(crate) deserialized: OnceCell<Result<UserHandle, String>>,
+
+        // The vtable that the connection's registry holds for the component, which is the
+        // one the C API used to create `user_handle`.
+        pub(crate) vtable: Option<Worker_ComponentVtable>,
     }
 
-    impl<'a> From<&'a Worker_ComponentUpdate> for ComponentUpdate<'a> {
-        fn from(update: &Worker_ComponentUpdate) -> Self {
+    impl<'a> ComponentUpdate<'a> {
+        /// Returns the update as an update for component `C`.
+        ///
+        /// If the C API didn't deserialize the object with one of our vtables, it is
+        /// deserialized from the schema object on first access and cached. Returns `None` if
+        /// that fails, as well as if the update belongs to a different component or has no
+        /// payload; use [`try_get`](#method.try_get) to tell these apart.
+        pub fn get<C: Component>(&self) -> Option<&C::Update> {
+            self.try_get::<C>().ok().flatten()
+        }
+
+        /// Like [`get`](#method.get), but returns the error from `C::from_update` if the update
+        /// had to be deserialized from the schema object and that failed.
+        pub fn try_get<C: Component>(&self) -> Result<Option<&C::Update>, String> {
+            if C::ID != self.component_id {
+                return Ok(None);
+            }
+
+            if !self.user_handle.is_null() {
+                return Ok(Some(unsafe { &*(self.user_handle as *const _) }));
+            }
+
+            if self.schema_type.internal.is_null() {
+                return Ok(None);
+            }
+            let deserialized = self.deserialized.get_or_init(|| {
+                C::from_update(&self.schema_type).map(UserHandle::component_update::<C>)
+            });
+            match deserialized {
+                Ok(handle) => Ok(unsafe { handle.get::<C, _>() }),
+                Err(message) => Err(message.clone()),
+            }
+        }
+
+        /// Takes a new reference to the object, either through `user_handle` or to the copy
+        /// deserialized by [`get`](#method.get). Returns `None` if neither is available.
+        pub(crate) fn to_owned_handle(&self) -> Option<UserHandle> {
+            unsafe {
+                UserHandle::copy_from(
+                    HandleKind::ComponentUpdate,
+                    self.component_id,
+                    self.user_handle as *const _,
+                    self.vtable,
+                )
+            }
+            .or_else(|| deserialized_handle(&self.deserialized))
+        }
+
+        /// Wraps an object owned by the C API, looking up the component's vtable in
+        /// `registry`.
+        pub(crate) fn new(
+            update: &'a Worker_ComponentUpdate,
+            registry: &ComponentRegistry,
+        ) -> Self {
             ComponentUpdate {
                 component_id: update.component_id,
                 schema_type: SchemaComponentUpdate {
@@ -175,6 +426,8 @@ pub(crate) mod internal {
                 },
                 user_handle: update.user_handle,
                 _marker: PhantomData,
+                deserialized: OnceCell::new(),
+                vtable: registry.get_vtable(update.component_id).cloned(),
             }
         }
     }
@@ -190,10 +443,71 @@ pub(crate) mod internal {
         // type-erased pointer that may be null, so we just mark that we're borrowing
         // *something*.
         pub _marker: PhantomData<&'a ()>,
+
+        // Set the first time the data is accessed if `user_handle` is null, in which case
+        // it is deserialized from `schema_type` instead. Holds the error if that failed.
+        pub(crate) deserialized: OnceCell<Result<UserHandle, String>>,
+
+        // The vtable that the connection's registry holds for the component, which is the
+        // one the C API used to create `user_handle`.
+        pub(crate) vtable: Option<Worker_ComponentVtable>,
     }
 
-    impl<'a> From<&'a Worker_CommandRequest> for CommandRequest<'a> {
-        fn from(request: &Worker_CommandRequest) -> Self {
+    impl<'a> CommandRequest<'a> {
+        /// Returns the request as a command request for component `C`.
+        ///
+        /// If the C API didn't deserialize the object with one of our vtables, it is
+        /// deserialized from the schema object on first access and cached. Returns `None` if
+        /// that fails, as well as if the request belongs to a different component or has no
+        /// payload; use [`try_get`](#method.try_get) to tell these apart.
+        pub fn get<C: Component>(&self) -> Option<&C::CommandRequest> {
+            self.try_get::<C>().ok().flatten()
+        }
+
+        /// Like [`get`](#method.get), but returns the error from `C::from_request` if the request
+        /// had to be deserialized from the schema object and that failed.
+        pub fn try_get<C: Component>(&self) -> Result<Option<&C::CommandRequest>, String> {
+            if C::ID != self.component_id {
+                return Ok(None);
+            }
+
+            if !self.user_handle.is_null() {
+                return Ok(Some(unsafe { &*(self.user_handle as *const _) }));
+            }
+
+            if self.schema_type.internal.is_null() {
+                return Ok(None);
+            }
+            let deserialized = self.deserialized.get_or_init(|| {
+                C::from_request(self.command_index, &self.schema_type)
+                    .map(UserHandle::command_request::<C>)
+            });
+            match deserialized {
+                Ok(handle) => Ok(unsafe { handle.get::<C, _>() }),
+                Err(message) => Err(message.clone()),
+            }
+        }
+
+        /// Takes a new reference to the object, either through `user_handle` or to the copy
+        /// deserialized by [`get`](#method.get). Returns `None` if neither is available.
+        pub(crate) fn to_owned_handle(&self) -> Option<UserHandle> {
+            unsafe {
+                UserHandle::copy_from(
+                    HandleKind::CommandRequest(self.command_index),
+                    self.component_id,
+                    self.user_handle as *const _,
+                    self.vtable,
+                )
+            }
+            .or_else(|| deserialized_handle(&self.deserialized))
+        }
+
+        /// Wraps an object owned by the C API, looking up the component's vtable in
+        /// `registry`.
+        pub(crate) fn new(
+            request: &'a Worker_CommandRequest,
+            registry: &ComponentRegistry,
+        ) -> Self {
             CommandRequest {
                 component_id: request.component_id,
                 command_index: request.command_index,
@@ -202,6 +516,8 @@ pub(crate) mod internal {
                 },
                 user_handle: request.user_handle,
                 _marker: PhantomData,
+                deserialized: OnceCell::new(),
+                vtable: registry.get_vtable(request.component_id).cloned(),
             }
         }
     }
@@ -217,10 +533,71 @@ pub(crate) mod internal {
         // type-erased pointer that may be null, so we just mark that we're borrowing
         // *something*.
         pub _marker: PhantomData<&'a ()>,
+
+        // Set the first time the data is accessed if `user_handle` is null, in which case
+        // it is deserialized from `schema_type` instead. Holds the error if that failed.
+        pub(crate) deserialized: OnceCell<Result<UserHandle, String>>,
+
+        // The vtable that the connection's registry holds for the component, which is the
+        // one the C API used to create `user_handle`.
+        pub(crate) vtable: Option<Worker_ComponentVtable>,
     }
 
-    impl<'a> From<&'a Worker_CommandResponse> for CommandResponse<'a> {
-        fn from(response: &Worker_CommandResponse) -> Self {
+    impl<'a> CommandResponse<'a> {
+        /// Returns the response as a command response for component `C`.
+        ///
+        /// If the C API didn't deserialize the object with one of our vtables, it is
+        /// deserialized from the schema object on first access and cached. Returns `None` if
+        /// that fails, as well as if the response belongs to a different component or has no
+        /// payload; use [`try_get`](#method.try_get) to tell these apart.
+        pub fn get<C: Component>(&self) -> Option<&C::CommandResponse> {
+            self.try_get::<C>().ok().flatten()
+        }
+
+        /// Like [`get`](#method.get), but returns the error from `C::from_response` if the response
+        /// had to be deserialized from the schema object and that failed.
+        pub fn try_get<C: Component>(&self) -> Result<Option<&C::CommandResponse>, String> {
+            if C::ID != self.component_id {
+                return Ok(None);
+            }
+
+            if !self.user_handle.is_null() {
+                return Ok(Some(unsafe { &*(self.user_handle as *const _) }));
+            }
+
+            if self.schema_type.internal.is_null() {
+                return Ok(None);
+            }
+            let deserialized = self.deserialized.get_or_init(|| {
+                C::from_response(self.command_index, &self.schema_type)
+                    .map(UserHandle::command_response::<C>)
+            });
+            match deserialized {
+                Ok(handle) => Ok(unsafe { handle.get::<C, _>() }),
+                Err(message) => Err(message.clone()),
+            }
+        }
+
+        /// Takes a new reference to the object, either through `user_handle` or to the copy
+        /// deserialized by [`get`](#method.get). Returns `None` if neither is available.
+        pub(crate) fn to_owned_handle(&self) -> Option<UserHandle> {
+            unsafe {
+                UserHandle::copy_from(
+                    HandleKind::CommandResponse(self.command_index),
+                    self.component_id,
+                    self.user_handle as *const _,
+                    self.vtable,
+                )
+            }
+            .or_else(|| deserialized_handle(&self.deserialized))
+        }
+
+        /// Wraps an object owned by the C API, looking up the component's vtable in
+        /// `registry`.
+        pub(crate) fn new(
+            response: &'a Worker_CommandResponse,
+            registry: &ComponentRegistry,
+        ) -> Self {
             CommandResponse {
                 component_id: response.component_id,
                 command_index: response.command_index,
@@ -229,6 +606,8 @@ pub(crate) mod internal {
                 },
                 user_handle: response.user_handle,
                 _marker: PhantomData,
+                deserialized: OnceCell::new(),
+                vtable: registry.get_vtable(response.component_id).cloned(),
             }
         }
     }
@@ -237,34 +616,104 @@ pub(crate) mod internal {
 inventory::collect!(VTable);
 
 lazy_static::lazy_static! {
-    pub(crate) static ref DATABASE: ComponentDatabase = {
+    static ref DATABASE: RwLock<ComponentRegistry> = {
+        let mut registry = ComponentRegistry::new();
+        for table in inventory::iter::<VTable> {
+            registry.insert(table.vtable);
+        }
+        RwLock::new(registry)
+    };
+}
 
-        let mut vtables = Vec::new();
-        let mut index_map = HashMap::new();
+/// Registers component `C` with the global registry.
+///
+/// Components defined in generated code are registered automatically when the program is
+/// linked. This is only needed for components that are only known at runtime, e.g. ones
+/// defined by dynamically loaded plugins.
+pub fn register_component<C: Component>() {
+    register_vtable(VTable::new::<C>());
+}
 
-        for (i, table) in inventory::iter::<VTable>.into_iter().enumerate() {
-            vtables.push(table.vtable);
-            index_map.insert(table.vtable.component_id, i);
-        }
+/// Registers a vtable with the global registry, replacing any vtable that was previously
+/// registered for the same component.
+pub fn register_vtable(vtable: VTable) {
+    DATABASE.write().unwrap().insert(vtable.vtable);
+}
 
-        ComponentDatabase {
-            component_vtables: vtables,
-            index_map,
-        }
-    };
+/// Returns the vtable of a component in the global registry.
+pub(crate) fn global_vtable(id: ComponentId) -> Option<Worker_ComponentVtable> {
+    DATABASE.read().unwrap().get_vtable(id).cloned()
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct ComponentDatabase {
+/// A set of component vtables, used by the C API to serialize and deserialize components.
+///
+/// Connections and snapshot streams use the global registry unless they are given a
+/// registry explicitly. The global registry contains every component defined in generated
+/// code, along with any components registered with [`register_component`].
+///
+/// # Examples
+///
+/// ```
+/// use spatialos_sdk::worker::{
+///     component::ComponentRegistry, parameters::ConnectionParameters,
+/// };
+///
+/// // Only use the components registered at link time, ignoring any registered later on.
+/// let registry = ComponentRegistry::global();
+/// let params = ConnectionParameters::new("client").with_component_registry(registry);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ComponentRegistry {
     component_vtables: Vec<Worker_ComponentVtable>,
     index_map: HashMap<ComponentId, usize>,
 }
 
-impl ComponentDatabase {
-    pub(crate) fn has_vtable(&self, id: ComponentId) -> bool {
+impl ComponentRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns a copy of the global registry.
+    pub fn global() -> Self {
+        DATABASE.read().unwrap().clone()
+    }
+
+    /// Adds component `C` to the registry.
+    pub fn register<C: Component>(&mut self) {
+        self.register_vtable(VTable::new::<C>());
+    }
+
+    /// Adds a vtable to the registry, replacing any vtable that was previously added for
+    /// the same component.
+    pub fn register_vtable(&mut self, vtable: VTable) {
+        self.insert(vtable.vtable);
+    }
+
+    pub fn with_component<C: Component>(mut self) -> Self {
+        self.register::<C>();
+        self
+    }
+
+    pub fn contains(&self, id: ComponentId) -> bool {
         self.index_map.contains_key(&id)
     }
 
+    /// Returns the IDs of all components in the registry, in registration order.
+    pub fn component_ids(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.component_vtables
+            .iter()
+            .map(|vtable| vtable.component_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.component_vtables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.component_vtables.is_empty()
+    }
+
     pub(crate) fn get_vtable(&self, id: ComponentId) -> Option<&Worker_ComponentVtable> {
         self.index_map
             .get(&id)
@@ -275,13 +724,22 @@ impl ComponentDatabase {
         self.component_vtables.as_ptr()
     }
 
-    pub(crate) fn len(&self) -> usize {
-        self.component_vtables.len()
+    fn insert(&mut self, vtable: Worker_ComponentVtable) {
+        match self.index_map.get(&vtable.component_id) {
+            Some(index) => self.component_vtables[*index] = vtable,
+            None => {
+                self.index_map
+                    .insert(vtable.component_id, self.component_vtables.len());
+                self.component_vtables.push(vtable);
+            }
+        }
     }
 }
 
-unsafe impl Sync for ComponentDatabase {}
-unsafe impl Send for ComponentDatabase {}
+// SAFE: The vtables only contain function pointers and a user data pointer that is never
+// dereferenced by this crate.
+unsafe impl Sync for ComponentRegistry {}
+unsafe impl Send for ComponentRegistry {}
 
 pub(crate) fn handle_allocate<T>(data: T) -> *mut raw::c_void {
     Arc::into_raw(Arc::new(data)) as *mut _
@@ -298,6 +756,246 @@ pub(crate) unsafe fn handle_copy<T>(handle: *mut raw::c_void) -> *mut raw::c_voi
     Arc::into_raw(copy) as *mut _
 }
 
+/// The kind of user data stored behind a [`UserHandle`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum HandleKind {
+    ComponentData,
+    ComponentUpdate,
+    CommandRequest(CommandIndex),
+    CommandResponse(CommandIndex),
+}
+
+/// An owned reference to type-erased user data, i.e. the `user_handle` stored in a
+/// `Worker_ComponentData`, `Worker_ComponentUpdate`, `Worker_CommandRequest` or
+/// `Worker_CommandResponse`.
+///
+/// The handle is reference-counted: cloning it shares the underlying data, which is freed
+/// once the last handle is dropped. Copying and freeing are performed through the
+/// component's vtable, so the concrete type doesn't need to be known.
+pub(crate) struct UserHandle {
+    kind: HandleKind,
+    component_id: ComponentId,
+    ptr: *mut raw::c_void,
+    vtable: Worker_ComponentVtable,
+}
+
+impl UserHandle {
+    pub(crate) fn component_data<C: Component>(data: C) -> Self {
+        UserHandle::new::<C>(HandleKind::ComponentData, handle_allocate(data))
+    }
+
+    pub(crate) fn component_update<C: Component>(update: C::Update) -> Self {
+        UserHandle::new::<C>(HandleKind::ComponentUpdate, handle_allocate(update))
+    }
+
+    pub(crate) fn command_request<C: Component>(request: C::CommandRequest) -> Self {
+        let command_index = C::get_request_command_index(&request);
+        UserHandle::new::<C>(
+            HandleKind::CommandRequest(command_index),
+            handle_allocate(request),
+        )
+    }
+
+    pub(crate) fn command_response<C: Component>(response: C::CommandResponse) -> Self {
+        let command_index = C::get_response_command_index(&response);
+        UserHandle::new::<C>(
+            HandleKind::CommandResponse(command_index),
+            handle_allocate(response),
+        )
+    }
+
+    fn new<C: Component>(kind: HandleKind, ptr: *mut raw::c_void) -> Self {
+        UserHandle {
+            kind,
+            component_id: C::ID,
+            ptr,
+            vtable: VTable::new::<C>().vtable,
+        }
+    }
+
+    /// Takes a new reference to a user handle that is owned by someone else, e.g. the
+    /// C API.
+    ///
+    /// Returns `None` if the handle is null or if there is no vtable for the component.
+    ///
+    /// UNSAFE: If `ptr` is non-null, it must have been allocated by `vtable`.
+    pub(crate) unsafe fn copy_from(
+        kind: HandleKind,
+        component_id: ComponentId,
+        ptr: *const raw::c_void,
+        vtable: Option<Worker_ComponentVtable>,
+    ) -> Option<Self> {
+        if ptr.is_null() {
+            return None;
+        }
+
+        let vtable = vtable?;
+        Some(UserHandle::copy_with_vtable(
+            kind,
+            component_id,
+            ptr,
+            vtable,
+        ))
+    }
+
+    /// Takes a new reference to a user handle that is owned by someone else, using the
+    /// given vtable to manage it.
+    ///
+    /// UNSAFE: `ptr` must be non-null and must have been allocated by `vtable`.
+    pub(crate) unsafe fn copy_with_vtable(
+        kind: HandleKind,
+        component_id: ComponentId,
+        ptr: *const raw::c_void,
+        vtable: Worker_ComponentVtable,
+    ) -> Self {
+        let borrowed = UserHandle::from_raw_parts(kind, component_id, ptr as *mut _, vtable);
+
+        // Take our own reference, then make sure we don't release the borrowed one.
+        let copy = borrowed.clone();
+        mem::forget(borrowed);
+        copy
+    }
+
+    /// Takes ownership of a user handle that was allocated by `vtable`.
+    ///
+    /// UNSAFE: `ptr` must be non-null and must have been allocated by `vtable`. The caller
+    /// must not release the reference that is passed in.
+    pub(crate) unsafe fn from_raw_parts(
+        kind: HandleKind,
+        component_id: ComponentId,
+        ptr: *mut raw::c_void,
+        vtable: Worker_ComponentVtable,
+    ) -> Self {
+        UserHandle {
+            kind,
+            component_id,
+            ptr,
+            vtable,
+        }
+    }
+
+    pub(crate) fn component_id(&self) -> ComponentId {
+        self.component_id
+    }
+
+    /// Serializes component data through the vtable into the schema wire format.
+    pub(crate) fn serialize_data(&self) -> Result<Vec<u8>, String> {
+        let id = self.component_id;
+        let serialize_func = self
+            .vtable
+            .component_data_serialize
+            .ok_or_else(|| format!("No component_data_serialize method defined for {}", id))?;
+
+        let mut data = ptr::null_mut();
+        unsafe { serialize_func(id, self.vtable.user_data, self.ptr, &mut data) };
+        if data.is_null() {
+            return Err(format!("Failed to serialize component {}", id));
+        }
+
+        let bytes = schema::SchemaComponentData { internal: data }
+            .fields()
+            .serialize();
+        unsafe { Schema_DestroyComponentData(data) };
+        bytes
+    }
+
+    pub(crate) fn command_index(&self) -> CommandIndex {
+        match self.kind {
+            HandleKind::CommandRequest(index) | HandleKind::CommandResponse(index) => index,
+            HandleKind::ComponentData | HandleKind::ComponentUpdate => 0,
+        }
+    }
+
+    /// Returns the raw pointer, which remains valid for as long as `self` is alive.
+    pub(crate) fn as_ptr(&self) -> *mut raw::c_void {
+        self.ptr
+    }
+
+    /// Returns a reference to the user data if it belongs to the component `C`.
+    ///
+    /// UNSAFE: `T` must be the type that corresponds to the handle's kind for `C`, i.e.
+    /// `C`, `C::Update`, `C::CommandRequest` or `C::CommandResponse`.
+    pub(crate) unsafe fn get<C: Component, T>(&self) -> Option<&T> {
+        if C::ID == self.component_id {
+            Some(&*(self.ptr as *const T))
+        } else {
+            None
+        }
+    }
+
+    fn copy_ptr(&self) -> *mut raw::c_void {
+        let vtable = &self.vtable;
+        let id = self.component_id;
+        let user_data = vtable.user_data;
+        unsafe {
+            match self.kind {
+                HandleKind::ComponentData => {
+                    (vtable.component_data_copy.unwrap())(id, user_data, self.ptr)
+                }
+                HandleKind::ComponentUpdate => {
+                    (vtable.component_update_copy.unwrap())(id, user_data, self.ptr)
+                }
+                HandleKind::CommandRequest(index) => {
+                    (vtable.command_request_copy.unwrap())(id, index, user_data, self.ptr)
+                }
+                HandleKind::CommandResponse(index) => {
+                    (vtable.command_response_copy.unwrap())(id, index, user_data, self.ptr)
+                }
+            }
+        }
+    }
+}
+
+impl Clone for UserHandle {
+    fn clone(&self) -> Self {
+        UserHandle {
+            ptr: self.copy_ptr(),
+            ..*self
+        }
+    }
+}
+
+impl Drop for UserHandle {
+    fn drop(&mut self) {
+        let vtable = &self.vtable;
+        let id = self.component_id;
+        let user_data = vtable.user_data;
+        unsafe {
+            match self.kind {
+                HandleKind::ComponentData => {
+                    (vtable.component_data_free.unwrap())(id, user_data, self.ptr)
+                }
+                HandleKind::ComponentUpdate => {
+                    (vtable.component_update_free.unwrap())(id, user_data, self.ptr)
+                }
+                HandleKind::CommandRequest(index) => {
+                    (vtable.command_request_free.unwrap())(id, index, user_data, self.ptr)
+                }
+                HandleKind::CommandResponse(index) => {
+                    (vtable.command_response_free.unwrap())(id, index, user_data, self.ptr)
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for UserHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserHandle")
+            .field("kind", &self.kind)
+            .field("component_id", &self.component_id)
+            .field("ptr", &self.ptr)
+            .finish()
+    }
+}
+
+// SAFE: Every vtable a handle can hold was built by `VTable::new::<C>`, which has a null
+// user data pointer and only copies and frees the data as an `Arc` of `C`, `C::Update`,
+// `C::CommandRequest` or `C::CommandResponse`. `Component` requires all of these to be
+// `Send + Sync`, so a handle can be shared between threads just like the `Arc` it wraps.
+unsafe impl Send for UserHandle {}
+unsafe impl Sync for UserHandle {}
+
 pub struct VTable {
     vtable: Worker_ComponentVtable,
 }
@@ -380,7 +1078,7 @@ unsafe extern "C" fn vtable_component_update_free<C: Component>(
     _: *mut raw::c_void,
     handle: *mut raw::c_void,
 ) {
-    handle_free::<C>(handle)
+    handle_free::<C::Update>(handle)
 }
 
 unsafe extern "C" fn vtable_component_update_copy<C: Component>(
@@ -388,7 +1086,7 @@ unsafe extern "C" fn vtable_component_update_copy<C: Component>(
     _: *mut raw::c_void,
     handle: *mut raw::c_void,
 ) -> *mut raw::c_void {
-    handle_copy::<C>(handle)
+    handle_copy::<C::Update>(handle)
 }
 
 unsafe extern "C" fn vtable_component_update_deserialize<C: Component>(
@@ -428,7 +1126,7 @@ unsafe extern "C" fn vtable_command_request_free<C: Component>(
     _: *mut raw::c_void,
     handle: *mut raw::c_void,
 ) {
-    handle_free::<C>(handle)
+    handle_free::<C::CommandRequest>(handle)
 }
 
 unsafe extern "C" fn vtable_command_request_copy<C: Component>(
@@ -437,7 +1135,7 @@ unsafe extern "C" fn vtable_command_request_copy<C: Component>(
     _: *mut raw::c_void,
     handle: *mut raw::c_void,
 ) -> *mut raw::c_void {
-    handle_copy::<C>(handle)
+    handle_copy::<C::CommandRequest>(handle)
 }
 
 unsafe extern "C" fn vtable_command_request_deserialize<C: Component>(
@@ -479,7 +1177,7 @@ unsafe extern "C" fn vtable_command_response_free<C: Component>(
     _: *mut raw::c_void,
     handle: *mut raw::c_void,
 ) {
-    handle_free::<C>(handle)
+    handle_free::<C::CommandResponse>(handle)
 }
 
 unsafe extern "C" fn vtable_command_response_copy<C: Component>(
@@ -488,7 +1186,7 @@ unsafe extern "C" fn vtable_command_response_copy<C: Component>(
     _: *mut raw::c_void,
     handle: *mut raw::c_void,
 ) -> *mut raw::c_void {
-    handle_copy::<C>(handle)
+    handle_copy::<C::CommandResponse>(handle)
 }
 
 unsafe extern "C" fn vtable_command_response_deserialize<C: Component>(
@@ -523,3 +1221,106 @@ unsafe extern "C" fn vtable_command_response_serialize<C: Component>(
         *response = ptr::null_mut();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::worker::internal::schema::*;
+    use crate::worker::mock::test::Counter;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // A component whose updates and commands count how many times they have been dropped.
+    struct Tracked;
+
+    struct DropCounter(Arc<AtomicUsize>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Component for Tracked {
+        type Update = DropCounter;
+        type CommandRequest = DropCounter;
+        type CommandResponse = DropCounter;
+
+        const ID: ComponentId = 4321;
+
+        fn from_data(_: &SchemaComponentData) -> Result<Self, String> {
+            Err("Tracked has no schema data".to_owned())
+        }
+        fn from_update(_: &SchemaComponentUpdate) -> Result<Self::Update, String> {
+            Err("Tracked has no schema updates".to_owned())
+        }
+        fn from_request(_: u32, _: &SchemaCommandRequest) -> Result<Self::CommandRequest, String> {
+            Err("Tracked has no schema commands".to_owned())
+        }
+        fn from_response(
+            _: u32,
+            _: &SchemaCommandResponse,
+        ) -> Result<Self::CommandResponse, String> {
+            Err("Tracked has no schema commands".to_owned())
+        }
+
+        fn to_data(_: &Self) -> Result<SchemaComponentData, String> {
+            Err("Tracked has no schema data".to_owned())
+        }
+        fn to_update(_: &Self::Update) -> Result<SchemaComponentUpdate, String> {
+            Err("Tracked has no schema updates".to_owned())
+        }
+        fn to_request(_: &Self::CommandRequest) -> Result<SchemaCommandRequest, String> {
+            Err("Tracked has no schema commands".to_owned())
+        }
+        fn to_response(_: &Self::CommandResponse) -> Result<SchemaCommandResponse, String> {
+            Err("Tracked has no schema commands".to_owned())
+        }
+
+        fn get_request_command_index(_: &Self::CommandRequest) -> u32 {
+            1
+        }
+        fn get_response_command_index(_: &Self::CommandResponse) -> u32 {
+            1
+        }
+    }
+
+    #[test]
+    fn user_handles_free_their_data_as_the_type_they_hold() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let handles = vec![
+            UserHandle::component_update::<Tracked>(DropCounter(drops.clone())),
+            UserHandle::command_request::<Tracked>(DropCounter(drops.clone())),
+            UserHandle::command_response::<Tracked>(DropCounter(drops.clone())),
+        ];
+
+        // Copies share the data, so it's only dropped once the last copy is released.
+        let copies = handles.clone();
+        drop(handles);
+        assert_eq!(0, drops.load(Ordering::SeqCst));
+
+        drop(copies);
+        assert_eq!(3, drops.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn registering_a_component_twice_replaces_its_vtable() {
+        let mut registry = ComponentRegistry::new().with_component::<Counter>();
+        registry.register::<Counter>();
+
+        assert_eq!(1, registry.len());
+        assert!(registry.contains(Counter::ID));
+        assert_eq!(
+            vec![Counter::ID],
+            registry.component_ids().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn global_registry_contains_submitted_components() {
+        assert!(ComponentRegistry::global().contains(Counter::ID));
+        assert!(global_vtable(Counter::ID).is_some());
+
+        register_component::<Counter>();
+        assert!(ComponentRegistry::global().contains(Counter::ID));
+    }
+}
@@ -1,9 +1,12 @@
 use crate::ptr::MutPtr;
 use crate::worker::{
     commands::*,
-    component::{self, Component, UpdateParameters},
+    component::{self, Component, ComponentRegistry, UpdateParameters},
     entity::Entity,
-    internal::utils::cstr_to_string,
+    internal::{
+        blocking_task::{BlockingTask, SendPtr},
+        utils::cstr_to_string,
+    },
     locator::*,
     metrics::Metrics,
     op::OpList,
@@ -14,7 +17,11 @@ use futures::{Async, Future};
 use spatialos_sdk_sys::worker::*;
 use std::{
     ffi::{CStr, CString, NulError},
+    mem,
+    pin::Pin,
     ptr,
+    sync::Arc,
+    task::{ready, Context, Poll},
 };
 
 /// Information about the status of a worker connection or network request.
@@ -165,10 +172,16 @@ pub struct WorkerConnection {
     // Cached copies of static connection data. These are stored internally so that we can guarantee it will be safe to access this data through `&self`.
     id: String,
     attributes: Vec<String>,
+
+    // The vtables that were passed to the C API when connecting.
+    component_registry: Arc<ComponentRegistry>,
 }
 
 impl WorkerConnection {
-    pub(crate) fn new(connection_ptr: *mut Worker_Connection) -> Self {
+    pub(crate) fn new(
+        connection_ptr: *mut Worker_Connection,
+        component_registry: Arc<ComponentRegistry>,
+    ) -> Self {
         unsafe {
             let worker_id = Worker_Connection_GetWorkerId(connection_ptr);
             let cstr = CStr::from_ptr(worker_id);
@@ -191,6 +204,7 @@ impl WorkerConnection {
                 connection_ptr: MutPtr::new(connection_ptr),
                 id: cstr.to_string_lossy().to_string(),
                 attributes,
+                component_registry,
             }
         }
     }
@@ -218,7 +232,7 @@ impl WorkerConnection {
             )
         };
         assert!(!future_ptr.is_null());
-        WorkerConnectionFuture::new(future_ptr)
+        WorkerConnectionFuture::new(future_ptr, Arc::clone(params.component_registry()))
     }
 
     pub fn connect_locator_async(
@@ -231,7 +245,7 @@ impl WorkerConnection {
 
         let future_ptr = unsafe { Worker_Locator_ConnectAsync(locator.locator, &params.as_raw()) };
         assert!(!future_ptr.is_null());
-        WorkerConnectionFuture::new(future_ptr)
+        WorkerConnectionFuture::new(future_ptr, Arc::clone(params.component_registry()))
     }
 }
 
@@ -551,7 +565,7 @@ impl Connection for WorkerConnection {
         let raw_op_list =
             unsafe { Worker_Connection_GetOpList(self.connection_ptr.get(), timeout_millis) };
         assert!(!raw_op_list.is_null());
-        OpList::new(raw_op_list)
+        OpList::new(raw_op_list, Arc::clone(&self.component_registry))
     }
 
     fn get_worker_id(&self) -> &str {
@@ -577,23 +591,47 @@ unsafe impl Sync for WorkerConnection {}
 pub struct WorkerConnectionFuture {
     future_ptr: *mut Worker_ConnectionFuture,
     was_consumed: bool,
+    task: Option<BlockingTask<Result<WorkerConnection, String>>>,
+    component_registry: Arc<ComponentRegistry>,
 }
 
 impl WorkerConnectionFuture {
-    pub(crate) fn new(ptr: *mut Worker_ConnectionFuture) -> Self {
+    pub(crate) fn new(
+        ptr: *mut Worker_ConnectionFuture,
+        component_registry: Arc<ComponentRegistry>,
+    ) -> Self {
         WorkerConnectionFuture {
             future_ptr: ptr,
             was_consumed: false,
+            task: None,
+            component_registry,
         }
     }
+
+    fn connection_from_ptr(
+        connection_ptr: *mut Worker_Connection,
+        component_registry: Arc<ComponentRegistry>,
+    ) -> Result<WorkerConnection, String> {
+        let mut connection = WorkerConnection::new(connection_ptr, component_registry);
+
+        let status = connection.get_connection_status();
+        if status.code == ConnectionStatusCode::Success {
+            return Ok(connection);
+        }
+
+        Err(status.detail)
+    }
 }
 
 impl Drop for WorkerConnectionFuture {
     fn drop(&mut self) {
-        assert!(!self.future_ptr.is_null());
-        unsafe {
-            Worker_ConnectionFuture_Destroy(self.future_ptr);
-        };
+        // The pointer is null if ownership of the future has been moved to a background
+        // task, which destroys it once it has completed.
+        if !self.future_ptr.is_null() {
+            unsafe {
+                Worker_ConnectionFuture_Destroy(self.future_ptr);
+            };
+        }
     }
 }
 
@@ -602,6 +640,17 @@ impl Future for WorkerConnectionFuture {
     type Error = String;
 
     fn poll(&mut self) -> Result<Async<<Self as Future>::Item>, <Self as Future>::Error> {
+        if let Some(task) = &mut self.task {
+            return match task.try_take() {
+                Some(result) => {
+                    self.was_consumed = true;
+                    self.task = None;
+                    result.map(Async::Ready)
+                }
+                None => Ok(Async::NotReady),
+            };
+        }
+
         if self.was_consumed {
             return Err("WorkerConnectionFuture has already been consumed.".to_owned());
         }
@@ -614,33 +663,80 @@ impl Future for WorkerConnectionFuture {
         }
 
         self.was_consumed = true;
-        let mut connection = WorkerConnection::new(connection_ptr);
-
-        let status = connection.get_connection_status();
-        if status.code == ConnectionStatusCode::Success {
-            return Ok(Async::Ready(connection));
-        }
-
-        Err(status.detail)
+        WorkerConnectionFuture::connection_from_ptr(
+            connection_ptr,
+            Arc::clone(&self.component_registry),
+        )
+        .map(Async::Ready)
     }
 
-    fn wait(self) -> Result<<Self as Future>::Item, <Self as Future>::Error>
+    fn wait(mut self) -> Result<<Self as Future>::Item, <Self as Future>::Error>
     where
         Self: Sized,
     {
+        if let Some(task) = self.task.take() {
+            return task.wait();
+        }
+
         if self.was_consumed {
             return Err("WorkerConnectionFuture has already been consumed.".to_owned());
         }
 
         assert!(!self.future_ptr.is_null());
         let connection_ptr = unsafe { Worker_ConnectionFuture_Get(self.future_ptr, ptr::null()) };
-        let mut connection = WorkerConnection::new(connection_ptr);
+        WorkerConnectionFuture::connection_from_ptr(
+            connection_ptr,
+            Arc::clone(&self.component_registry),
+        )
+    }
+}
 
-        let status = connection.get_connection_status();
-        if status.code == ConnectionStatusCode::Success {
-            return Ok(connection);
+/// Allows the connection to be awaited from an async task.
+///
+/// If the connection isn't ready when first polled, the future is moved onto a
+/// background thread that waits for it to complete and then wakes the task, so
+/// the executor isn't kept busy while the connection is being established.
+impl std::future::Future for WorkerConnectionFuture {
+    type Output = Result<WorkerConnection, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(task) = &mut self.task {
+            let result = ready!(task.poll(cx));
+            self.was_consumed = true;
+            self.task = None;
+            return Poll::Ready(result);
         }
 
-        Err(status.detail)
+        if self.was_consumed {
+            return Poll::Ready(Err(
+                "WorkerConnectionFuture has already been consumed.".to_owned()
+            ));
+        }
+
+        assert!(!self.future_ptr.is_null());
+        let connection_ptr = unsafe { Worker_ConnectionFuture_Get(self.future_ptr, &0) };
+        if !connection_ptr.is_null() {
+            self.was_consumed = true;
+            return Poll::Ready(WorkerConnectionFuture::connection_from_ptr(
+                connection_ptr,
+                Arc::clone(&self.component_registry),
+            ));
+        }
+
+        let future_ptr = SendPtr(mem::replace(&mut self.future_ptr, ptr::null_mut()));
+        let component_registry = Arc::clone(&self.component_registry);
+        let task = BlockingTask::spawn(
+            move || unsafe {
+                let connection_ptr = Worker_ConnectionFuture_Get(future_ptr.0, ptr::null());
+                let result =
+                    WorkerConnectionFuture::connection_from_ptr(connection_ptr, component_registry);
+                Worker_ConnectionFuture_Destroy(future_ptr.0);
+                result
+            },
+            cx.waker().clone(),
+        );
+        self.task = Some(task);
+
+        Poll::Pending
     }
 }
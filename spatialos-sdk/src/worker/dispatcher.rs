@@ -0,0 +1,487 @@
+//! Callback-based processing of op lists.
+//!
+//! A [`Dispatcher`] holds a set of callbacks, registered per op type and, for
+//! component-specific ops, per component. Calling [`Dispatcher::process`] with an
+//! [`OpList`] invokes the matching callbacks for every op in the list, in order.
+//!
+//! # Examples
+//!
+//! ```
+//! use spatialos_sdk::worker::{
+//!     connection::Connection, dispatcher::Dispatcher, mock::{MockConnection, MockOpList},
+//!     EntityId,
+//! };
+//!
+//! let mut connection = MockConnection::new("TestWorker");
+//! connection.push_op_list(MockOpList::new().with_add_entity(EntityId::new(1)));
+//!
+//! let mut added = Vec::new();
+//! {
+//!     let mut dispatcher = Dispatcher::new();
+//!     dispatcher.on_add_entity(|op| added.push(op.entity_id));
+//!     dispatcher.process(&connection.get_op_list(0));
+//! }
+//!
+//! assert_eq!(vec![EntityId::new(1)], added);
+//! ```
+
+use crate::worker::{
+    component::{Component, ComponentId},
+    op::*,
+    Authority, EntityId,
+};
+use std::collections::HashMap;
+
+/// Identifies a callback registered with a [`Dispatcher`], so that it can be removed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CallbackKey(u64);
+
+struct Callbacks<F: ?Sized> {
+    callbacks: Vec<(CallbackKey, Box<F>)>,
+}
+
+impl<F: ?Sized> Callbacks<F> {
+    fn push(&mut self, key: CallbackKey, callback: Box<F>) {
+        self.callbacks.push((key, callback));
+    }
+
+    fn remove(&mut self, key: CallbackKey) -> bool {
+        let len = self.callbacks.len();
+        self.callbacks.retain(|(k, _)| *k != key);
+        len != self.callbacks.len()
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<F>> {
+        self.callbacks.iter_mut().map(|(_, callback)| callback)
+    }
+}
+
+impl<F: ?Sized> Default for Callbacks<F> {
+    fn default() -> Self {
+        Callbacks {
+            callbacks: Vec::new(),
+        }
+    }
+}
+
+type ComponentCallbacks<F> = HashMap<ComponentId, Callbacks<F>>;
+
+/// Invokes user callbacks for the ops in an [`OpList`].
+///
+/// This mirrors the `Dispatcher` found in the C++ and C# worker SDKs. Callbacks for
+/// component-specific ops are registered with the component type, and receive the
+/// deserialized data alongside the op. Callbacks for a component are only invoked if the
+/// op's data could be accessed as that component, i.e. if the op's `get::<C>()` accessor
+/// returns `Some`.
+///
+/// Callbacks may borrow from their environment for the lifetime `'a` of the dispatcher.
+/// Every registration method returns a [`CallbackKey`] that can be passed to
+/// [`remove`](#method.remove) to unregister the callback.
+#[derive(Default)]
+pub struct Dispatcher<'a> {
+    next_key: u64,
+
+    disconnect: Callbacks<dyn FnMut(&DisconnectOp) + 'a>,
+    flag_update: Callbacks<dyn FnMut(&FlagUpdateOp) + 'a>,
+    log_message: Callbacks<dyn FnMut(&LogMessageOp) + 'a>,
+    metrics: Callbacks<dyn FnMut(&MetricsOp) + 'a>,
+    critical_section: Callbacks<dyn FnMut(&CriticalSectionOp) + 'a>,
+    add_entity: Callbacks<dyn FnMut(&AddEntityOp) + 'a>,
+    remove_entity: Callbacks<dyn FnMut(&RemoveEntityOp) + 'a>,
+    reserve_entity_ids_response: Callbacks<dyn FnMut(&ReserveEntityIdsResponseOp) + 'a>,
+    create_entity_response: Callbacks<dyn FnMut(&CreateEntityResponseOp) + 'a>,
+    delete_entity_response: Callbacks<dyn FnMut(&DeleteEntityResponseOp) + 'a>,
+    entity_query_response: Callbacks<dyn FnMut(&EntityQueryResponseOp) + 'a>,
+
+    add_component: ComponentCallbacks<dyn FnMut(&AddComponentOp<'_>) + 'a>,
+    remove_component: ComponentCallbacks<dyn FnMut(&RemoveComponentOp) + 'a>,
+    component_update: ComponentCallbacks<dyn FnMut(&ComponentUpdateOp<'_>) + 'a>,
+    authority_change: ComponentCallbacks<dyn FnMut(&AuthorityChangeOp) + 'a>,
+    command_request: ComponentCallbacks<dyn FnMut(&CommandRequestOp<'_>) + 'a>,
+    command_response: ComponentCallbacks<dyn FnMut(&CommandResponseOp<'_>) + 'a>,
+}
+
+impl<'a> Dispatcher<'a> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn on_disconnect(&mut self, callback: impl FnMut(&DisconnectOp) + 'a) -> CallbackKey {
+        let key = self.next_key();
+        self.disconnect.push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_flag_update(&mut self, callback: impl FnMut(&FlagUpdateOp) + 'a) -> CallbackKey {
+        let key = self.next_key();
+        self.flag_update.push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_log_message(&mut self, callback: impl FnMut(&LogMessageOp) + 'a) -> CallbackKey {
+        let key = self.next_key();
+        self.log_message.push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_metrics(&mut self, callback: impl FnMut(&MetricsOp) + 'a) -> CallbackKey {
+        let key = self.next_key();
+        self.metrics.push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_critical_section(
+        &mut self,
+        callback: impl FnMut(&CriticalSectionOp) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.critical_section.push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_add_entity(&mut self, callback: impl FnMut(&AddEntityOp) + 'a) -> CallbackKey {
+        let key = self.next_key();
+        self.add_entity.push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_remove_entity(&mut self, callback: impl FnMut(&RemoveEntityOp) + 'a) -> CallbackKey {
+        let key = self.next_key();
+        self.remove_entity.push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_reserve_entity_ids_response(
+        &mut self,
+        callback: impl FnMut(&ReserveEntityIdsResponseOp) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.reserve_entity_ids_response
+            .push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_create_entity_response(
+        &mut self,
+        callback: impl FnMut(&CreateEntityResponseOp) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.create_entity_response.push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_delete_entity_response(
+        &mut self,
+        callback: impl FnMut(&DeleteEntityResponseOp) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.delete_entity_response.push(key, Box::new(callback));
+        key
+    }
+
+    pub fn on_entity_query_response(
+        &mut self,
+        callback: impl FnMut(&EntityQueryResponseOp) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.entity_query_response.push(key, Box::new(callback));
+        key
+    }
+
+    /// Registers a callback for when a component of type `C` is added to an entity.
+    pub fn on_add_component<C: Component + 'a>(
+        &mut self,
+        mut callback: impl FnMut(&AddComponentOp<'_>, &C) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.add_component.entry(C::ID).or_default().push(
+            key,
+            Box::new(move |op: &AddComponentOp<'_>| {
+                if let Some(data) = op.get::<C>() {
+                    callback(op, data);
+                }
+            }),
+        );
+        key
+    }
+
+    /// Registers a callback for when a component of type `C` is removed from an entity.
+    pub fn on_remove_component<C: Component>(
+        &mut self,
+        callback: impl FnMut(&RemoveComponentOp) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.remove_component
+            .entry(C::ID)
+            .or_default()
+            .push(key, Box::new(callback));
+        key
+    }
+
+    /// Registers a callback for updates to a component of type `C`.
+    pub fn on_component_update<C: Component + 'a>(
+        &mut self,
+        mut callback: impl FnMut(&ComponentUpdateOp<'_>, &C::Update) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.component_update.entry(C::ID).or_default().push(
+            key,
+            Box::new(move |op: &ComponentUpdateOp<'_>| {
+                if let Some(update) = op.get::<C>() {
+                    callback(op, update);
+                }
+            }),
+        );
+        key
+    }
+
+    /// Registers a callback for changes to the worker's authority over a component of
+    /// type `C`.
+    pub fn on_authority_change<C: Component>(
+        &mut self,
+        mut callback: impl FnMut(EntityId, Authority) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.authority_change.entry(C::ID).or_default().push(
+            key,
+            Box::new(move |op: &AuthorityChangeOp| callback(op.entity_id, op.authority)),
+        );
+        key
+    }
+
+    /// Registers a callback for incoming command requests for a component of type `C`.
+    pub fn on_command_request<C: Component + 'a>(
+        &mut self,
+        mut callback: impl FnMut(&CommandRequestOp<'_>, &C::CommandRequest) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.command_request.entry(C::ID).or_default().push(
+            key,
+            Box::new(move |op: &CommandRequestOp<'_>| {
+                if let Some(request) = op.get::<C>() {
+                    callback(op, request);
+                }
+            }),
+        );
+        key
+    }
+
+    /// Registers a callback for responses to command requests sent for a component of
+    /// type `C`.
+    ///
+    /// The callback is invoked for failed requests as well. On success, the response can
+    /// be accessed with `CommandResponse::get::<C>()`.
+    pub fn on_command_response<C: Component>(
+        &mut self,
+        callback: impl FnMut(&CommandResponseOp<'_>) + 'a,
+    ) -> CallbackKey {
+        let key = self.next_key();
+        self.command_response
+            .entry(C::ID)
+            .or_default()
+            .push(key, Box::new(callback));
+        key
+    }
+
+    /// Removes a previously registered callback.
+    ///
+    /// Returns `false` if no callback is registered with `key`.
+    pub fn remove(&mut self, key: CallbackKey) -> bool {
+        let mut removed = self.disconnect.remove(key)
+            || self.flag_update.remove(key)
+            || self.log_message.remove(key)
+            || self.metrics.remove(key)
+            || self.critical_section.remove(key)
+            || self.add_entity.remove(key)
+            || self.remove_entity.remove(key)
+            || self.reserve_entity_ids_response.remove(key)
+            || self.create_entity_response.remove(key)
+            || self.delete_entity_response.remove(key)
+            || self.entity_query_response.remove(key);
+
+        removed = removed || remove_component_callback(&mut self.add_component, key);
+        removed = removed || remove_component_callback(&mut self.remove_component, key);
+        removed = removed || remove_component_callback(&mut self.component_update, key);
+        removed = removed || remove_component_callback(&mut self.authority_change, key);
+        removed = removed || remove_component_callback(&mut self.command_request, key);
+        removed || remove_component_callback(&mut self.command_response, key)
+    }
+
+    /// Invokes the registered callbacks for every op in `op_list`, in order.
+    pub fn process(&mut self, op_list: &OpList) {
+        for op in op_list {
+            self.process_op(&op);
+        }
+    }
+
+    /// Invokes the registered callbacks for a single op.
+    pub fn process_op(&mut self, op: &WorkerOp<'_>) {
+        match op {
+            WorkerOp::Disconnect(op) => invoke(&mut self.disconnect, op),
+            WorkerOp::FlagUpdate(op) => invoke(&mut self.flag_update, op),
+            WorkerOp::LogMessage(op) => invoke(&mut self.log_message, op),
+            WorkerOp::Metrics(op) => invoke(&mut self.metrics, op),
+            WorkerOp::CriticalSection(op) => invoke(&mut self.critical_section, op),
+            WorkerOp::AddEntity(op) => invoke(&mut self.add_entity, op),
+            WorkerOp::RemoveEntity(op) => invoke(&mut self.remove_entity, op),
+            WorkerOp::ReserveEntityIdsResponse(op) => {
+                invoke(&mut self.reserve_entity_ids_response, op)
+            }
+            WorkerOp::CreateEntityResponse(op) => invoke(&mut self.create_entity_response, op),
+            WorkerOp::DeleteEntityResponse(op) => invoke(&mut self.delete_entity_response, op),
+            WorkerOp::EntityQueryResponse(op) => invoke(&mut self.entity_query_response, op),
+            WorkerOp::AddComponent(op) => {
+                if let Some(callbacks) = self.add_component.get_mut(&op.component_id) {
+                    callbacks.iter_mut().for_each(|callback| callback(op));
+                }
+            }
+            WorkerOp::RemoveComponent(op) => {
+                if let Some(callbacks) = self.remove_component.get_mut(&op.component_id) {
+                    invoke(callbacks, op);
+                }
+            }
+            WorkerOp::ComponentUpdate(op) => {
+                if let Some(callbacks) = self.component_update.get_mut(&op.component_id) {
+                    callbacks.iter_mut().for_each(|callback| callback(op));
+                }
+            }
+            WorkerOp::AuthorityChange(op) => {
+                if let Some(callbacks) = self.authority_change.get_mut(&op.component_id) {
+                    invoke(callbacks, op);
+                }
+            }
+            WorkerOp::CommandRequest(op) => {
+                if let Some(callbacks) = self.command_request.get_mut(&op.component_id) {
+                    callbacks.iter_mut().for_each(|callback| callback(op));
+                }
+            }
+            WorkerOp::CommandResponse(op) => {
+                if let Some(callbacks) = self.command_response.get_mut(&op.component_id) {
+                    callbacks.iter_mut().for_each(|callback| callback(op));
+                }
+            }
+        }
+    }
+
+    fn next_key(&mut self) -> CallbackKey {
+        let key = CallbackKey(self.next_key);
+        self.next_key += 1;
+        key
+    }
+}
+
+fn invoke<'a, T>(callbacks: &mut Callbacks<dyn FnMut(&T) + 'a>, op: &T) {
+    callbacks.iter_mut().for_each(|callback| callback(op));
+}
+
+fn remove_component_callback<F: ?Sized>(
+    callbacks: &mut ComponentCallbacks<F>,
+    key: CallbackKey,
+) -> bool {
+    callbacks
+        .values_mut()
+        .any(|callbacks| callbacks.remove(key))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::worker::{
+        mock::{
+            test::{Counter, CounterUpdate, Increment},
+            MockOpList,
+        },
+        RequestId,
+    };
+
+    #[test]
+    fn callbacks_are_invoked_for_matching_ops_in_order() {
+        let entity_id = EntityId::new(1);
+        let op_list = MockOpList::new()
+            .with_add_entity(entity_id)
+            .with_add_component(entity_id, Counter { value: 1 })
+            .with_authority_change(entity_id, Counter::ID, Authority::Authoritative)
+            .with_authority_change(entity_id, 54, Authority::Authoritative)
+            .with_component_update::<Counter>(entity_id, CounterUpdate { value: Some(2) })
+            .with_command_request::<Counter>(
+                RequestId::new(5),
+                entity_id,
+                "Caller",
+                &[],
+                Increment(3),
+            )
+            .with_remove_component(entity_id, Counter::ID)
+            .with_disconnect("Bye")
+            .into_op_list();
+
+        let mut events = Vec::new();
+        {
+            let events = ::std::cell::RefCell::new(&mut events);
+            let mut dispatcher = Dispatcher::new();
+            dispatcher
+                .on_add_entity(|op| events.borrow_mut().push(format!("add {}", op.entity_id.id)));
+            dispatcher.on_add_component::<Counter>(|_, data| {
+                events.borrow_mut().push(format!("data {}", data.value))
+            });
+            dispatcher.on_authority_change::<Counter>(|_, authority| {
+                events.borrow_mut().push(format!("auth {:?}", authority))
+            });
+            dispatcher.on_component_update::<Counter>(|_, update| {
+                events
+                    .borrow_mut()
+                    .push(format!("update {:?}", update.value))
+            });
+            dispatcher.on_command_request::<Counter>(|op, request| {
+                events.borrow_mut().push(format!(
+                    "request {} from {}",
+                    request.0, op.caller_worker_id
+                ))
+            });
+            dispatcher
+                .on_remove_component::<Counter>(|_| events.borrow_mut().push("remove".to_owned()));
+            dispatcher.on_disconnect(|op| {
+                events
+                    .borrow_mut()
+                    .push(format!("disconnect {}", op.reason))
+            });
+
+            dispatcher.process(&op_list);
+        }
+
+        assert_eq!(
+            vec![
+                "add 1",
+                "data 1",
+                "auth Authoritative",
+                "update Some(2)",
+                "request 3 from Caller",
+                "remove",
+                "disconnect Bye",
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn removed_callbacks_are_not_invoked() {
+        let op_list = MockOpList::new()
+            .with_add_entity(EntityId::new(1))
+            .with_add_component(EntityId::new(1), Counter { value: 1 })
+            .into_op_list();
+
+        let mut count = 0;
+        {
+            let mut dispatcher = Dispatcher::new();
+            let key = dispatcher.on_add_component::<Counter>(|_, _| count += 1);
+            let other = dispatcher.on_add_entity(|_| {});
+
+            assert!(dispatcher.remove(key));
+            assert!(!dispatcher.remove(key));
+            assert!(dispatcher.remove(other));
+
+            dispatcher.process(&op_list);
+        }
+
+        assert_eq!(0, count);
+    }
+}
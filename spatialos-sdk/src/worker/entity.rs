@@ -1,13 +1,16 @@
-use crate::worker::component::{self, Component, ComponentId, DATABASE};
+use crate::worker::component::{self, Component, ComponentId, HandleKind, UserHandle};
 use crate::worker::internal::schema::SchemaComponentData;
-use spatialos_sdk_sys::worker::{Schema_DestroyComponentData, Worker_ComponentData, Worker_Entity};
+use spatialos_sdk_sys::worker::{
+    Schema_ComponentData, Schema_DestroyComponentData, Worker_ComponentData,
+    Worker_ComponentVtable, Worker_Entity,
+};
 use std::collections::HashMap;
 use std::ptr;
 use std::slice;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct Entity {
-    components: HashMap<ComponentId, Worker_ComponentData>,
+    components: HashMap<ComponentId, UserHandle>,
 }
 
 impl Entity {
@@ -15,14 +18,29 @@ impl Entity {
         Entity::default()
     }
 
-    pub(crate) unsafe fn from_worker_sdk(raw_entity: &Worker_Entity) -> Result<Self, String> {
+    /// Copies the components of an entity owned by the C API.
+    ///
+    /// `get_vtable` must return the vtable that was used to deserialize each component.
+    pub(crate) unsafe fn from_worker_sdk<F>(
+        raw_entity: &Worker_Entity,
+        get_vtable: F,
+    ) -> Result<Self, String>
+    where
+        F: Fn(ComponentId) -> Option<Worker_ComponentVtable>,
+    {
         let mut entity = Entity::new();
 
         let component_data =
             slice::from_raw_parts(raw_entity.components, raw_entity.component_count as usize);
 
         for data in component_data {
-            entity.add_raw(data)?;
+            let vtable = get_vtable(data.component_id).ok_or_else(|| {
+                format!(
+                    "Could not find a vtable implementation for component {}",
+                    data.component_id
+                )
+            })?;
+            entity.add_raw(data, vtable)?;
         }
 
         Ok(entity)
@@ -30,44 +48,35 @@ impl Entity {
 
     pub(crate) fn add<C: Component>(&mut self, component: C) -> Result<(), String> {
         self.pre_add_check(C::ID)?;
-
-        let data_ptr = component::handle_allocate(component);
-        let raw_data = Worker_ComponentData {
-            reserved: ptr::null_mut(),
-            component_id: C::ID,
-            schema_type: ptr::null_mut(),
-            user_handle: data_ptr as *mut _,
-        };
-
-        self.components.insert(C::ID, raw_data);
-
+        self.components
+            .insert(C::ID, UserHandle::component_data::<C>(component));
         Ok(())
     }
 
     pub(crate) unsafe fn add_raw(
         &mut self,
         component: &Worker_ComponentData,
+        vtable: Worker_ComponentVtable,
     ) -> Result<(), String> {
         let id = component.component_id;
 
         self.pre_add_check(id)?;
 
-        // Call copy on the component data. We don't own this Worker_ComponentData.
-        let vtable = DATABASE.get_vtable(id).unwrap();
-        let copy_data_func = vtable
-            .component_data_copy
-            .unwrap_or_else(|| panic!("No component_data_free method defined for {}", id));
-        copy_data_func(id, ptr::null_mut(), component.user_handle);
-
-        self.components.insert(
-            id,
-            Worker_ComponentData {
-                reserved: ptr::null_mut(),
-                component_id: id,
-                schema_type: ptr::null_mut(),
-                user_handle: component.user_handle,
-            },
-        );
+        // We don't own this Worker_ComponentData, so either take our own reference to it or
+        // deserialize our own copy if the C API only gave us the schema data.
+        let handle = if !component.user_handle.is_null() {
+            UserHandle::copy_with_vtable(
+                HandleKind::ComponentData,
+                id,
+                component.user_handle,
+                vtable,
+            )
+        } else if !component.schema_type.is_null() {
+            Entity::deserialize(id, component.schema_type, vtable)?
+        } else {
+            return Err(format!("Component {} has no data", id));
+        };
+        self.components.insert(id, handle);
 
         Ok(())
     }
@@ -77,27 +86,52 @@ impl Entity {
         component_id: ComponentId,
         component: SchemaComponentData,
     ) -> Result<(), String> {
-        let vtable = DATABASE.get_vtable(component_id).unwrap();
-        let deserialize_func = vtable.component_data_deserialize.unwrap_or_else(|| {
-            Schema_DestroyComponentData(component.internal);
-            panic!(
+        match component::global_vtable(component_id) {
+            Some(vtable) => self.add_serialized_with_vtable(component_id, component, vtable),
+            None => {
+                Schema_DestroyComponentData(component.internal);
+                Err(format!(
+                    "Could not find a vtable implementation for component {}",
+                    component_id
+                ))
+            }
+        }
+    }
+
+    /// Deserializes `component` through `vtable` and adds it to the entity, taking ownership of
+    /// the serialized data.
+    pub(crate) unsafe fn add_serialized_with_vtable(
+        &mut self,
+        component_id: ComponentId,
+        component: SchemaComponentData,
+        vtable: Worker_ComponentVtable,
+    ) -> Result<(), String> {
+        let handle = self
+            .pre_add_check(component_id)
+            .and_then(|_| Entity::deserialize(component_id, component.internal, vtable));
+        Schema_DestroyComponentData(component.internal);
+
+        self.components.insert(component_id, handle?);
+        Ok(())
+    }
+
+    /// Deserializes `component` through `vtable`, leaving the serialized data to the caller.
+    unsafe fn deserialize(
+        component_id: ComponentId,
+        component: *mut Schema_ComponentData,
+        vtable: Worker_ComponentVtable,
+    ) -> Result<UserHandle, String> {
+        let deserialize_func = vtable.component_data_deserialize.ok_or_else(|| {
+            format!(
                 "No component_data_deserialize method defined for {}",
                 component_id
             )
-        });
-
-        // Create the **void that the C API requires. We need to then clean this up later.
-        // The value pointed to by handle_out_ptr is written to during the deserialize method.
-        let placeholder_ptr = Box::into_raw(Box::new(0)) as *mut ::std::os::raw::c_void;
-        let handle_out_ptr = Box::into_raw(Box::new(placeholder_ptr));
+        })?;
 
-        let deserialize_result = deserialize_func(
-            component_id,
-            ptr::null_mut(),
-            component.internal,
-            handle_out_ptr,
-        );
-        Schema_DestroyComponentData(component.internal);
+        // The value pointed to by handle_out is written to during the deserialize method.
+        let mut handle_out = ptr::null_mut();
+        let deserialize_result =
+            deserialize_func(component_id, vtable.user_data, component, &mut handle_out);
 
         match deserialize_result {
             1 => {},
@@ -105,25 +139,58 @@ impl Entity {
             _ => panic!("Unexpected return value from deserialize function. Expected true or false. Received other.")
         };
 
-        let component_data = Worker_ComponentData {
-            reserved: ptr::null_mut(),
+        Ok(UserHandle::from_raw_parts(
+            HandleKind::ComponentData,
             component_id,
-            schema_type: ptr::null_mut(),
-            user_handle: *handle_out_ptr,
-        };
+            handle_out as *mut _,
+            vtable,
+        ))
+    }
+
+    /// Serializes every component through its vtable into the schema wire format, ordered by
+    /// component ID.
+    pub(crate) fn serialize_components(&self) -> Result<Vec<(ComponentId, Vec<u8>)>, String> {
+        let mut serialized = self
+            .components
+            .iter()
+            .map(|(&id, handle)| Ok((id, handle.serialize_data()?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        serialized.sort_by_key(|&(id, _)| id);
+        Ok(serialized)
+    }
+
+    /// Serializes a single component through its vtable, or returns `None` if the entity
+    /// doesn't have it.
+    pub(crate) fn serialize_component(&self, id: ComponentId) -> Option<Result<Vec<u8>, String>> {
+        self.components.get(&id).map(UserHandle::serialize_data)
+    }
+
+    /// Returns a copy of the entity that only has the components in `component_ids`.
+    pub(crate) fn project(&self, component_ids: &[ComponentId]) -> Entity {
+        Entity {
+            components: self
+                .components
+                .iter()
+                .filter(|(id, _)| component_ids.contains(id))
+                .map(|(&id, handle)| (id, handle.clone()))
+                .collect(),
+        }
+    }
 
-        // Reconstruct these objects so they can be de-alloc'ed. We have pulled the data required
-        // from them by de-referencing handle_out_ptr above.
-        Box::from_raw(placeholder_ptr);
-        Box::from_raw(handle_out_ptr);
+    pub(crate) fn add_handle(&mut self, handle: UserHandle) -> Result<(), String> {
+        self.pre_add_check(handle.component_id())?;
+        self.components.insert(handle.component_id(), handle);
+        Ok(())
+    }
 
-        self.add_raw(&component_data)
+    pub fn has_component(&self, id: ComponentId) -> bool {
+        self.components.contains_key(&id)
     }
 
     pub fn get<C: Component>(&self) -> Option<&C> {
         self.components
             .get(&C::ID)
-            .map(|data| unsafe { &*(data.user_handle as *const _) })
+            .and_then(|handle| unsafe { handle.get::<C, C>() })
     }
 
     pub(crate) fn raw_component_data(&self) -> RawEntity {
@@ -138,95 +205,76 @@ impl Entity {
             ));
         }
 
-        if !DATABASE.has_vtable(id) {
-            panic!(format!(
-                "Could not find a vtable implementation for component {}",
-                id
-            ));
-        }
-
         Ok(())
     }
 }
 
-impl Default for Entity {
-    fn default() -> Self {
-        Entity {
-            components: HashMap::new(),
-        }
-    }
-}
-
-impl Drop for Entity {
-    fn drop(&mut self) {
-        for component_data in self.components.values() {
-            let id = component_data.component_id;
-
-            let vtable = DATABASE.get_vtable(id).unwrap();
-
-            let free_data_func = vtable
-                .component_data_free
-                .unwrap_or_else(|| panic!("No component_data_free method defined for {}", id));
-
-            unsafe { free_data_func(id, ptr::null_mut(), component_data.user_handle) };
-        }
-    }
-}
-
 // Required for when we call Entity::raw_component_data() and want a Vec<Worker_ComponentData> rather
 // than a Vec<&Worker_ComponentData> which most callers *will* want due to how Worker_Entity is structured.
 pub(crate) struct RawEntity {
     pub components: Vec<Worker_ComponentData>,
+
+    // Keeps the data referenced by `components` alive.
+    _handles: Vec<UserHandle>,
 }
 
 impl RawEntity {
-    pub fn new<'a, I>(original_data: I) -> Self
+    pub fn new<'a, I>(handles: I) -> Self
     where
-        I: Iterator<Item = &'a Worker_ComponentData>,
+        I: Iterator<Item = &'a UserHandle>,
     {
-        // Go through each Worker_ComponentData object, make a copy and call handle_copy using the vtable.
-        let new_data = original_data
-            .map(|original_component_data| {
-                let new_component_data = *original_component_data; // Is a copy operation.
-                let id = original_component_data.component_id;
-
-                let vtable = DATABASE.get_vtable(id).unwrap();
-
-                let copy_data_func = vtable
-                    .component_data_copy
-                    .unwrap_or_else(|| panic!("No component_data_copy method defined for {}", id));
-
-                unsafe { copy_data_func(id, ptr::null_mut(), original_component_data.user_handle) };
-
-                new_component_data
+        let handles: Vec<UserHandle> = handles.cloned().collect();
+        let components = handles
+            .iter()
+            .map(|handle| Worker_ComponentData {
+                reserved: ptr::null_mut(),
+                component_id: handle.component_id(),
+                schema_type: ptr::null_mut(),
+                user_handle: handle.as_ptr() as *mut _,
             })
             .collect();
 
         RawEntity {
-            components: new_data,
+            components,
+            _handles: handles,
         }
     }
 }
 
-impl Drop for RawEntity {
-    fn drop(&mut self) {
-        for component_data in &self.components {
-            let vtable = DATABASE.get_vtable(component_data.component_id).unwrap();
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::worker::mock::test::Counter;
 
-            let free_data_func = vtable.component_data_free.unwrap_or_else(|| {
-                panic!(
-                    "No component_data_free method defined for {}",
-                    component_data.component_id
-                )
-            });
+    #[test]
+    fn components_can_be_added_and_read_back() {
+        let mut entity = Entity::new();
+        entity.add(Counter { value: 3 }).unwrap();
 
-            unsafe {
-                free_data_func(
-                    component_data.component_id,
-                    ptr::null_mut(),
-                    component_data.user_handle,
-                )
-            };
+        assert_eq!(Some(&Counter { value: 3 }), entity.get::<Counter>());
+        assert!(entity.add(Counter { value: 4 }).is_err());
+    }
+
+    #[test]
+    fn raw_component_data_shares_component_data() {
+        let mut entity = Entity::new();
+        entity.add(Counter { value: 3 }).unwrap();
+
+        let raw = entity.raw_component_data();
+        let copy = unsafe {
+            Entity::from_worker_sdk(
+                &Worker_Entity {
+                    entity_id: 1,
+                    component_count: raw.components.len() as u32,
+                    components: raw.components.as_ptr(),
+                },
+                component::global_vtable,
+            )
         }
+        .unwrap();
+        drop(raw);
+        drop(entity);
+
+        assert_eq!(Some(&Counter { value: 3 }), copy.get::<Counter>());
     }
 }
@@ -9,9 +9,9 @@ use crate::worker::{
 };
 use std::collections::{HashMap, HashSet};
 
-const ENTITY_ACL_COMPONENT_ID: ComponentId = 50;
+pub(crate) const ENTITY_ACL_COMPONENT_ID: ComponentId = 50;
 const METADATA_COMPONENT_ID: ComponentId = 53;
-const POSITION_COMPONENT_ID: ComponentId = 54;
+pub(crate) const POSITION_COMPONENT_ID: ComponentId = 54;
 const PERSISTENCE_COMPONENT_ID: ComponentId = 55;
 
 pub struct EntityBuilder {
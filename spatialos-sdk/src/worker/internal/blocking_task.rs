@@ -0,0 +1,143 @@
+//! Support for exposing the blocking futures of the C API as `std::future::Future`s.
+//!
+//! The C API only allows its futures to be waited on with a timeout, and provides no way
+//! to be notified when a result becomes available. To avoid spinning inside an executor,
+//! the blocking wait is moved onto a dedicated thread, which wakes the task once the
+//! result is ready.
+
+use std::{
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+/// A raw pointer that can be moved onto the thread running a [`BlockingTask`].
+///
+/// The C API futures may be waited on from any thread, as long as only one thread uses
+/// them at a time. Ownership of the pointer is moved to the task thread, which is then
+/// responsible for destroying it.
+pub(crate) struct SendPtr<T>(pub *mut T);
+
+// SAFE: The pointer is only ever used by the thread it has been sent to.
+unsafe impl<T> Send for SendPtr<T> {}
+
+/// A blocking computation running on a background thread.
+pub(crate) struct BlockingTask<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+struct Shared<T> {
+    result: Option<T>,
+    waker: Option<Waker>,
+}
+
+impl<T: Send + 'static> BlockingTask<T> {
+    /// Runs `f` on a new thread, waking `waker` once it has completed.
+    pub(crate) fn spawn<F>(f: F, waker: Waker) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let shared = Arc::new(Mutex::new(Shared {
+            result: None,
+            waker: Some(waker),
+        }));
+
+        let thread_shared = shared.clone();
+        let handle = thread::spawn(move || {
+            let result = f();
+
+            // Take the waker while holding the lock, so that a concurrent `poll` either
+            // sees the result or leaves a waker for us to wake.
+            let waker = {
+                let mut shared = thread_shared.lock().unwrap();
+                shared.result = Some(result);
+                shared.waker.take()
+            };
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+
+        BlockingTask {
+            shared,
+            handle: Some(handle),
+        }
+    }
+
+    /// Returns the result if it is ready, otherwise registers the task in `cx` to be woken
+    /// once it is.
+    pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Returns the result if it is ready, without registering for a wakeup.
+    pub(crate) fn try_take(&mut self) -> Option<T> {
+        self.shared.lock().unwrap().result.take()
+    }
+
+    /// Blocks the current thread until the result is ready.
+    pub(crate) fn wait(mut self) -> T {
+        if let Some(handle) = self.handle.take() {
+            handle
+                .join()
+                .expect("Background thread waiting on a native future panicked");
+        }
+
+        self.try_take()
+            .expect("Result of a background task has already been taken")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        sync::{atomic::AtomicUsize, atomic::Ordering, mpsc},
+        task::Wake,
+    };
+
+    #[derive(Default)]
+    struct CountingWaker {
+        wakes: AtomicUsize,
+    }
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.wakes.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn task_wakes_once_result_is_ready() {
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let (sender, receiver) = mpsc::channel();
+        let mut task = BlockingTask::spawn(move || receiver.recv().unwrap(), waker.clone());
+        assert_eq!(Poll::Pending, task.poll(&mut cx));
+
+        sender.send(5).unwrap();
+        let handle = task.handle.take().unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(1, counter.wakes.load(Ordering::SeqCst));
+        assert_eq!(Poll::Ready(5), task.poll(&mut cx));
+    }
+
+    #[test]
+    fn wait_blocks_until_result_is_ready() {
+        let waker = Waker::from(Arc::new(CountingWaker::default()));
+        let task = BlockingTask::spawn(|| "done", waker);
+        assert_eq!("done", task.wait());
+    }
+}
@@ -1,2 +1,3 @@
+pub(crate) mod blocking_task;
 pub mod schema;
 pub(crate) mod utils;
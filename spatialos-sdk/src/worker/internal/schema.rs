@@ -1,7 +1,9 @@
 use crate::worker::component::ComponentId;
+use crate::worker::internal::utils::cstr_to_string;
 use crate::worker::EntityId;
 use spatialos_sdk_sys::worker::*;
 use std::marker::PhantomData;
+use std::ptr;
 use std::slice;
 
 pub type FieldId = u32;
@@ -62,7 +64,32 @@ impl SchemaComponentUpdate {
         }
     }
 
-    // TODO: Cleared fields.
+    /// Returns `true` if the update sets the option, list or map field to the empty value.
+    pub fn is_field_cleared(&self, field_id: FieldId) -> bool {
+        unsafe { Schema_IsComponentUpdateFieldCleared(self.internal, field_id) != 0 }
+    }
+
+    /// Marks an option, list or map field as being set to the empty value by the update.
+    ///
+    /// Empty values can't be written to the fields object, so this is the only way to
+    /// clear a field.
+    pub fn add_cleared_field(&mut self, field_id: FieldId) {
+        unsafe { Schema_AddComponentUpdateClearedField(self.internal, field_id) }
+    }
+
+    /// Returns the IDs of all fields that the update sets to the empty value.
+    pub fn cleared_fields(&self) -> Vec<FieldId> {
+        unsafe {
+            let count = Schema_GetComponentUpdateClearedFieldCount(self.internal);
+            let mut fields = vec![0; count as usize];
+            Schema_GetComponentUpdateClearedFieldList(self.internal, fields.as_mut_ptr());
+            fields
+        }
+    }
+
+    pub fn clear_cleared_fields(&mut self) {
+        unsafe { Schema_ClearComponentUpdateClearedFields(self.internal) }
+    }
 }
 
 impl Default for SchemaComponentUpdate {
@@ -271,6 +298,47 @@ impl SchemaObject {
             _phantom: PhantomData,
         }
     }
+
+    /// Serializes the object into the schema wire format.
+    pub fn serialize(&self) -> Result<Vec<u8>, String> {
+        unsafe {
+            let length = Schema_GetWriteBufferLength(self.internal);
+            let mut buffer = vec![0u8; length as usize];
+            if Schema_SerializeToBuffer(self.internal, buffer.as_mut_ptr(), length) == 0 {
+                return Err(self.error());
+            }
+            Ok(buffer)
+        }
+    }
+
+    /// Merges data in the schema wire format into the object, appending to any existing fields.
+    pub fn merge_from_buffer(&mut self, buffer: &[u8]) -> Result<(), String> {
+        unsafe {
+            // The buffer must live as long as the object, so copy it into memory owned by the object.
+            let length = buffer.len() as u32;
+            let owned = Schema_AllocateBuffer(self.internal, length);
+            ptr::copy_nonoverlapping(buffer.as_ptr(), owned, buffer.len());
+            if Schema_MergeFromBuffer(self.internal, owned, length) == 0 {
+                return Err(self.error());
+            }
+            Ok(())
+        }
+    }
+
+    /// Copies every value of field `field_id` to `destination`. The copy is shallow, so object
+    /// values are shared with `self` and must not be used after `self` is destroyed.
+    pub fn shallow_copy_field(&self, field_id: FieldId, destination: &mut SchemaObject) {
+        unsafe { Schema_ShallowCopyField(self.internal, destination.internal, field_id) }
+    }
+
+    fn error(&self) -> String {
+        let error = unsafe { Schema_GetError(self.internal) };
+        if error.is_null() {
+            "Unknown schema error".to_owned()
+        } else {
+            cstr_to_string(error)
+        }
+    }
 }
 
 macro_rules! impl_primitive_field {
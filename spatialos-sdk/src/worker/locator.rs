@@ -1,10 +1,19 @@
-use std::ffi::CString;
+use std::{
+    ffi::CString,
+    mem,
+    pin::Pin,
+    ptr,
+    task::{ready, Context, Poll},
+};
 
 use futures::{Async, Future};
 
 use spatialos_sdk_sys::worker::*;
 
-use crate::worker::internal::utils::cstr_to_string;
+use crate::worker::internal::{
+    blocking_task::{BlockingTask, SendPtr},
+    utils::cstr_to_string,
+};
 use crate::worker::parameters::ProtocolLoggingParameters;
 
 pub struct Locator {
@@ -154,6 +163,7 @@ impl Deployment {
 pub struct DeploymentListFuture {
     internal: *mut Worker_DeploymentListFuture,
     consumed: bool,
+    task: Option<BlockingTask<Result<Vec<Deployment>, String>>>,
 }
 
 impl DeploymentListFuture {
@@ -180,6 +190,26 @@ impl DeploymentListFuture {
             *data = Some(Ok(deployments));
         }
     }
+
+    fn get_future(
+        internal: *mut Worker_DeploymentListFuture,
+        timeout: Option<u32>,
+    ) -> Option<Result<Vec<Deployment>, String>> {
+        assert!(!internal.is_null());
+        let mut data: Option<Result<Vec<Deployment>, String>> = None;
+        unsafe {
+            Worker_DeploymentListFuture_Get(
+                internal,
+                timeout
+                    .as_ref()
+                    .map_or(::std::ptr::null(), |value| value as *const u32),
+                &mut data as *mut _ as *mut ::std::os::raw::c_void,
+                Some(DeploymentListFuture::callback_handler),
+            );
+        }
+
+        data
+    }
 }
 
 impl Future for DeploymentListFuture {
@@ -187,47 +217,83 @@ impl Future for DeploymentListFuture {
     type Error = String;
 
     fn poll(&mut self) -> Result<Async<<Self as Future>::Item>, <Self as Future>::Error> {
-        if self.consumed {
-            return Err("DeploymentListFuture has already been consumed".to_owned());
+        if let Some(task) = &mut self.task {
+            return match task.try_take() {
+                Some(result) => {
+                    self.consumed = true;
+                    self.task = None;
+                    result.map(Async::Ready)
+                }
+                None => Ok(Async::NotReady),
+            };
         }
 
-        assert!(!self.internal.is_null());
-        let mut data: Option<Result<Vec<Deployment>, String>> = None;
-        unsafe {
-            Worker_DeploymentListFuture_Get(
-                self.internal,
-                &0,
-                &mut data as *mut _ as *mut ::std::os::raw::c_void,
-                Some(DeploymentListFuture::callback_handler),
-            );
+        if self.consumed {
+            return Err("DeploymentListFuture has already been consumed".to_owned());
         }
 
-        data.map_or(Ok(Async::NotReady), |result| {
-            self.consumed = true;
-            result.map(Async::Ready)
-        })
+        DeploymentListFuture::get_future(self.internal, Some(0)).map_or(
+            Ok(Async::NotReady),
+            |result| {
+                self.consumed = true;
+                result.map(Async::Ready)
+            },
+        )
     }
 
-    fn wait(self) -> Result<<Self as Future>::Item, <Self as Future>::Error>
+    fn wait(mut self) -> Result<<Self as Future>::Item, <Self as Future>::Error>
     where
         Self: Sized,
     {
+        if let Some(task) = self.task.take() {
+            return task.wait();
+        }
+
         if self.consumed {
             return Err("DeploymentListFuture has already been consumed".to_owned());
         }
 
-        assert!(!self.internal.is_null());
-        let mut data: Option<Result<Vec<Deployment>, String>> = None;
-        unsafe {
-            Worker_DeploymentListFuture_Get(
-                self.internal,
-                ::std::ptr::null(),
-                &mut data as *mut _ as *mut ::std::os::raw::c_void,
-                Some(DeploymentListFuture::callback_handler),
-            );
+        DeploymentListFuture::get_future(self.internal, None)
+            .expect("Blocking call to Worker_DeploymentListFuture_Get did not trigger callback")
+    }
+}
+
+impl std::future::Future for DeploymentListFuture {
+    type Output = Result<Vec<Deployment>, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(task) = &mut self.task {
+            let result = ready!(task.poll(cx));
+            self.consumed = true;
+            self.task = None;
+            return Poll::Ready(result);
+        }
+
+        if self.consumed {
+            return Poll::Ready(Err(
+                "DeploymentListFuture has already been consumed".to_owned()
+            ));
+        }
+
+        if let Some(result) = DeploymentListFuture::get_future(self.internal, Some(0)) {
+            self.consumed = true;
+            return Poll::Ready(result);
         }
 
-        data.expect("Blocking call to Worker_DeploymentListFuture_Get did not trigger callback")
+        let internal = SendPtr(mem::replace(&mut self.internal, ptr::null_mut()));
+        let task = BlockingTask::spawn(
+            move || unsafe {
+                let result = DeploymentListFuture::get_future(internal.0, None).expect(
+                    "Blocking call to Worker_DeploymentListFuture_Get did not trigger callback",
+                );
+                Worker_DeploymentListFuture_Destroy(internal.0);
+                result
+            },
+            cx.waker().clone(),
+        );
+        self.task = Some(task);
+
+        Poll::Pending
     }
 }
 
@@ -320,6 +386,7 @@ impl PlayerIdentityTokenResponse {
 pub struct PlayerIdentityTokenFuture {
     internal: *mut Worker_Alpha_PlayerIdentityTokenResponseFuture,
     consumed: bool,
+    task: Option<BlockingTask<Result<PlayerIdentityTokenResponse, String>>>,
 }
 
 impl PlayerIdentityTokenFuture {
@@ -328,6 +395,7 @@ impl PlayerIdentityTokenFuture {
         PlayerIdentityTokenFuture {
             internal: ptr,
             consumed: false,
+            task: None,
         }
     }
 
@@ -354,16 +422,18 @@ impl PlayerIdentityTokenFuture {
     }
 
     fn get_future(
-        &self,
+        internal: *mut Worker_Alpha_PlayerIdentityTokenResponseFuture,
         timeout: Option<u32>,
     ) -> Option<Result<PlayerIdentityTokenResponse, String>> {
-        assert!(!self.internal.is_null());
+        assert!(!internal.is_null());
 
         let mut data: Option<Result<PlayerIdentityTokenResponse, String>> = None;
         unsafe {
             Worker_Alpha_PlayerIdentityTokenResponseFuture_Get(
-                self.internal,
-                timeout.map_or(::std::ptr::null(), |value| &value),
+                internal,
+                timeout
+                    .as_ref()
+                    .map_or(::std::ptr::null(), |value| value as *const u32),
                 &mut data as *mut _ as *mut ::std::os::raw::c_void,
                 Some(PlayerIdentityTokenFuture::callback_handler),
             );
@@ -378,32 +448,88 @@ impl Future for PlayerIdentityTokenFuture {
     type Error = String;
 
     fn poll(&mut self) -> Result<Async<<Self as Future>::Item>, <Self as Future>::Error> {
+        if let Some(task) = &mut self.task {
+            return match task.try_take() {
+                Some(result) => {
+                    self.consumed = true;
+                    self.task = None;
+                    result.map(Async::Ready)
+                }
+                None => Ok(Async::NotReady),
+            };
+        }
+
         if self.consumed {
             return Err("PlayerIdentityTokenFuture has already been consumed".to_owned());
         }
 
-        self.get_future(Some(0))
-            .map_or(Ok(Async::NotReady), |result| {
+        PlayerIdentityTokenFuture::get_future(self.internal, Some(0)).map_or(
+            Ok(Async::NotReady),
+            |result| {
                 self.consumed = true;
                 result.map(Async::Ready)
-            })
+            },
+        )
     }
 
-    fn wait(self) -> Result<<Self as Future>::Item, <Self as Future>::Error>
+    fn wait(mut self) -> Result<<Self as Future>::Item, <Self as Future>::Error>
     where
         Self: Sized,
     {
+        if let Some(task) = self.task.take() {
+            return task.wait();
+        }
+
         if self.consumed {
             return Err("PlayerIdentityTokenFuture has already been consumed".to_owned());
         }
 
-        self.get_future(None).expect("Blocking call to Worker_Alpha_PlayerIdentityTokenResponseFuture_Get did not trigger callback")
+        PlayerIdentityTokenFuture::get_future(self.internal, None).expect("Blocking call to Worker_Alpha_PlayerIdentityTokenResponseFuture_Get did not trigger callback")
+    }
+}
+
+impl std::future::Future for PlayerIdentityTokenFuture {
+    type Output = Result<PlayerIdentityTokenResponse, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(task) = &mut self.task {
+            let result = ready!(task.poll(cx));
+            self.consumed = true;
+            self.task = None;
+            return Poll::Ready(result);
+        }
+
+        if self.consumed {
+            return Poll::Ready(Err(
+                "PlayerIdentityTokenFuture has already been consumed".to_owned()
+            ));
+        }
+
+        if let Some(result) = PlayerIdentityTokenFuture::get_future(self.internal, Some(0)) {
+            self.consumed = true;
+            return Poll::Ready(result);
+        }
+
+        let internal = SendPtr(mem::replace(&mut self.internal, ptr::null_mut()));
+        let task = BlockingTask::spawn(
+            move || unsafe {
+                let result = PlayerIdentityTokenFuture::get_future(internal.0, None).expect("Blocking call to Worker_Alpha_PlayerIdentityTokenResponseFuture_Get did not trigger callback");
+                Worker_Alpha_PlayerIdentityTokenResponseFuture_Destroy(internal.0);
+                result
+            },
+            cx.waker().clone(),
+        );
+        self.task = Some(task);
+
+        Poll::Pending
     }
 }
 
 impl Drop for PlayerIdentityTokenFuture {
     fn drop(&mut self) {
-        unsafe { Worker_Alpha_PlayerIdentityTokenResponseFuture_Destroy(self.internal) }
+        if !self.internal.is_null() {
+            unsafe { Worker_Alpha_PlayerIdentityTokenResponseFuture_Destroy(self.internal) }
+        }
     }
 }
 
@@ -500,6 +626,7 @@ impl LoginTokenDetails {
 pub struct LoginTokensFuture {
     internal: *mut Worker_Alpha_LoginTokensResponseFuture,
     consumed: bool,
+    task: Option<BlockingTask<Result<LoginTokensResponse, String>>>,
 }
 
 impl LoginTokensFuture {
@@ -508,6 +635,7 @@ impl LoginTokensFuture {
         LoginTokensFuture {
             internal: ptr,
             consumed: false,
+            task: None,
         }
     }
 
@@ -532,19 +660,25 @@ impl LoginTokensFuture {
         }
     }
 
-    fn get_future(&self, timeout: Option<u32>) -> Option<Result<LoginTokensResponse, String>> {
-        assert!(!self.internal.is_null());
+    fn get_future(
+        internal: *mut Worker_Alpha_LoginTokensResponseFuture,
+        timeout: Option<u32>,
+    ) -> Option<Result<LoginTokensResponse, String>> {
+        assert!(!internal.is_null());
+
         let mut data: Option<Result<LoginTokensResponse, String>> = None;
         unsafe {
             Worker_Alpha_LoginTokensResponseFuture_Get(
-                self.internal,
-                timeout.map_or(::std::ptr::null(), |value| &value),
+                internal,
+                timeout
+                    .as_ref()
+                    .map_or(::std::ptr::null(), |value| value as *const u32),
                 &mut data as *mut _ as *mut ::std::os::raw::c_void,
                 Some(LoginTokensFuture::callback_handler),
             );
-
-            data
         }
+
+        data
     }
 }
 
@@ -553,32 +687,88 @@ impl Future for LoginTokensFuture {
     type Error = String;
 
     fn poll(&mut self) -> Result<Async<<Self as Future>::Item>, <Self as Future>::Error> {
+        if let Some(task) = &mut self.task {
+            return match task.try_take() {
+                Some(result) => {
+                    self.consumed = true;
+                    self.task = None;
+                    result.map(Async::Ready)
+                }
+                None => Ok(Async::NotReady),
+            };
+        }
+
         if self.consumed {
             return Err("LoginTokensFuture has already been consumed".to_owned());
         }
 
-        self.get_future(Some(0))
-            .map_or(Ok(Async::NotReady), |result| {
+        LoginTokensFuture::get_future(self.internal, Some(0)).map_or(
+            Ok(Async::NotReady),
+            |result| {
                 self.consumed = true;
                 result.map(Async::Ready)
-            })
+            },
+        )
     }
 
-    fn wait(self) -> Result<<Self as Future>::Item, <Self as Future>::Error>
+    fn wait(mut self) -> Result<<Self as Future>::Item, <Self as Future>::Error>
     where
         Self: Sized,
     {
+        if let Some(task) = self.task.take() {
+            return task.wait();
+        }
+
         if self.consumed {
             return Err("LoginTokensFuture has already been consumed".to_owned());
         }
 
-        self.get_future(None)
+        LoginTokensFuture::get_future(self.internal, None)
             .expect("Blocking call to Worker_Alpha_LoginTokensFuture_Get did not trigger callback")
     }
 }
 
+impl std::future::Future for LoginTokensFuture {
+    type Output = Result<LoginTokensResponse, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(task) = &mut self.task {
+            let result = ready!(task.poll(cx));
+            self.consumed = true;
+            self.task = None;
+            return Poll::Ready(result);
+        }
+
+        if self.consumed {
+            return Poll::Ready(Err("LoginTokensFuture has already been consumed".to_owned()));
+        }
+
+        if let Some(result) = LoginTokensFuture::get_future(self.internal, Some(0)) {
+            self.consumed = true;
+            return Poll::Ready(result);
+        }
+
+        let internal = SendPtr(mem::replace(&mut self.internal, ptr::null_mut()));
+        let task = BlockingTask::spawn(
+            move || unsafe {
+                let result = LoginTokensFuture::get_future(internal.0, None).expect(
+                    "Blocking call to Worker_Alpha_LoginTokensFuture_Get did not trigger callback",
+                );
+                Worker_Alpha_LoginTokensResponseFuture_Destroy(internal.0);
+                result
+            },
+            cx.waker().clone(),
+        );
+        self.task = Some(task);
+
+        Poll::Pending
+    }
+}
+
 impl Drop for LoginTokensFuture {
     fn drop(&mut self) {
-        unsafe { Worker_Alpha_LoginTokensResponseFuture_Destroy(self.internal) }
+        if !self.internal.is_null() {
+            unsafe { Worker_Alpha_LoginTokensResponseFuture_Destroy(self.internal) }
+        }
     }
 }
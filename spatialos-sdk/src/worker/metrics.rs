@@ -13,7 +13,7 @@ pub enum MetricsError {
     KeyAlreadyExists,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct Metrics {
     pub load: Option<f64>,
     pub gauge_metrics: HashMap<String, f64>,
@@ -171,7 +171,7 @@ impl From<&Worker_Metrics> for Metrics {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HistogramMetric {
     pub sum: f64,
     pub buckets: Vec<HistogramMetricBucket>,
@@ -258,7 +258,7 @@ impl From<&Worker_HistogramMetric> for HistogramMetric {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HistogramMetricBucket {
     pub upper_bound: f64,
     pub samples: u32,
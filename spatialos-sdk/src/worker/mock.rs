@@ -0,0 +1,1366 @@
+//! In-memory stand-ins for a SpatialOS connection, for unit-testing worker logic.
+//!
+//! [`MockConnection`] implements [`Connection`] without linking against a running
+//! SpatialOS runtime. Every outgoing message is recorded in a public queue that tests can
+//! inspect, and [`MockOpList`] can be used to build synthetic ops that are returned from
+//! [`Connection::get_op_list`].
+//!
+//! # Examples
+//!
+//! ```
+//! use spatialos_sdk::worker::{
+//!     connection::Connection,
+//!     mock::{MockConnection, MockOpList},
+//!     op::WorkerOp,
+//!     EntityId, LogLevel,
+//! };
+//!
+//! let mut connection = MockConnection::new("TestWorker");
+//! connection.push_op_list(MockOpList::new().with_add_entity(EntityId::new(1)));
+//!
+//! let op_list = connection.get_op_list(0);
+//! for op in &op_list {
+//!     if let WorkerOp::AddEntity(op) = op {
+//!         connection.send_log_message(LogLevel::Info, "test", "Entity added", Some(op.entity_id));
+//!     }
+//! }
+//!
+//! assert_eq!(1, connection.log_messages.len());
+//! ```
+
+use crate::worker::{
+    commands::*,
+    component::{Component, ComponentId, UpdateParameters, UserHandle},
+    connection::{Connection, ConnectionStatus, ConnectionStatusCode},
+    entity::{Entity, RawEntity},
+    metrics::{Metrics, WrappedWorkerMetrics},
+    op::*,
+    Authority, EntityId, InterestOverride, LogLevel, RequestId,
+};
+use spatialos_sdk_sys::worker::*;
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::{CString, NulError},
+    fmt,
+    os::raw,
+    ptr,
+};
+
+/// A log message sent through [`Connection::send_log_message`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogMessage {
+    pub level: LogLevel,
+    pub logger_name: String,
+    pub message: String,
+    pub entity_id: Option<EntityId>,
+}
+
+/// A world command request sent through a [`MockConnection`].
+#[derive(Debug)]
+pub struct SentRequest<T> {
+    pub request_id: RequestId<T>,
+    pub payload: T,
+    pub timeout_millis: Option<u32>,
+}
+
+/// A create entity request sent through [`Connection::send_create_entity_request`].
+#[derive(Debug)]
+pub struct SentCreateEntityRequest {
+    pub request_id: RequestId<CreateEntityRequest>,
+    pub entity: Entity,
+    pub entity_id: Option<EntityId>,
+    pub timeout_millis: Option<u32>,
+}
+
+/// A component command request sent through [`Connection::send_command_request`].
+#[derive(Debug)]
+pub struct SentCommandRequest {
+    pub request_id: RequestId<OutgoingCommandRequest>,
+    pub entity_id: EntityId,
+    pub component_id: ComponentId,
+    pub command_index: u32,
+    pub timeout_millis: Option<u32>,
+    pub params: CommandParameters,
+    request: UserHandle,
+}
+
+impl SentCommandRequest {
+    pub fn get<C: Component>(&self) -> Option<&C::CommandRequest> {
+        unsafe { self.request.get::<C, C::CommandRequest>() }
+    }
+}
+
+/// A component command response sent through [`Connection::send_command_response`].
+#[derive(Debug)]
+pub struct SentCommandResponse {
+    pub request_id: RequestId<IncomingCommandRequest>,
+    pub component_id: ComponentId,
+    pub command_index: u32,
+    response: UserHandle,
+}
+
+impl SentCommandResponse {
+    pub fn get<C: Component>(&self) -> Option<&C::CommandResponse> {
+        unsafe { self.response.get::<C, C::CommandResponse>() }
+    }
+}
+
+/// A command failure sent through [`Connection::send_command_failure`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentCommandFailure {
+    pub request_id: RequestId<IncomingCommandRequest>,
+    pub message: String,
+}
+
+/// A component update sent through [`Connection::send_component_update`].
+#[derive(Debug)]
+pub struct SentComponentUpdate {
+    pub entity_id: EntityId,
+    pub component_id: ComponentId,
+    pub parameters: UpdateParameters,
+    update: UserHandle,
+}
+
+impl SentComponentUpdate {
+    pub fn get<C: Component>(&self) -> Option<&C::Update> {
+        unsafe { self.update.get::<C, C::Update>() }
+    }
+}
+
+/// A component interest change sent through [`Connection::send_component_interest`].
+#[derive(Debug, Clone)]
+pub struct SentComponentInterest {
+    pub entity_id: EntityId,
+    pub interest_overrides: Vec<InterestOverride>,
+}
+
+/// A [`Connection`] implementation that never talks to SpatialOS.
+///
+/// All outgoing messages are appended to the public queues on the struct, in the order
+/// they were sent. Request IDs are allocated sequentially starting from 1 and are shared
+/// between all request types, mirroring the behaviour of a real connection.
+///
+/// Ops are injected with [`push_op_list`], and each call to [`get_op_list`] pops the
+/// oldest injected op list. If no op lists are queued, an empty op list is returned.
+///
+/// [`push_op_list`]: #method.push_op_list
+/// [`get_op_list`]: ../connection/trait.Connection.html#tymethod.get_op_list
+#[derive(Debug)]
+pub struct MockConnection {
+    pub log_messages: Vec<LogMessage>,
+    pub metrics: Vec<Metrics>,
+    pub reserve_entity_ids_requests: Vec<SentRequest<ReserveEntityIdsRequest>>,
+    pub create_entity_requests: Vec<SentCreateEntityRequest>,
+    pub delete_entity_requests: Vec<SentRequest<DeleteEntityRequest>>,
+    pub entity_query_requests: Vec<SentRequest<EntityQueryRequest>>,
+    pub command_requests: Vec<SentCommandRequest>,
+    pub command_responses: Vec<SentCommandResponse>,
+    pub command_failures: Vec<SentCommandFailure>,
+    pub component_updates: Vec<SentComponentUpdate>,
+    pub component_interests: Vec<SentComponentInterest>,
+    pub authority_loss_imminent_acknowledgements: Vec<(EntityId, ComponentId)>,
+    pub protocol_logging_enabled: bool,
+
+    id: String,
+    attributes: Vec<String>,
+    flags: HashMap<String, String>,
+    status: ConnectionStatus,
+    op_lists: VecDeque<MockOpList>,
+    next_request_id: i64,
+}
+
+impl MockConnection {
+    pub fn new<T: Into<String>>(worker_id: T) -> Self {
+        MockConnection {
+            log_messages: Vec::new(),
+            metrics: Vec::new(),
+            reserve_entity_ids_requests: Vec::new(),
+            create_entity_requests: Vec::new(),
+            delete_entity_requests: Vec::new(),
+            entity_query_requests: Vec::new(),
+            command_requests: Vec::new(),
+            command_responses: Vec::new(),
+            command_failures: Vec::new(),
+            component_updates: Vec::new(),
+            component_interests: Vec::new(),
+            authority_loss_imminent_acknowledgements: Vec::new(),
+            protocol_logging_enabled: false,
+
+            id: worker_id.into(),
+            attributes: Vec::new(),
+            flags: HashMap::new(),
+            status: ConnectionStatus {
+                code: ConnectionStatusCode::Success,
+                detail: "OK".to_owned(),
+            },
+            op_lists: VecDeque::new(),
+            next_request_id: 1,
+        }
+    }
+
+    pub fn with_attribute<T: Into<String>>(mut self, attribute: T) -> Self {
+        self.attributes.push(attribute.into());
+        self
+    }
+
+    pub fn with_worker_flag<T: Into<String>, U: Into<String>>(mut self, name: T, value: U) -> Self {
+        self.set_worker_flag(name, value);
+        self
+    }
+
+    pub fn set_worker_flag<T: Into<String>, U: Into<String>>(&mut self, name: T, value: U) {
+        self.flags.insert(name.into(), value.into());
+    }
+
+    pub fn set_connection_status(&mut self, status: ConnectionStatus) {
+        self.status = status;
+    }
+
+    /// Queues an op list to be returned by a later call to `get_op_list`.
+    pub fn push_op_list(&mut self, op_list: MockOpList) {
+        self.op_lists.push_back(op_list);
+    }
+
+    /// Returns the number of injected op lists that have not been returned yet.
+    pub fn pending_op_lists(&self) -> usize {
+        self.op_lists.len()
+    }
+
+    fn next_request_id<T>(&mut self) -> RequestId<T> {
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        RequestId::new(id)
+    }
+}
+
+impl Connection for MockConnection {
+    fn send_log_message(
+        &mut self,
+        level: LogLevel,
+        logger_name: &str,
+        message: &str,
+        entity_id: Option<EntityId>,
+    ) {
+        self.log_messages.push(LogMessage {
+            level,
+            logger_name: logger_name.to_owned(),
+            message: message.to_owned(),
+            entity_id,
+        });
+    }
+
+    fn send_metrics(&mut self, metrics: &Metrics) {
+        self.metrics.push(metrics.clone());
+    }
+
+    fn send_reserve_entity_ids_request(
+        &mut self,
+        payload: ReserveEntityIdsRequest,
+        timeout_millis: Option<u32>,
+    ) -> RequestId<ReserveEntityIdsRequest> {
+        let request_id = self.next_request_id();
+        self.reserve_entity_ids_requests.push(SentRequest {
+            request_id,
+            payload,
+            timeout_millis,
+        });
+        request_id
+    }
+
+    fn send_create_entity_request(
+        &mut self,
+        entity: Entity,
+        entity_id: Option<EntityId>,
+        timeout_millis: Option<u32>,
+    ) -> RequestId<CreateEntityRequest> {
+        let request_id = self.next_request_id();
+        self.create_entity_requests.push(SentCreateEntityRequest {
+            request_id,
+            entity,
+            entity_id,
+            timeout_millis,
+        });
+        request_id
+    }
+
+    fn send_delete_entity_request(
+        &mut self,
+        payload: DeleteEntityRequest,
+        timeout_millis: Option<u32>,
+    ) -> RequestId<DeleteEntityRequest> {
+        let request_id = self.next_request_id();
+        self.delete_entity_requests.push(SentRequest {
+            request_id,
+            payload,
+            timeout_millis,
+        });
+        request_id
+    }
+
+    fn send_entity_query_request(
+        &mut self,
+        payload: EntityQueryRequest,
+        timeout_millis: Option<u32>,
+    ) -> RequestId<EntityQueryRequest> {
+        let request_id = self.next_request_id();
+        self.entity_query_requests.push(SentRequest {
+            request_id,
+            payload,
+            timeout_millis,
+        });
+        request_id
+    }
+
+    fn send_command_request<C: Component>(
+        &mut self,
+        entity_id: EntityId,
+        request: C::CommandRequest,
+        timeout_millis: Option<u32>,
+        params: CommandParameters,
+    ) -> RequestId<OutgoingCommandRequest> {
+        let request_id = self.next_request_id();
+        self.command_requests.push(SentCommandRequest {
+            request_id,
+            entity_id,
+            component_id: C::ID,
+            command_index: C::get_request_command_index(&request),
+            timeout_millis,
+            params,
+            request: UserHandle::command_request::<C>(request),
+        });
+        request_id
+    }
+
+    fn send_command_response<C: Component>(
+        &mut self,
+        request_id: RequestId<IncomingCommandRequest>,
+        response: C::CommandResponse,
+    ) {
+        self.command_responses.push(SentCommandResponse {
+            request_id,
+            component_id: C::ID,
+            command_index: C::get_response_command_index(&response),
+            response: UserHandle::command_response::<C>(response),
+        });
+    }
+
+    fn send_command_failure(
+        &mut self,
+        request_id: RequestId<IncomingCommandRequest>,
+        message: &str,
+    ) -> Result<(), NulError> {
+        // Match the validation performed by `WorkerConnection`.
+        CString::new(message)?;
+        self.command_failures.push(SentCommandFailure {
+            request_id,
+            message: message.to_owned(),
+        });
+
+        Ok(())
+    }
+
+    fn send_component_update<C: Component>(
+        &mut self,
+        entity_id: EntityId,
+        update: C::Update,
+        parameters: UpdateParameters,
+    ) {
+        self.component_updates.push(SentComponentUpdate {
+            entity_id,
+            component_id: C::ID,
+            parameters,
+            update: UserHandle::component_update::<C>(update),
+        });
+    }
+
+    fn send_component_interest(
+        &mut self,
+        entity_id: EntityId,
+        interest_overrides: &[InterestOverride],
+    ) {
+        self.component_interests.push(SentComponentInterest {
+            entity_id,
+            interest_overrides: interest_overrides.to_vec(),
+        });
+    }
+
+    fn send_authority_loss_imminent_acknowledgement(
+        &mut self,
+        entity_id: EntityId,
+        component_id: u32,
+    ) {
+        self.authority_loss_imminent_acknowledgements
+            .push((entity_id, component_id));
+    }
+
+    fn set_protocol_logging_enabled(&mut self, enabled: bool) {
+        self.protocol_logging_enabled = enabled;
+    }
+
+    fn get_connection_status(&mut self) -> ConnectionStatus {
+        self.status.clone()
+    }
+
+    fn get_worker_flag(&mut self, name: &str) -> Option<String> {
+        self.flags.get(name).cloned()
+    }
+
+    fn get_op_list(&mut self, _timeout_millis: u32) -> OpList {
+        self.op_lists.pop_front().unwrap_or_default().into_op_list()
+    }
+
+    fn get_worker_id(&self) -> &str {
+        &self.id
+    }
+
+    fn get_worker_attributes(&self) -> &[String] {
+        &self.attributes
+    }
+}
+
+/// A builder for synthetic op lists.
+///
+/// The ops are laid out in memory exactly as the C API would return them, so the resulting
+/// [`OpList`] behaves the same as one received from a real connection. Component data,
+/// updates and command payloads are stored directly as user handles, so no schema
+/// serialization takes place.
+///
+/// Any [`OwnedWorkerOp`] can be added with [`with_op`], and a whole [`OwnedOpList`] can be
+/// converted into a `MockOpList`, so ops recorded from a real connection can be replayed.
+///
+/// # Panics
+///
+/// The string arguments of the builder methods must not contain 0 bytes.
+///
+/// # Examples
+///
+/// ```
+/// use spatialos_sdk::worker::{mock::MockOpList, op::WorkerOp, Authority, EntityId};
+///
+/// let op_list = MockOpList::new()
+///     .with_add_entity(EntityId::new(1))
+///     .with_authority_change(EntityId::new(1), 54, Authority::Authoritative)
+///     .into_op_list();
+///
+/// assert_eq!(2, op_list.iter().count());
+/// ```
+///
+/// [`with_op`]: #method.with_op
+#[derive(Default)]
+pub struct MockOpList {
+    ops: Vec<Worker_Op>,
+    list: Worker_OpList,
+
+    // Storage for the data that `ops` points to.
+    strings: Vec<CString>,
+    attribute_sets: Vec<Vec<*const raw::c_char>>,
+    metrics: Vec<(Box<Metrics>, WrappedWorkerMetrics)>,
+    entities: Vec<(Vec<RawEntity>, Vec<Worker_Entity>)>,
+    handles: Vec<UserHandle>,
+}
+
+impl MockOpList {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_disconnect(self, reason: &str) -> Self {
+        self.with_op(OwnedWorkerOp::Disconnect(DisconnectOp {
+            reason: reason.to_owned(),
+        }))
+    }
+
+    pub fn with_flag_update(self, name: &str, value: &str) -> Self {
+        self.with_op(OwnedWorkerOp::FlagUpdate(FlagUpdateOp {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        }))
+    }
+
+    pub fn with_log_message(self, log_level: LogLevel, message: &str) -> Self {
+        self.with_op(OwnedWorkerOp::LogMessage(LogMessageOp {
+            message: message.to_owned(),
+            log_level,
+        }))
+    }
+
+    pub fn with_metrics(self, metrics: Metrics) -> Self {
+        self.with_op(OwnedWorkerOp::Metrics(MetricsOp { metrics }))
+    }
+
+    pub fn with_critical_section(self, in_critical_section: bool) -> Self {
+        self.with_op(OwnedWorkerOp::CriticalSection(CriticalSectionOp {
+            in_critical_section,
+        }))
+    }
+
+    pub fn with_add_entity(self, entity_id: EntityId) -> Self {
+        self.with_op(OwnedWorkerOp::AddEntity(AddEntityOp { entity_id }))
+    }
+
+    pub fn with_remove_entity(self, entity_id: EntityId) -> Self {
+        self.with_op(OwnedWorkerOp::RemoveEntity(RemoveEntityOp { entity_id }))
+    }
+
+    pub fn with_add_component<C: Component>(self, entity_id: EntityId, data: C) -> Self {
+        self.with_op(OwnedWorkerOp::AddComponent(OwnedAddComponentOp::new(
+            entity_id, data,
+        )))
+    }
+
+    pub fn with_remove_component(self, entity_id: EntityId, component_id: ComponentId) -> Self {
+        self.with_op(OwnedWorkerOp::RemoveComponent(RemoveComponentOp {
+            entity_id,
+            component_id,
+        }))
+    }
+
+    pub fn with_authority_change(
+        self,
+        entity_id: EntityId,
+        component_id: ComponentId,
+        authority: Authority,
+    ) -> Self {
+        self.with_op(OwnedWorkerOp::AuthorityChange(AuthorityChangeOp {
+            entity_id,
+            component_id,
+            authority,
+        }))
+    }
+
+    pub fn with_component_update<C: Component>(
+        self,
+        entity_id: EntityId,
+        update: C::Update,
+    ) -> Self {
+        self.with_op(OwnedWorkerOp::ComponentUpdate(
+            OwnedComponentUpdateOp::new::<C>(entity_id, update),
+        ))
+    }
+
+    pub fn with_command_request<C: Component>(
+        self,
+        request_id: RequestId<IncomingCommandRequest>,
+        entity_id: EntityId,
+        caller_worker_id: &str,
+        caller_attribute_set: &[String],
+        request: C::CommandRequest,
+    ) -> Self {
+        let mut op = OwnedCommandRequestOp::new::<C>(request_id, entity_id, request);
+        op.caller_worker_id = caller_worker_id.to_owned();
+        op.caller_attribute_set = caller_attribute_set.to_vec();
+        self.with_op(OwnedWorkerOp::CommandRequest(op))
+    }
+
+    pub fn with_command_response<C: Component>(
+        self,
+        request_id: RequestId<OutgoingCommandRequest>,
+        entity_id: EntityId,
+        response: StatusCode<C::CommandResponse>,
+    ) -> Self {
+        let response = match response {
+            StatusCode::Success(response) => {
+                StatusCode::Success(OwnedCommandResponse::new::<C>(response))
+            }
+            StatusCode::Timeout(message) => StatusCode::Timeout(message),
+            StatusCode::NotFound(message) => StatusCode::NotFound(message),
+            StatusCode::AuthorityLost(message) => StatusCode::AuthorityLost(message),
+            StatusCode::PermissionDenied(message) => StatusCode::PermissionDenied(message),
+            StatusCode::ApplicationError(message) => StatusCode::ApplicationError(message),
+            StatusCode::InternalError(message) => StatusCode::InternalError(message),
+        };
+
+        self.with_op(OwnedWorkerOp::CommandResponse(OwnedCommandResponseOp {
+            request_id,
+            entity_id,
+            component_id: C::ID,
+            response,
+        }))
+    }
+
+    /// Adds a reserve entity IDs response. On success, the status holds the first reserved
+    /// entity ID and the number of reserved IDs.
+    pub fn with_reserve_entity_ids_response(
+        self,
+        request_id: RequestId<ReserveEntityIdsRequest>,
+        status: StatusCode<(EntityId, u32)>,
+    ) -> Self {
+        let status_code = match status {
+            StatusCode::Success((first, number)) => {
+                StatusCode::Success(ReservedEntityIdRange::new(first.id, number))
+            }
+            StatusCode::Timeout(message) => StatusCode::Timeout(message),
+            StatusCode::NotFound(message) => StatusCode::NotFound(message),
+            StatusCode::AuthorityLost(message) => StatusCode::AuthorityLost(message),
+            StatusCode::PermissionDenied(message) => StatusCode::PermissionDenied(message),
+            StatusCode::ApplicationError(message) => StatusCode::ApplicationError(message),
+            StatusCode::InternalError(message) => StatusCode::InternalError(message),
+        };
+
+        self.with_op(OwnedWorkerOp::ReserveEntityIdsResponse(
+            ReserveEntityIdsResponseOp {
+                request_id,
+                status_code,
+            },
+        ))
+    }
+
+    pub fn with_create_entity_response(
+        self,
+        request_id: RequestId<CreateEntityRequest>,
+        status_code: StatusCode<EntityId>,
+    ) -> Self {
+        self.with_op(OwnedWorkerOp::CreateEntityResponse(
+            CreateEntityResponseOp {
+                request_id,
+                status_code,
+            },
+        ))
+    }
+
+    pub fn with_delete_entity_response(
+        self,
+        request_id: RequestId<DeleteEntityRequest>,
+        entity_id: EntityId,
+        status_code: StatusCode<()>,
+    ) -> Self {
+        self.with_op(OwnedWorkerOp::DeleteEntityResponse(
+            DeleteEntityResponseOp {
+                request_id,
+                entity_id,
+                status_code,
+            },
+        ))
+    }
+
+    pub fn with_entity_query_response(
+        self,
+        request_id: RequestId<EntityQueryRequest>,
+        status_code: StatusCode<QueryResponse>,
+    ) -> Self {
+        self.with_op(OwnedWorkerOp::EntityQueryResponse(EntityQueryResponseOp {
+            request_id,
+            status_code,
+        }))
+    }
+
+    /// Adds an arbitrary owned op to the list.
+    pub fn with_op(mut self, op: OwnedWorkerOp) -> Self {
+        self.push_op(op);
+        self
+    }
+
+    /// Returns the number of ops in the list.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn into_op_list(self) -> OpList {
+        let mut storage = Box::new(self);
+        storage.list = Worker_OpList {
+            ops: storage.ops.as_mut_ptr(),
+            op_count: storage.ops.len() as u32,
+        };
+        OpList::from_mock(storage)
+    }
+
+    pub(crate) fn raw(&mut self) -> *mut Worker_OpList {
+        &mut self.list
+    }
+
+    fn push_op(&mut self, op: OwnedWorkerOp) {
+        let mut raw = Worker_Op::default();
+        let erased_op = &mut raw.op;
+        let op_type = match op {
+            OwnedWorkerOp::Disconnect(op) => {
+                erased_op.disconnect = Worker_DisconnectOp {
+                    connection_status_code: 0,
+                    reason: self.c_string(&op.reason),
+                };
+                Worker_OpType_WORKER_OP_TYPE_DISCONNECT
+            }
+            OwnedWorkerOp::FlagUpdate(op) => {
+                erased_op.flag_update = Worker_FlagUpdateOp {
+                    name: self.c_string(&op.name),
+                    value: self.c_string(&op.value),
+                };
+                Worker_OpType_WORKER_OP_TYPE_FLAG_UPDATE
+            }
+            OwnedWorkerOp::LogMessage(op) => {
+                erased_op.log_message = Worker_LogMessageOp {
+                    level: op.log_level.to_worker_sdk(),
+                    message: self.c_string(&op.message),
+                };
+                Worker_OpType_WORKER_OP_TYPE_LOG_MESSAGE
+            }
+            OwnedWorkerOp::Metrics(op) => {
+                // `to_worker_sdk` borrows from the `Metrics` object, so it's boxed to keep it
+                // at a stable address.
+                let metrics = Box::new(op.metrics);
+                let worker_metrics = metrics.to_worker_sdk();
+                erased_op.metrics = Worker_MetricsOp {
+                    metrics: worker_metrics.metrics,
+                };
+                self.metrics.push((metrics, worker_metrics));
+                Worker_OpType_WORKER_OP_TYPE_METRICS
+            }
+            OwnedWorkerOp::CriticalSection(op) => {
+                erased_op.critical_section = Worker_CriticalSectionOp {
+                    in_critical_section: op.in_critical_section as u8,
+                };
+                Worker_OpType_WORKER_OP_TYPE_CRITICAL_SECTION
+            }
+            OwnedWorkerOp::AddEntity(op) => {
+                erased_op.add_entity = Worker_AddEntityOp {
+                    entity_id: op.entity_id.id,
+                };
+                Worker_OpType_WORKER_OP_TYPE_ADD_ENTITY
+            }
+            OwnedWorkerOp::RemoveEntity(op) => {
+                erased_op.remove_entity = Worker_RemoveEntityOp {
+                    entity_id: op.entity_id.id,
+                };
+                Worker_OpType_WORKER_OP_TYPE_REMOVE_ENTITY
+            }
+            OwnedWorkerOp::AddComponent(op) => {
+                erased_op.add_component = Worker_AddComponentOp {
+                    entity_id: op.entity_id.id,
+                    data: Worker_ComponentData {
+                        reserved: ptr::null_mut(),
+                        component_id: op.component_id,
+                        schema_type: ptr::null_mut(),
+                        user_handle: self.handle(op.handle()),
+                    },
+                };
+                Worker_OpType_WORKER_OP_TYPE_ADD_COMPONENT
+            }
+            OwnedWorkerOp::RemoveComponent(op) => {
+                erased_op.remove_component = Worker_RemoveComponentOp {
+                    entity_id: op.entity_id.id,
+                    component_id: op.component_id,
+                };
+                Worker_OpType_WORKER_OP_TYPE_REMOVE_COMPONENT
+            }
+            OwnedWorkerOp::ComponentUpdate(op) => {
+                erased_op.component_update = Worker_ComponentUpdateOp {
+                    entity_id: op.entity_id.id,
+                    update: Worker_ComponentUpdate {
+                        reserved: ptr::null_mut(),
+                        component_id: op.component_id,
+                        schema_type: ptr::null_mut(),
+                        user_handle: self.handle(op.handle()),
+                    },
+                };
+                Worker_OpType_WORKER_OP_TYPE_COMPONENT_UPDATE
+            }
+            OwnedWorkerOp::AuthorityChange(op) => {
+                erased_op.authority_change = Worker_AuthorityChangeOp {
+                    entity_id: op.entity_id.id,
+                    component_id: op.component_id,
+                    authority: op.authority.to_worker_sdk(),
+                };
+                Worker_OpType_WORKER_OP_TYPE_AUTHORITY_CHANGE
+            }
+            OwnedWorkerOp::CommandRequest(op) => {
+                let mut attributes = op
+                    .caller_attribute_set
+                    .iter()
+                    .map(|attribute| self.c_string(attribute))
+                    .collect::<Vec<_>>();
+                let caller_attribute_set = Worker_WorkerAttributes {
+                    attribute_count: attributes.len() as u32,
+                    attributes: attributes.as_mut_ptr(),
+                };
+                self.attribute_sets.push(attributes);
+
+                erased_op.command_request = Worker_CommandRequestOp {
+                    request_id: op.request_id.id,
+                    entity_id: op.entity_id.id,
+                    timeout_millis: op.timeout_millis,
+                    caller_worker_id: self.c_string(&op.caller_worker_id),
+                    caller_attribute_set,
+                    request: Worker_CommandRequest {
+                        reserved: ptr::null_mut(),
+                        component_id: op.component_id,
+                        command_index: op.command_index,
+                        schema_type: ptr::null_mut(),
+                        user_handle: self.handle(op.handle()),
+                    },
+                };
+                Worker_OpType_WORKER_OP_TYPE_COMMAND_REQUEST
+            }
+            OwnedWorkerOp::CommandResponse(op) => {
+                let (status_code, message, response) = self.split_status(op.response);
+                let (command_index, user_handle) = match response {
+                    Some(response) => (response.command_index, self.handle(response.handle())),
+                    None => (0, ptr::null_mut()),
+                };
+
+                erased_op.command_response = Worker_CommandResponseOp {
+                    request_id: op.request_id.id,
+                    entity_id: op.entity_id.id,
+                    status_code,
+                    message,
+                    response: Worker_CommandResponse {
+                        reserved: ptr::null_mut(),
+                        component_id: op.component_id,
+                        command_index,
+                        schema_type: ptr::null_mut(),
+                        user_handle,
+                    },
+                };
+                Worker_OpType_WORKER_OP_TYPE_COMMAND_RESPONSE
+            }
+            OwnedWorkerOp::ReserveEntityIdsResponse(op) => {
+                let (status_code, message, range) = self.split_status(op.status_code);
+                let (first_entity_id, number_of_entity_ids) = range
+                    .map(|range| (range.current, range.reserved - range.consumed))
+                    .unwrap_or_default();
+
+                erased_op.reserve_entity_ids_response = Worker_ReserveEntityIdsResponseOp {
+                    request_id: op.request_id.id,
+                    status_code,
+                    message,
+                    first_entity_id,
+                    number_of_entity_ids,
+                };
+                Worker_OpType_WORKER_OP_TYPE_RESERVE_ENTITY_IDS_RESPONSE
+            }
+            OwnedWorkerOp::CreateEntityResponse(op) => {
+                let (status_code, message, entity_id) = self.split_status(op.status_code);
+                erased_op.create_entity_response = Worker_CreateEntityResponseOp {
+                    request_id: op.request_id.id,
+                    status_code,
+                    message,
+                    entity_id: entity_id.unwrap_or_default().id,
+                };
+                Worker_OpType_WORKER_OP_TYPE_CREATE_ENTITY_RESPONSE
+            }
+            OwnedWorkerOp::DeleteEntityResponse(op) => {
+                let (status_code, message, _) = self.split_status(op.status_code);
+                erased_op.delete_entity_response = Worker_DeleteEntityResponseOp {
+                    request_id: op.request_id.id,
+                    entity_id: op.entity_id.id,
+                    status_code,
+                    message,
+                };
+                Worker_OpType_WORKER_OP_TYPE_DELETE_ENTITY_RESPONSE
+            }
+            OwnedWorkerOp::EntityQueryResponse(op) => {
+                let (status_code, message, response) = self.split_status(op.status_code);
+                let (result_count, results) = match response {
+                    None => (0, ptr::null()),
+                    Some(QueryResponse::Result(count)) => (count, ptr::null()),
+                    Some(QueryResponse::Snapshot(entities)) => {
+                        let raw_entities = entities
+                            .values()
+                            .map(Entity::raw_component_data)
+                            .collect::<Vec<_>>();
+                        let worker_entities = entities
+                            .keys()
+                            .zip(&raw_entities)
+                            .map(|(entity_id, raw_entity)| Worker_Entity {
+                                entity_id: entity_id.id,
+                                component_count: raw_entity.components.len() as u32,
+                                components: raw_entity.components.as_ptr(),
+                            })
+                            .collect::<Vec<_>>();
+
+                        let result = (worker_entities.len() as u32, worker_entities.as_ptr());
+                        self.entities.push((raw_entities, worker_entities));
+                        result
+                    }
+                };
+
+                erased_op.entity_query_response = Worker_EntityQueryResponseOp {
+                    request_id: op.request_id.id,
+                    status_code,
+                    message,
+                    result_count,
+                    results,
+                };
+                Worker_OpType_WORKER_OP_TYPE_ENTITY_QUERY_RESPONSE
+            }
+        };
+
+        raw.op_type = op_type as u8;
+        self.ops.push(raw);
+    }
+
+    fn c_string(&mut self, value: &str) -> *const raw::c_char {
+        let value = CString::new(value).expect("Received 0 byte in mock op string.");
+        let ptr = value.as_ptr();
+        self.strings.push(value);
+        ptr
+    }
+
+    // Keeps a reference to the user data alive for as long as the op list, and returns
+    // the raw handle (or null if there is no user data).
+    fn handle(&mut self, handle: Option<&UserHandle>) -> *mut raw::c_void {
+        match handle {
+            Some(handle) => {
+                let ptr = handle.as_ptr();
+                self.handles.push(handle.clone());
+                ptr
+            }
+            None => ptr::null_mut(),
+        }
+    }
+
+    fn split_status<T>(&mut self, status: StatusCode<T>) -> (u8, *const raw::c_char, Option<T>) {
+        let (code, message, value) = match status {
+            StatusCode::Success(value) => (
+                Worker_StatusCode_WORKER_STATUS_CODE_SUCCESS,
+                String::new(),
+                Some(value),
+            ),
+            StatusCode::Timeout(message) => {
+                (Worker_StatusCode_WORKER_STATUS_CODE_TIMEOUT, message, None)
+            }
+            StatusCode::NotFound(message) => (
+                Worker_StatusCode_WORKER_STATUS_CODE_NOT_FOUND,
+                message,
+                None,
+            ),
+            StatusCode::AuthorityLost(message) => (
+                Worker_StatusCode_WORKER_STATUS_CODE_AUTHORITY_LOST,
+                message,
+                None,
+            ),
+            StatusCode::PermissionDenied(message) => (
+                Worker_StatusCode_WORKER_STATUS_CODE_PERMISSION_DENIED,
+                message,
+                None,
+            ),
+            StatusCode::ApplicationError(message) => (
+                Worker_StatusCode_WORKER_STATUS_CODE_APPLICATION_ERROR,
+                message,
+                None,
+            ),
+            StatusCode::InternalError(message) => (
+                Worker_StatusCode_WORKER_STATUS_CODE_INTERNAL_ERROR,
+                message,
+                None,
+            ),
+        };
+
+        let message = self.c_string(&message);
+        (code as u8, message, value)
+    }
+}
+
+impl From<OwnedOpList> for MockOpList {
+    fn from(ops: OwnedOpList) -> Self {
+        ops.into_iter()
+            .fold(MockOpList::new(), |list, op| list.with_op(op))
+    }
+}
+
+impl fmt::Debug for MockOpList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockOpList")
+            .field("op_count", &self.ops.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::worker::{
+        component::{ComponentData, VTable},
+        internal::schema::*,
+        query::{EntityQuery, QueryConstraint, ResultType},
+    };
+    use std::thread;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub(crate) struct Counter {
+        pub value: u32,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub(crate) struct CounterUpdate {
+        pub value: Option<u32>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub(crate) struct Increment(pub u32);
+
+    impl ComponentData<Counter> for Counter {
+        fn merge(&mut self, update: CounterUpdate) {
+            if let Some(value) = update.value {
+                self.value = value;
+            }
+        }
+    }
+
+    impl Component for Counter {
+        type Update = CounterUpdate;
+        type CommandRequest = Increment;
+        type CommandResponse = Counter;
+
+        const ID: ComponentId = 1234;
+
+        fn from_data(_: &SchemaComponentData) -> Result<Self, String> {
+            Err("Counter has no schema data".to_owned())
+        }
+        fn from_update(_: &SchemaComponentUpdate) -> Result<Self::Update, String> {
+            Err("Counter has no schema updates".to_owned())
+        }
+        fn from_request(_: u32, _: &SchemaCommandRequest) -> Result<Self::CommandRequest, String> {
+            Err("Counter has no schema commands".to_owned())
+        }
+        fn from_response(
+            _: u32,
+            _: &SchemaCommandResponse,
+        ) -> Result<Self::CommandResponse, String> {
+            Err("Counter has no schema commands".to_owned())
+        }
+
+        fn to_data(_: &Self) -> Result<SchemaComponentData, String> {
+            Err("Counter has no schema data".to_owned())
+        }
+        fn to_update(_: &Self::Update) -> Result<SchemaComponentUpdate, String> {
+            Err("Counter has no schema updates".to_owned())
+        }
+        fn to_request(_: &Self::CommandRequest) -> Result<SchemaCommandRequest, String> {
+            Err("Counter has no schema commands".to_owned())
+        }
+        fn to_response(_: &Self::CommandResponse) -> Result<SchemaCommandResponse, String> {
+            Err("Counter has no schema commands".to_owned())
+        }
+
+        fn get_request_command_index(_: &Self::CommandRequest) -> u32 {
+            1
+        }
+        fn get_response_command_index(_: &Self::CommandResponse) -> u32 {
+            1
+        }
+    }
+
+    crate::worker::component::inventory::submit!(VTable::new::<Counter>());
+
+    #[test]
+    fn outgoing_messages_are_recorded_in_order() {
+        let mut connection = MockConnection::new("TestWorker");
+        let entity_id = EntityId::new(5);
+
+        connection.send_log_message(LogLevel::Warn, "logger", "first", None);
+        connection.send_log_message(LogLevel::Info, "logger", "second", Some(entity_id));
+        connection.send_component_update::<Counter>(
+            entity_id,
+            CounterUpdate { value: Some(3) },
+            UpdateParameters::new(),
+        );
+        connection.send_component_interest(entity_id, &[InterestOverride::new(Counter::ID, true)]);
+        connection.send_authority_loss_imminent_acknowledgement(entity_id, Counter::ID);
+        connection.set_protocol_logging_enabled(true);
+
+        assert_eq!(
+            vec!["first", "second"],
+            connection
+                .log_messages
+                .iter()
+                .map(|log| log.message.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(entity_id), connection.log_messages[1].entity_id);
+
+        let update = &connection.component_updates[0];
+        assert_eq!(entity_id, update.entity_id);
+        assert_eq!(
+            Some(&CounterUpdate { value: Some(3) }),
+            update.get::<Counter>()
+        );
+
+        assert!(connection.component_interests[0].interest_overrides[0].is_interested);
+        assert_eq!(
+            vec![(entity_id, Counter::ID)],
+            connection.authority_loss_imminent_acknowledgements
+        );
+        assert!(connection.protocol_logging_enabled);
+    }
+
+    #[test]
+    fn requests_are_assigned_sequential_ids() {
+        let mut connection = MockConnection::new("TestWorker");
+
+        let reserve = connection.send_reserve_entity_ids_request(ReserveEntityIdsRequest(10), None);
+        let command = connection.send_command_request::<Counter>(
+            EntityId::new(1),
+            Increment(2),
+            Some(100),
+            CommandParameters::new(),
+        );
+        let query = connection.send_entity_query_request(
+            EntityQueryRequest(EntityQuery::new(
+                QueryConstraint::Component(Counter::ID),
+                ResultType::Count,
+            )),
+            None,
+        );
+
+        assert_eq!(RequestId::new(1), reserve);
+        assert_eq!(RequestId::new(2), command);
+        assert_eq!(RequestId::new(3), query);
+
+        assert_eq!(10, connection.reserve_entity_ids_requests[0].payload.0);
+        let request = &connection.command_requests[0];
+        assert_eq!(Some(100), request.timeout_millis);
+        assert_eq!(1, request.command_index);
+        assert_eq!(Some(&Increment(2)), request.get::<Counter>());
+    }
+
+    #[test]
+    fn command_failure_rejects_nul_bytes() {
+        let mut connection = MockConnection::new("TestWorker");
+
+        assert!(connection
+            .send_command_failure(RequestId::new(1), "bad\0message")
+            .is_err());
+        assert!(connection.command_failures.is_empty());
+    }
+
+    #[test]
+    fn worker_flags_and_status_are_configurable() {
+        let mut connection = MockConnection::new("TestWorker")
+            .with_attribute("physics")
+            .with_worker_flag("speed", "10");
+
+        assert_eq!("TestWorker", connection.get_worker_id());
+        assert_eq!(&["physics".to_owned()], connection.get_worker_attributes());
+        assert_eq!(Some("10".to_owned()), connection.get_worker_flag("speed"));
+        assert_eq!(None, connection.get_worker_flag("missing"));
+
+        connection.set_connection_status(ConnectionStatus {
+            code: ConnectionStatusCode::NetworkError,
+            detail: "Lost connection".to_owned(),
+        });
+        assert_eq!(
+            ConnectionStatusCode::NetworkError,
+            connection.get_connection_status().code
+        );
+    }
+
+    #[test]
+    fn get_op_list_returns_injected_ops_in_order() {
+        let mut connection = MockConnection::new("TestWorker");
+        let entity_id = EntityId::new(7);
+
+        connection.push_op_list(
+            MockOpList::new()
+                .with_add_entity(entity_id)
+                .with_add_component(entity_id, Counter { value: 1 })
+                .with_authority_change(entity_id, Counter::ID, Authority::Authoritative),
+        );
+        connection.push_op_list(
+            MockOpList::new()
+                .with_component_update::<Counter>(entity_id, CounterUpdate { value: Some(2) })
+                .with_command_request::<Counter>(
+                    RequestId::new(3),
+                    entity_id,
+                    "OtherWorker",
+                    &["client".to_owned()],
+                    Increment(4),
+                ),
+        );
+        assert_eq!(2, connection.pending_op_lists());
+
+        let first = connection.get_op_list(0);
+        let ops = first.iter().collect::<Vec<_>>();
+        assert_eq!(3, ops.len());
+        match &ops[0] {
+            WorkerOp::AddEntity(op) => assert_eq!(entity_id, op.entity_id),
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match &ops[1] {
+            WorkerOp::AddComponent(op) => {
+                assert_eq!(Some(&Counter { value: 1 }), op.get::<Counter>())
+            }
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match &ops[2] {
+            WorkerOp::AuthorityChange(op) => assert_eq!(Authority::Authoritative, op.authority),
+            op => panic!("Unexpected op {:?}", op),
+        }
+
+        let second = connection.get_op_list(0);
+        let ops = second.iter().collect::<Vec<_>>();
+        match &ops[0] {
+            WorkerOp::ComponentUpdate(op) => {
+                assert_eq!(Some(&CounterUpdate { value: Some(2) }), op.get::<Counter>())
+            }
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match &ops[1] {
+            WorkerOp::CommandRequest(op) => {
+                assert_eq!("OtherWorker", op.caller_worker_id);
+                assert_eq!(vec!["client".to_owned()], op.caller_attribute_set);
+                assert_eq!(Some(&Increment(4)), op.get::<Counter>());
+            }
+            op => panic!("Unexpected op {:?}", op),
+        }
+
+        assert_eq!(0, connection.get_op_list(0).iter().count());
+    }
+
+    #[test]
+    fn mock_op_list_encodes_responses_and_status_codes() {
+        let op_list = MockOpList::new()
+            .with_disconnect("Shutting down")
+            .with_metrics(Metrics::new().with_load(0.5))
+            .with_reserve_entity_ids_response(
+                RequestId::new(1),
+                StatusCode::Success((EntityId::new(100), 3)),
+            )
+            .with_create_entity_response(RequestId::new(2), StatusCode::Timeout("slow".to_owned()))
+            .with_command_response::<Counter>(
+                RequestId::new(3),
+                EntityId::new(1),
+                StatusCode::Success(Counter { value: 9 }),
+            )
+            .with_entity_query_response(
+                RequestId::new(4),
+                StatusCode::Success(QueryResponse::Result(12)),
+            )
+            .into_op_list();
+
+        let mut ops = op_list.iter();
+        match ops.next().unwrap() {
+            WorkerOp::Disconnect(op) => assert_eq!("Shutting down", op.reason),
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match ops.next().unwrap() {
+            WorkerOp::Metrics(op) => assert_eq!(Some(0.5), op.metrics.load),
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match ops.next().unwrap() {
+            WorkerOp::ReserveEntityIdsResponse(op) => match op.status_code {
+                StatusCode::Success(range) => assert_eq!(
+                    vec![100, 101, 102],
+                    range.map(|entity_id| entity_id.id).collect::<Vec<_>>()
+                ),
+                status => panic!("Unexpected status {:?}", status),
+            },
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match ops.next().unwrap() {
+            WorkerOp::CreateEntityResponse(op) => match op.status_code {
+                StatusCode::Timeout(message) => assert_eq!("slow", message),
+                status => panic!("Unexpected status {:?}", status),
+            },
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match ops.next().unwrap() {
+            WorkerOp::CommandResponse(op) => match op.response {
+                StatusCode::Success(response) => {
+                    assert_eq!(Some(&Counter { value: 9 }), response.get::<Counter>())
+                }
+                status => panic!("Unexpected status {:?}", status),
+            },
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match ops.next().unwrap() {
+            WorkerOp::EntityQueryResponse(op) => match op.status_code {
+                StatusCode::Success(QueryResponse::Result(count)) => assert_eq!(12, count),
+                status => panic!("Unexpected status {:?}", status),
+            },
+            op => panic!("Unexpected op {:?}", op),
+        }
+        assert!(ops.next().is_none());
+    }
+
+    #[test]
+    fn owned_ops_outlive_their_op_list_and_share_data() {
+        let entity_id = EntityId::new(3);
+        let op_list = MockOpList::new()
+            .with_add_component(entity_id, Counter { value: 5 })
+            .with_component_update::<Counter>(entity_id, CounterUpdate { value: None })
+            .with_flag_update("name", "value")
+            .into_op_list();
+
+        let owned = op_list.to_owned();
+        drop(op_list);
+
+        // Owned ops can be moved to another thread.
+        let owned = thread::spawn(move || owned).join().unwrap();
+        assert_eq!(3, owned.len());
+
+        let ops = owned.iter().collect::<Vec<_>>();
+        match ops[0] {
+            OwnedWorkerOp::AddComponent(op) => {
+                assert_eq!(entity_id, op.entity_id);
+                assert_eq!(Some(&Counter { value: 5 }), op.get::<Counter>());
+
+                // Cloning the op shares the component data rather than copying it.
+                let copy = op.clone();
+                assert!(std::ptr::eq(
+                    op.get::<Counter>().unwrap(),
+                    copy.get::<Counter>().unwrap()
+                ));
+            }
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match ops[1] {
+            OwnedWorkerOp::ComponentUpdate(op) => {
+                assert_eq!(Some(&CounterUpdate { value: None }), op.get::<Counter>())
+            }
+            op => panic!("Unexpected op {:?}", op),
+        }
+        match ops[2] {
+            OwnedWorkerOp::FlagUpdate(op) => assert_eq!("value", op.value),
+            op => panic!("Unexpected op {:?}", op),
+        }
+    }
+
+    #[test]
+    fn owned_op_list_can_be_built_from_a_vec_and_replayed() {
+        let entity_id = EntityId::new(9);
+        let ops: OwnedOpList = vec![
+            OwnedWorkerOp::AddEntity(AddEntityOp { entity_id }),
+            OwnedWorkerOp::CommandRequest(OwnedCommandRequestOp::new::<Counter>(
+                RequestId::new(1),
+                entity_id,
+                Increment(2),
+            )),
+            OwnedWorkerOp::CommandResponse(OwnedCommandResponseOp {
+                request_id: RequestId::new(2),
+                entity_id,
+                component_id: Counter::ID,
+                response: StatusCode::Success(OwnedCommandResponse::new::<Counter>(Counter {
+                    value: 7,
+                })),
+            }),
+        ]
+        .into();
+
+        let mut connection = MockConnection::new("TestWorker");
+        connection.push_op_list(ops.clone().into());
+        let op_list = connection.get_op_list(0);
+
+        let replayed = op_list.to_owned();
+        assert_eq!(ops.len(), replayed.len());
+        match &replayed.into_vec()[1] {
+            OwnedWorkerOp::CommandRequest(op) => {
+                assert_eq!(1, op.command_index);
+                assert_eq!(Some(&Increment(2)), op.get::<Counter>());
+            }
+            op => panic!("Unexpected op {:?}", op),
+        }
+
+        match op_list.iter().nth(2).unwrap() {
+            WorkerOp::CommandResponse(op) => match op.response {
+                StatusCode::Success(response) => {
+                    assert_eq!(Some(&Counter { value: 7 }), response.get::<Counter>())
+                }
+                status => panic!("Unexpected status {:?}", status),
+            },
+            op => panic!("Unexpected op {:?}", op),
+        }
+    }
+}
@@ -3,14 +3,21 @@ pub mod internal;
 pub mod commands;
 pub mod component;
 pub mod connection;
+pub mod dispatcher;
 pub mod entity;
 pub mod entity_builder;
 pub mod locator;
 pub mod metrics;
+pub mod mock;
 pub mod op;
 pub mod parameters;
 pub mod query;
+pub mod requests;
+pub mod schema_value;
+#[cfg(feature = "serde")]
+pub mod serde_support;
 pub mod snapshot;
+pub mod view;
 pub mod vtable;
 
 use component::ComponentId;
@@ -21,6 +28,11 @@ use std::fmt::{Display, Error, Formatter};
 use std::marker::PhantomData;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct EntityId {
     pub id: i64,
 }
@@ -92,6 +104,14 @@ impl Authority {
     pub fn has_authority(self) -> bool {
         self != Authority::NotAuthoritative
     }
+
+    pub(crate) fn to_worker_sdk(self) -> u8 {
+        match self {
+            Authority::NotAuthoritative => 0,
+            Authority::Authoritative => 1,
+            Authority::AuthorityLossImminent => 2,
+        }
+    }
 }
 
 impl From<u8> for Authority {
@@ -142,6 +162,7 @@ impl From<u8> for LogLevel {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InterestOverride {
     pub component_id: ComponentId,
     pub is_interested: bool,
@@ -11,19 +11,40 @@ use crate::worker::{
         utils::*,
     },
     metrics::Metrics,
+    mock::MockOpList,
     {Authority, EntityId, LogLevel, RequestId},
 };
 use spatialos_sdk_sys::worker::*;
-use std::{collections::HashMap, slice};
+use std::{collections::HashMap, error::Error, fmt, iter::FromIterator, slice, sync::Arc, vec};
 
 pub struct OpList {
     raw: *mut Worker_OpList,
+
+    // Set if the op list was built in Rust rather than allocated by the C API, in which
+    // case `raw` points into this storage.
+    mock: Option<Box<MockOpList>>,
+
+    // The vtables that the C API was given for the connection, which are needed to take
+    // owned copies of the data it deserialized.
+    registry: Arc<ComponentRegistry>,
 }
 
 impl OpList {
-    pub(crate) fn new(raw: *mut Worker_OpList) -> Self {
+    pub(crate) fn new(raw: *mut Worker_OpList, registry: Arc<ComponentRegistry>) -> Self {
         assert!(!raw.is_null());
-        OpList { raw }
+        OpList {
+            raw,
+            mock: None,
+            registry,
+        }
+    }
+
+    pub(crate) fn from_mock(mut mock: Box<MockOpList>) -> Self {
+        OpList {
+            raw: mock.raw(),
+            mock: Some(mock),
+            registry: Arc::new(ComponentRegistry::global()),
+        }
     }
 
     /// Returns an iterator over the list.
@@ -41,6 +62,13 @@ impl OpList {
         self.into_iter()
     }
 
+    /// Creates an owned copy of every op in the list.
+    ///
+    /// See [`WorkerOp::to_owned`] for details.
+    pub fn to_owned(&self) -> OwnedOpList {
+        self.iter().map(|op| op.to_owned()).collect()
+    }
+
     /// Returns the number of ops in the list.
     fn len(&self) -> usize {
         self.raw().op_count as usize
@@ -61,32 +89,41 @@ impl<'a> IntoIterator for &'a OpList {
 
     fn into_iter(self) -> Self::IntoIter {
         let slice = unsafe { slice::from_raw_parts(self.raw().ops, self.len()) };
-        Iter { iter: slice.iter() }
+        Iter {
+            iter: slice.iter(),
+            registry: &self.registry,
+        }
     }
 }
 
 impl Drop for OpList {
     fn drop(&mut self) {
         assert!(!self.raw.is_null());
-        unsafe {
-            Worker_OpList_Destroy(self.raw);
+        if self.mock.is_none() {
+            unsafe {
+                Worker_OpList_Destroy(self.raw);
+            }
         }
     }
 }
 
 pub struct Iter<'a> {
     iter: slice::Iter<'a, Worker_Op>,
+    registry: &'a ComponentRegistry,
 }
 
 impl<'a> Iterator for Iter<'a> {
     type Item = WorkerOp<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(WorkerOp::from)
+        let registry = self.registry;
+        self.iter
+            .next()
+            .map(|op| WorkerOp::from_worker_sdk(op, registry))
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StatusCode<T> {
     Success(T),
     Timeout(String),
@@ -97,6 +134,119 @@ pub enum StatusCode<T> {
     InternalError(String),
 }
 
+impl<T> StatusCode<T> {
+    /// Like [`into_result`](#method.into_result), but borrows the value of a successful status.
+    pub fn as_result(&self) -> Result<&T, RequestError> {
+        match self {
+            StatusCode::Success(value) => Ok(value),
+            StatusCode::Timeout(message) => Err(RequestError::Timeout(message.clone())),
+            StatusCode::NotFound(message) => Err(RequestError::NotFound(message.clone())),
+            StatusCode::AuthorityLost(message) => Err(RequestError::AuthorityLost(message.clone())),
+            StatusCode::PermissionDenied(message) => {
+                Err(RequestError::PermissionDenied(message.clone()))
+            }
+            StatusCode::ApplicationError(message) => {
+                Err(RequestError::ApplicationError(message.clone()))
+            }
+            StatusCode::InternalError(message) => Err(RequestError::InternalError(message.clone())),
+        }
+    }
+
+    /// Converts the status code into a `Result`, with every status other than
+    /// `Success` becoming a [`RequestError`].
+    pub fn into_result(self) -> Result<T, RequestError> {
+        match self {
+            StatusCode::Success(value) => Ok(value),
+            StatusCode::Timeout(message) => Err(RequestError::Timeout(message)),
+            StatusCode::NotFound(message) => Err(RequestError::NotFound(message)),
+            StatusCode::AuthorityLost(message) => Err(RequestError::AuthorityLost(message)),
+            StatusCode::PermissionDenied(message) => Err(RequestError::PermissionDenied(message)),
+            StatusCode::ApplicationError(message) => Err(RequestError::ApplicationError(message)),
+            StatusCode::InternalError(message) => Err(RequestError::InternalError(message)),
+        }
+    }
+}
+
+/// The failure cases of a [`StatusCode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestError {
+    Timeout(String),
+    NotFound(String),
+    AuthorityLost(String),
+    PermissionDenied(String),
+    ApplicationError(String),
+    InternalError(String),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Timeout(message) => write!(f, "Request timed out: {}", message),
+            RequestError::NotFound(message) => write!(f, "Request target not found: {}", message),
+            RequestError::AuthorityLost(message) => {
+                write!(f, "Request target lost authority: {}", message)
+            }
+            RequestError::PermissionDenied(message) => {
+                write!(f, "Request permission denied: {}", message)
+            }
+            RequestError::ApplicationError(message) => {
+                write!(f, "Request failed with application error: {}", message)
+            }
+            RequestError::InternalError(message) => {
+                write!(f, "Request failed with internal error: {}", message)
+            }
+        }
+    }
+}
+
+impl Error for RequestError {}
+
+/// The reasons the entities of an entity query response can't be read as a set of components.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityQueryError {
+    /// The query failed.
+    Request(RequestError),
+
+    /// The query used the count result type, so the response has no entities.
+    CountResult(u32),
+
+    /// An entity in the response doesn't have one of the requested components, e.g. because
+    /// the query's result type didn't include it.
+    MissingComponent {
+        entity_id: EntityId,
+        component_id: ComponentId,
+    },
+}
+
+impl fmt::Display for EntityQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityQueryError::Request(error) => error.fmt(f),
+            EntityQueryError::CountResult(count) => write!(
+                f,
+                "Expected a snapshot result, but the query returned a count of {}",
+                count
+            ),
+            EntityQueryError::MissingComponent {
+                entity_id,
+                component_id,
+            } => write!(
+                f,
+                "Entity {} in the query response doesn't have component {}",
+                entity_id.id, component_id
+            ),
+        }
+    }
+}
+
+impl Error for EntityQueryError {}
+
+impl From<RequestError> for EntityQueryError {
+    fn from(error: RequestError) -> Self {
+        EntityQueryError::Request(error)
+    }
+}
+
 #[derive(Debug)]
 pub enum WorkerOp<'a> {
     Disconnect(DisconnectOp),
@@ -118,8 +268,10 @@ pub enum WorkerOp<'a> {
     EntityQueryResponse(EntityQueryResponseOp),
 }
 
-impl<'a> From<&'a Worker_Op> for WorkerOp<'a> {
-    fn from(op: &'a Worker_Op) -> Self {
+impl<'a> WorkerOp<'a> {
+    /// Converts an op owned by the C API, using `registry` to look up the vtables that
+    /// the C API used to deserialize any component data in it.
+    pub(crate) fn from_worker_sdk(op: &'a Worker_Op, registry: &ComponentRegistry) -> Self {
         unsafe {
             let erased_op = &op.op;
             let op_type = Worker_StatusCode::from(op.op_type);
@@ -180,7 +332,7 @@ impl<'a> From<&'a Worker_Op> for WorkerOp<'a> {
                     let add_component_op = AddComponentOp {
                         entity_id: EntityId::new(op.entity_id),
                         component_id: op.data.component_id,
-                        component_data: internal::ComponentData::from(&op.data),
+                        component_data: internal::ComponentData::new(&op.data, registry),
                     };
                     WorkerOp::AddComponent(add_component_op)
                 }
@@ -206,7 +358,7 @@ impl<'a> From<&'a Worker_Op> for WorkerOp<'a> {
                     let component_update_op = ComponentUpdateOp {
                         entity_id: EntityId::new(op.entity_id),
                         component_id: op.update.component_id,
-                        component_update: internal::ComponentUpdate::from(&op.update),
+                        component_update: internal::ComponentUpdate::new(&op.update, registry),
                     };
                     WorkerOp::ComponentUpdate(component_update_op)
                 }
@@ -224,7 +376,7 @@ impl<'a> From<&'a Worker_Op> for WorkerOp<'a> {
                         caller_worker_id: cstr_to_string(op.caller_worker_id),
                         caller_attribute_set: attribute_set,
                         component_id: op.request.component_id,
-                        request: internal::CommandRequest::from(&op.request),
+                        request: internal::CommandRequest::new(&op.request, registry),
                     };
                     WorkerOp::CommandRequest(command_request_op)
                 }
@@ -233,7 +385,7 @@ impl<'a> From<&'a Worker_Op> for WorkerOp<'a> {
                     let status_code = match Worker_StatusCode::from(op.status_code) {
                         Worker_StatusCode_WORKER_STATUS_CODE_SUCCESS => {
                             StatusCode::Success(CommandResponse {
-                                response: internal::CommandResponse::from(&op.response),
+                                response: internal::CommandResponse::new(&op.response, registry),
                             })
                         }
                         Worker_StatusCode_WORKER_STATUS_CODE_TIMEOUT => {
@@ -383,18 +535,32 @@ impl<'a> From<&'a Worker_Op> for WorkerOp<'a> {
                                 // Is count type.
                                 StatusCode::Success(QueryResponse::Result(op.result_count))
                             } else {
-                                let mut entities = HashMap::new();
                                 let raw_entities =
                                     slice::from_raw_parts(op.results, op.result_count as usize);
 
-                                for raw_entity in raw_entities {
-                                    entities.insert(
-                                        EntityId::new(raw_entity.entity_id),
-                                        Entity::from_worker_sdk(raw_entity).unwrap(),
-                                    );
-                                }
+                                let entities = raw_entities
+                                    .iter()
+                                    .map(|raw_entity| {
+                                        let entity_id = EntityId::new(raw_entity.entity_id);
+                                        Entity::from_worker_sdk(raw_entity, |id| {
+                                            registry.get_vtable(id).cloned()
+                                        })
+                                        .map(|entity| (entity_id, entity))
+                                        .map_err(|message| {
+                                            format!(
+                                                "Could not read entity {} in the query response: {}",
+                                                entity_id.id, message
+                                            )
+                                        })
+                                    })
+                                    .collect::<Result<HashMap<_, _>, String>>();
 
-                                StatusCode::Success(QueryResponse::Snapshot(entities))
+                                match entities {
+                                    Ok(entities) => {
+                                        StatusCode::Success(QueryResponse::Snapshot(entities))
+                                    }
+                                    Err(message) => StatusCode::InternalError(message),
+                                }
                             }
                         }
                         Worker_StatusCode_WORKER_STATUS_CODE_TIMEOUT => {
@@ -434,44 +600,44 @@ impl<'a> From<&'a Worker_Op> for WorkerOp<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DisconnectOp {
     pub reason: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FlagUpdateOp {
     pub name: String,
     pub value: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LogMessageOp {
     pub message: String,
     pub log_level: LogLevel,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MetricsOp {
     pub metrics: Metrics,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CriticalSectionOp {
     pub in_critical_section: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AddEntityOp {
     pub entity_id: EntityId,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RemoveEntityOp {
     pub entity_id: EntityId,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ReserveEntityIdsResponseOp {
     pub request_id: RequestId<ReserveEntityIdsRequest>,
     pub status_code: StatusCode<ReservedEntityIdRange>,
@@ -479,15 +645,15 @@ pub struct ReserveEntityIdsResponseOp {
 
 // TODO: When https://doc.rust-lang.org/std/iter/trait.Step.html is stabilized - replace this
 //       with std::ops::Range<EntityId> and implement Step for EntityId.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ReservedEntityIdRange {
-    current: i64,
-    consumed: u32,
-    reserved: u32,
+    pub(crate) current: i64,
+    pub(crate) consumed: u32,
+    pub(crate) reserved: u32,
 }
 
 impl ReservedEntityIdRange {
-    pub(crate) fn new(first: i64, number: u32) -> Self {
+    pub fn new(first: i64, number: u32) -> Self {
         ReservedEntityIdRange {
             current: first,
             consumed: 0,
@@ -512,31 +678,87 @@ impl Iterator for ReservedEntityIdRange {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CreateEntityResponseOp {
     pub request_id: RequestId<CreateEntityRequest>,
     pub status_code: StatusCode<EntityId>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DeleteEntityResponseOp {
     pub request_id: RequestId<DeleteEntityRequest>,
     pub entity_id: EntityId,
     pub status_code: StatusCode<()>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum QueryResponse {
     Snapshot(HashMap<EntityId, Entity>),
     Result(u32),
 }
 
-#[derive(Debug)]
+impl QueryResponse {
+    /// Returns the entities of a snapshot response along with their components of the types in
+    /// `T`, ordered by entity ID.
+    ///
+    /// `T` is either a single component or a tuple of components, and each item is the entity
+    /// ID followed by a reference to each component. If any entity is missing one of the
+    /// components, an error is returned rather than skipping it.
+    pub fn entities<'a, T: ComponentRefs<'a>>(
+        &'a self,
+    ) -> Result<vec::IntoIter<T::Item>, EntityQueryError> {
+        let entities = match self {
+            QueryResponse::Snapshot(entities) => entities,
+            QueryResponse::Result(count) => return Err(EntityQueryError::CountResult(*count)),
+        };
+
+        let mut ids = entities.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        let items = ids
+            .into_iter()
+            .map(|entity_id| {
+                T::get(entity_id, &entities[&entity_id]).map_err(|component_id| {
+                    EntityQueryError::MissingComponent {
+                        entity_id,
+                        component_id,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items.into_iter())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct EntityQueryResponseOp {
     pub request_id: RequestId<EntityQueryRequest>,
     pub status_code: StatusCode<QueryResponse>,
 }
 
+impl EntityQueryResponseOp {
+    /// Returns the entities in the response along with their components of the types in `T`.
+    ///
+    /// See [`QueryResponse::entities`](enum.QueryResponse.html#method.entities). Failed
+    /// queries return [`EntityQueryError::Request`](enum.EntityQueryError.html).
+    ///
+    /// ```
+    /// # use spatialos_sdk::worker::{component::Component, op::*};
+    /// # fn example<Position: Component, Metadata: Component>(
+    /// #     op: &EntityQueryResponseOp,
+    /// # ) -> Result<(), EntityQueryError> {
+    /// for (entity_id, position, metadata) in op.entities::<(Position, Metadata)>()? {
+    ///     // ...
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn entities<'a, T: ComponentRefs<'a>>(
+        &'a self,
+    ) -> Result<vec::IntoIter<T::Item>, EntityQueryError> {
+        self.status_code.as_result()?.entities::<T>()
+    }
+}
+
 #[derive(Debug)]
 pub struct AddComponentOp<'a> {
     pub entity_id: EntityId,
@@ -546,12 +768,13 @@ pub struct AddComponentOp<'a> {
 
 impl<'a> AddComponentOp<'a> {
     pub fn get<C: Component>(&self) -> Option<&C> {
-        if C::ID == self.component_data.component_id {
-            // TODO: Deserialize schema_type if user_handle is null.
-            Some(unsafe { &*(self.component_data.user_handle as *const _) })
-        } else {
-            None
-        }
+        self.component_data.get::<C>()
+    }
+
+    /// Like [`get`](#method.get), but returns an error if the payload had to be deserialized
+    /// from schema and that failed.
+    pub fn try_get<C: Component>(&self) -> Result<Option<&C>, String> {
+        self.component_data.try_get::<C>()
     }
 
     fn schema(&self) -> &SchemaComponentData {
@@ -559,13 +782,13 @@ impl<'a> AddComponentOp<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RemoveComponentOp {
     pub entity_id: EntityId,
     pub component_id: ComponentId,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AuthorityChangeOp {
     pub entity_id: EntityId,
     pub component_id: ComponentId,
@@ -581,14 +804,20 @@ pub struct ComponentUpdateOp<'a> {
 
 impl<'a> ComponentUpdateOp<'a> {
     pub fn get<C: Component>(&self) -> Option<&C::Update> {
-        // TODO: Deserialize schema_type if user_handle is null.
-        if C::ID == self.component_update.component_id
-            && !self.component_update.user_handle.is_null()
-        {
-            Some(unsafe { &*(self.component_update.user_handle as *const _) })
-        } else {
-            None
-        }
+        self.component_update.get::<C>()
+    }
+
+    /// Like [`get`](#method.get), but returns an error if the payload had to be deserialized
+    /// from schema and that failed.
+    pub fn try_get<C: Component>(&self) -> Result<Option<&C::Update>, String> {
+        self.component_update.try_get::<C>()
+    }
+
+    /// Returns the events of type `E` that were triggered in this update.
+    ///
+    /// Returns an empty slice if the update is for a different component.
+    pub fn events<E: Event>(&self) -> &[E::Data] {
+        self.get::<E::Component>().map_or(&[], E::get)
     }
 
     fn schema(&self) -> &SchemaComponentUpdate {
@@ -609,12 +838,18 @@ pub struct CommandRequestOp<'a> {
 
 impl<'a> CommandRequestOp<'a> {
     pub fn get<C: Component>(&self) -> Option<&C::CommandRequest> {
-        // TODO: Deserialize schema_type if user_handle is null.
-        if C::ID == self.component_id && !self.request.user_handle.is_null() {
-            Some(unsafe { &*(self.request.user_handle as *const _) })
-        } else {
-            None
-        }
+        self.request.get::<C>()
+    }
+
+    /// Like [`get`](#method.get), but returns an error if the payload had to be deserialized
+    /// from schema and that failed.
+    pub fn try_get<C: Component>(&self) -> Result<Option<&C::CommandRequest>, String> {
+        self.request.try_get::<C>()
+    }
+
+    /// Returns the request if it is a request for command `C`.
+    pub fn get_command<C: Command>(&self) -> Option<&C::Request> {
+        self.get::<C::Component>().and_then(C::get_request)
     }
 
     fn schema(&self) -> &SchemaCommandRequest {
@@ -637,12 +872,18 @@ pub struct CommandResponse<'a> {
 
 impl<'a> CommandResponse<'a> {
     pub fn get<C: Component>(&self) -> Option<&C::CommandResponse> {
-        // TODO: Deserialize schema_type if user_handle is null.
-        if C::ID == self.response.component_id && !self.response.user_handle.is_null() {
-            Some(unsafe { &*(self.response.user_handle as *const _) })
-        } else {
-            None
-        }
+        self.response.get::<C>()
+    }
+
+    /// Like [`get`](#method.get), but returns an error if the payload had to be deserialized
+    /// from schema and that failed.
+    pub fn try_get<C: Component>(&self) -> Result<Option<&C::CommandResponse>, String> {
+        self.response.try_get::<C>()
+    }
+
+    /// Returns the response if it is a response to command `C`.
+    pub fn get_command<C: Command>(&self) -> Option<&C::Response> {
+        self.get::<C::Component>().and_then(C::get_response)
     }
 
     fn schema(&self) -> &SchemaCommandResponse {
@@ -650,9 +891,572 @@ impl<'a> CommandResponse<'a> {
     }
 }
 
+// ================================= Owned Ops ================================= //
+
+/// An owned version of [`WorkerOp`].
+///
+/// Unlike [`WorkerOp`], an `OwnedWorkerOp` doesn't borrow from an [`OpList`], so it can be
+/// stored, sent between threads and constructed directly in Rust (e.g. in tests). Component
+/// data, updates and command payloads are shared with the op they were copied from through
+/// their reference count rather than being deep-copied.
+///
+/// Owned ops are created with [`WorkerOp::to_owned`], or constructed directly.
+///
+/// # Examples
+///
+/// ```
+/// use spatialos_sdk::worker::{
+///     op::{AddEntityOp, OwnedOpList, OwnedWorkerOp},
+///     EntityId,
+/// };
+///
+/// let ops: OwnedOpList = vec![OwnedWorkerOp::AddEntity(AddEntityOp {
+///     entity_id: EntityId::new(1),
+/// })]
+/// .into();
+///
+/// assert_eq!(1, ops.len());
+/// ```
+#[derive(Debug, Clone)]
+pub enum OwnedWorkerOp {
+    Disconnect(DisconnectOp),
+    FlagUpdate(FlagUpdateOp),
+    LogMessage(LogMessageOp),
+    Metrics(MetricsOp),
+    CriticalSection(CriticalSectionOp),
+    AddEntity(AddEntityOp),
+    RemoveEntity(RemoveEntityOp),
+    AddComponent(OwnedAddComponentOp),
+    RemoveComponent(RemoveComponentOp),
+    ComponentUpdate(OwnedComponentUpdateOp),
+    AuthorityChange(AuthorityChangeOp),
+    CommandRequest(OwnedCommandRequestOp),
+    CommandResponse(OwnedCommandResponseOp),
+    ReserveEntityIdsResponse(ReserveEntityIdsResponseOp),
+    CreateEntityResponse(CreateEntityResponseOp),
+    DeleteEntityResponse(DeleteEntityResponseOp),
+    EntityQueryResponse(EntityQueryResponseOp),
+}
+
+impl<'a> WorkerOp<'a> {
+    /// Creates an owned copy of the op.
+    ///
+    /// Component data, updates and command payloads are only carried over if they were
+    /// deserialized by a vtable in the connection's component registry, or if they have
+    /// already been deserialized from their schema objects by calling `get` on this op.
+    /// Otherwise the corresponding `get` methods on the owned op will return `None`.
+    pub fn to_owned(&self) -> OwnedWorkerOp {
+        match self {
+            WorkerOp::Disconnect(op) => OwnedWorkerOp::Disconnect(op.clone()),
+            WorkerOp::FlagUpdate(op) => OwnedWorkerOp::FlagUpdate(op.clone()),
+            WorkerOp::LogMessage(op) => OwnedWorkerOp::LogMessage(op.clone()),
+            WorkerOp::Metrics(op) => OwnedWorkerOp::Metrics(op.clone()),
+            WorkerOp::CriticalSection(op) => OwnedWorkerOp::CriticalSection(op.clone()),
+            WorkerOp::AddEntity(op) => OwnedWorkerOp::AddEntity(op.clone()),
+            WorkerOp::RemoveEntity(op) => OwnedWorkerOp::RemoveEntity(op.clone()),
+            WorkerOp::AddComponent(op) => OwnedWorkerOp::AddComponent(OwnedAddComponentOp {
+                entity_id: op.entity_id,
+                component_id: op.component_id,
+                data: op.component_data.to_owned_handle(),
+            }),
+            WorkerOp::RemoveComponent(op) => OwnedWorkerOp::RemoveComponent(op.clone()),
+            WorkerOp::ComponentUpdate(op) => {
+                OwnedWorkerOp::ComponentUpdate(OwnedComponentUpdateOp {
+                    entity_id: op.entity_id,
+                    component_id: op.component_id,
+                    update: op.component_update.to_owned_handle(),
+                })
+            }
+            WorkerOp::AuthorityChange(op) => OwnedWorkerOp::AuthorityChange(op.clone()),
+            WorkerOp::CommandRequest(op) => OwnedWorkerOp::CommandRequest(OwnedCommandRequestOp {
+                request_id: op.request_id,
+                entity_id: op.entity_id,
+                timeout_millis: op.timeout_millis,
+                caller_worker_id: op.caller_worker_id.clone(),
+                caller_attribute_set: op.caller_attribute_set.clone(),
+                component_id: op.component_id,
+                command_index: op.request.command_index,
+                request: op.request.to_owned_handle(),
+            }),
+            WorkerOp::CommandResponse(op) => {
+                let response = match &op.response {
+                    StatusCode::Success(response) => {
+                        let response = &response.response;
+                        StatusCode::Success(OwnedCommandResponse {
+                            component_id: response.component_id,
+                            command_index: response.command_index,
+                            response: response.to_owned_handle(),
+                        })
+                    }
+                    StatusCode::Timeout(message) => StatusCode::Timeout(message.clone()),
+                    StatusCode::NotFound(message) => StatusCode::NotFound(message.clone()),
+                    StatusCode::AuthorityLost(message) => {
+                        StatusCode::AuthorityLost(message.clone())
+                    }
+                    StatusCode::PermissionDenied(message) => {
+                        StatusCode::PermissionDenied(message.clone())
+                    }
+                    StatusCode::ApplicationError(message) => {
+                        StatusCode::ApplicationError(message.clone())
+                    }
+                    StatusCode::InternalError(message) => {
+                        StatusCode::InternalError(message.clone())
+                    }
+                };
+
+                OwnedWorkerOp::CommandResponse(OwnedCommandResponseOp {
+                    request_id: op.request_id,
+                    entity_id: op.entity_id,
+                    component_id: op.component_id,
+                    response,
+                })
+            }
+            WorkerOp::ReserveEntityIdsResponse(op) => {
+                OwnedWorkerOp::ReserveEntityIdsResponse(op.clone())
+            }
+            WorkerOp::CreateEntityResponse(op) => OwnedWorkerOp::CreateEntityResponse(op.clone()),
+            WorkerOp::DeleteEntityResponse(op) => OwnedWorkerOp::DeleteEntityResponse(op.clone()),
+            WorkerOp::EntityQueryResponse(op) => OwnedWorkerOp::EntityQueryResponse(op.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OwnedAddComponentOp {
+    pub entity_id: EntityId,
+    pub component_id: ComponentId,
+    data: Option<UserHandle>,
+}
+
+impl OwnedAddComponentOp {
+    pub fn new<C: Component>(entity_id: EntityId, data: C) -> Self {
+        OwnedAddComponentOp {
+            entity_id,
+            component_id: C::ID,
+            data: Some(UserHandle::component_data::<C>(data)),
+        }
+    }
+
+    pub fn get<C: Component>(&self) -> Option<&C> {
+        self.data
+            .as_ref()
+            .and_then(|data| unsafe { data.get::<C, C>() })
+    }
+
+    pub(crate) fn handle(&self) -> Option<&UserHandle> {
+        self.data.as_ref()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OwnedComponentUpdateOp {
+    pub entity_id: EntityId,
+    pub component_id: ComponentId,
+    update: Option<UserHandle>,
+}
+
+impl OwnedComponentUpdateOp {
+    pub fn new<C: Component>(entity_id: EntityId, update: C::Update) -> Self {
+        OwnedComponentUpdateOp {
+            entity_id,
+            component_id: C::ID,
+            update: Some(UserHandle::component_update::<C>(update)),
+        }
+    }
+
+    pub fn get<C: Component>(&self) -> Option<&C::Update> {
+        self.update
+            .as_ref()
+            .and_then(|update| unsafe { update.get::<C, C::Update>() })
+    }
+
+    pub fn events<E: Event>(&self) -> &[E::Data] {
+        self.get::<E::Component>().map_or(&[], E::get)
+    }
+
+    pub(crate) fn handle(&self) -> Option<&UserHandle> {
+        self.update.as_ref()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OwnedCommandRequestOp {
+    pub request_id: RequestId<IncomingCommandRequest>,
+    pub entity_id: EntityId,
+    pub timeout_millis: u32,
+    pub caller_worker_id: String,
+    pub caller_attribute_set: Vec<String>,
+    pub component_id: ComponentId,
+    pub command_index: CommandIndex,
+    request: Option<UserHandle>,
+}
+
+impl OwnedCommandRequestOp {
+    /// Creates a command request op with no timeout and an empty caller worker ID and
+    /// attribute set. The public fields can be modified afterwards.
+    pub fn new<C: Component>(
+        request_id: RequestId<IncomingCommandRequest>,
+        entity_id: EntityId,
+        request: C::CommandRequest,
+    ) -> Self {
+        let request = UserHandle::command_request::<C>(request);
+        OwnedCommandRequestOp {
+            request_id,
+            entity_id,
+            timeout_millis: 0,
+            caller_worker_id: String::new(),
+            caller_attribute_set: Vec::new(),
+            component_id: C::ID,
+            command_index: request.command_index(),
+            request: Some(request),
+        }
+    }
+
+    pub fn get<C: Component>(&self) -> Option<&C::CommandRequest> {
+        self.request
+            .as_ref()
+            .and_then(|request| unsafe { request.get::<C, C::CommandRequest>() })
+    }
+
+    pub fn get_command<C: Command>(&self) -> Option<&C::Request> {
+        self.get::<C::Component>().and_then(C::get_request)
+    }
+
+    pub(crate) fn handle(&self) -> Option<&UserHandle> {
+        self.request.as_ref()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OwnedCommandResponseOp {
+    pub request_id: RequestId<OutgoingCommandRequest>,
+    pub entity_id: EntityId,
+    pub component_id: ComponentId,
+    pub response: StatusCode<OwnedCommandResponse>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OwnedCommandResponse {
+    pub component_id: ComponentId,
+    pub command_index: CommandIndex,
+    response: Option<UserHandle>,
+}
+
+impl OwnedCommandResponse {
+    pub fn new<C: Component>(response: C::CommandResponse) -> Self {
+        let response = UserHandle::command_response::<C>(response);
+        OwnedCommandResponse {
+            component_id: C::ID,
+            command_index: response.command_index(),
+            response: Some(response),
+        }
+    }
+
+    pub fn get<C: Component>(&self) -> Option<&C::CommandResponse> {
+        self.response
+            .as_ref()
+            .and_then(|response| unsafe { response.get::<C, C::CommandResponse>() })
+    }
+
+    pub fn get_command<C: Command>(&self) -> Option<&C::Response> {
+        self.get::<C::Component>().and_then(C::get_response)
+    }
+
+    pub(crate) fn handle(&self) -> Option<&UserHandle> {
+        self.response.as_ref()
+    }
+}
+
+/// A list of [`OwnedWorkerOp`]s.
+///
+/// This is the owned counterpart of [`OpList`]. It can be built from a `Vec` or an
+/// iterator of ops, or copied from an [`OpList`] with [`OpList::to_owned`].
+#[derive(Debug, Clone, Default)]
+pub struct OwnedOpList {
+    ops: Vec<OwnedWorkerOp>,
+}
+
+impl OwnedOpList {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, op: OwnedWorkerOp) {
+        self.ops.push(op);
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, OwnedWorkerOp> {
+        self.ops.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn into_vec(self) -> Vec<OwnedWorkerOp> {
+        self.ops
+    }
+}
+
+impl From<Vec<OwnedWorkerOp>> for OwnedOpList {
+    fn from(ops: Vec<OwnedWorkerOp>) -> Self {
+        OwnedOpList { ops }
+    }
+}
+
+impl FromIterator<OwnedWorkerOp> for OwnedOpList {
+    fn from_iter<I: IntoIterator<Item = OwnedWorkerOp>>(iter: I) -> Self {
+        OwnedOpList {
+            ops: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<OwnedWorkerOp> for OwnedOpList {
+    fn extend<I: IntoIterator<Item = OwnedWorkerOp>>(&mut self, iter: I) {
+        self.ops.extend(iter);
+    }
+}
+
+impl IntoIterator for OwnedOpList {
+    type Item = OwnedWorkerOp;
+    type IntoIter = ::std::vec::IntoIter<OwnedWorkerOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a OwnedOpList {
+    type Item = &'a OwnedWorkerOp;
+    type IntoIter = slice::Iter<'a, OwnedWorkerOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.iter()
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::ReservedEntityIdRange;
+    use super::*;
+    use crate::worker::mock::test::Counter;
+    use std::{mem, os::raw, ptr::NonNull};
+
+    // A component that deserializes to fixed values without touching the schema objects.
+    struct Fallback;
+
+    impl Component for Fallback {
+        type Update = u32;
+        type CommandRequest = u32;
+        type CommandResponse = u32;
+
+        const ID: ComponentId = 4321;
+
+        fn from_data(_: &SchemaComponentData) -> Result<Self, String> {
+            Ok(Fallback)
+        }
+        fn from_update(_: &SchemaComponentUpdate) -> Result<Self::Update, String> {
+            Ok(2)
+        }
+        fn from_request(index: u32, _: &SchemaCommandRequest) -> Result<u32, String> {
+            Ok(index)
+        }
+        fn from_response(_: u32, _: &SchemaCommandResponse) -> Result<u32, String> {
+            Err("Failed to deserialize response".to_owned())
+        }
+
+        fn to_data(_: &Self) -> Result<SchemaComponentData, String> {
+            Err("Fallback data is never serialized".to_owned())
+        }
+        fn to_update(_: &Self::Update) -> Result<SchemaComponentUpdate, String> {
+            Err("Fallback updates are never serialized".to_owned())
+        }
+        fn to_request(_: &Self::CommandRequest) -> Result<SchemaCommandRequest, String> {
+            Err("Fallback requests are never serialized".to_owned())
+        }
+        fn to_response(_: &Self::CommandResponse) -> Result<SchemaCommandResponse, String> {
+            Err("Fallback responses are never serialized".to_owned())
+        }
+
+        fn get_request_command_index(request: &Self::CommandRequest) -> u32 {
+            *request
+        }
+        fn get_response_command_index(response: &Self::CommandResponse) -> u32 {
+            *response
+        }
+    }
+
+    fn raw_op(op_type: Worker_OpType, schema_type: *mut raw::c_void) -> Worker_Op {
+        let mut op: Worker_Op = unsafe { mem::zeroed() };
+        op.op_type = op_type as _;
+        match op_type {
+            Worker_OpType_WORKER_OP_TYPE_ADD_COMPONENT => {
+                op.op.add_component.data.component_id = Fallback::ID;
+                op.op.add_component.data.schema_type = schema_type as _;
+            }
+            Worker_OpType_WORKER_OP_TYPE_COMPONENT_UPDATE => {
+                op.op.component_update.update.component_id = Fallback::ID;
+                op.op.component_update.update.schema_type = schema_type as _;
+            }
+            Worker_OpType_WORKER_OP_TYPE_COMMAND_REQUEST => {
+                op.op.command_request.caller_worker_id = b"TestWorker\0".as_ptr() as _;
+                op.op.command_request.request.component_id = Fallback::ID;
+                op.op.command_request.request.command_index = 3;
+                op.op.command_request.request.schema_type = schema_type as _;
+            }
+            Worker_OpType_WORKER_OP_TYPE_COMMAND_RESPONSE => {
+                op.op.command_response.status_code =
+                    Worker_StatusCode_WORKER_STATUS_CODE_SUCCESS as _;
+                op.op.command_response.response.component_id = Fallback::ID;
+                op.op.command_response.response.schema_type = schema_type as _;
+            }
+            _ => unreachable!(),
+        }
+        op
+    }
+
+    #[test]
+    fn ops_without_user_handles_are_deserialized_from_schema() {
+        // The schema objects are never dereferenced by `Fallback`.
+        let schema = NonNull::<raw::c_void>::dangling().as_ptr();
+
+        let raw = raw_op(Worker_OpType_WORKER_OP_TYPE_ADD_COMPONENT, schema);
+        match WorkerOp::from_worker_sdk(&raw, &ComponentRegistry::global()) {
+            WorkerOp::AddComponent(op) => {
+                assert!(op.get::<Fallback>().is_some());
+                match WorkerOp::AddComponent(op).to_owned() {
+                    OwnedWorkerOp::AddComponent(owned) => {
+                        assert!(owned.get::<Fallback>().is_some())
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        let raw = raw_op(Worker_OpType_WORKER_OP_TYPE_COMPONENT_UPDATE, schema);
+        match WorkerOp::from_worker_sdk(&raw, &ComponentRegistry::global()) {
+            WorkerOp::ComponentUpdate(op) => assert_eq!(Some(&2), op.get::<Fallback>()),
+            _ => unreachable!(),
+        }
+
+        let raw = raw_op(Worker_OpType_WORKER_OP_TYPE_COMMAND_REQUEST, schema);
+        match WorkerOp::from_worker_sdk(&raw, &ComponentRegistry::global()) {
+            WorkerOp::CommandRequest(op) => assert_eq!(Some(&3), op.get::<Fallback>()),
+            _ => unreachable!(),
+        }
+
+        let raw = raw_op(Worker_OpType_WORKER_OP_TYPE_COMMAND_RESPONSE, schema);
+        match WorkerOp::from_worker_sdk(&raw, &ComponentRegistry::global()) {
+            WorkerOp::CommandResponse(op) => match op.response {
+                StatusCode::Success(response) => {
+                    assert_eq!(None, response.get::<Fallback>());
+                    assert_eq!(
+                        Err("Failed to deserialize response".to_owned()),
+                        response.try_get::<Fallback>()
+                    );
+                }
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn ops_without_user_handles_or_schema_return_none() {
+        let raw = raw_op(
+            Worker_OpType_WORKER_OP_TYPE_ADD_COMPONENT,
+            std::ptr::null_mut(),
+        );
+        match WorkerOp::from_worker_sdk(&raw, &ComponentRegistry::global()) {
+            WorkerOp::AddComponent(op) => assert!(op.try_get::<Fallback>().unwrap().is_none()),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn ops_are_copied_with_the_connections_registry() {
+        // `Fallback` is never added to the global registry.
+        let registry = ComponentRegistry::new().with_component::<Fallback>();
+        let handle = UserHandle::component_data::<Fallback>(Fallback);
+        let mut raw = raw_op(
+            Worker_OpType_WORKER_OP_TYPE_ADD_COMPONENT,
+            std::ptr::null_mut(),
+        );
+        raw.op.add_component.data.user_handle = handle.as_ptr() as _;
+
+        let owned = WorkerOp::from_worker_sdk(&raw, &registry).to_owned();
+        drop(handle);
+        match owned {
+            OwnedWorkerOp::AddComponent(op) => assert!(op.get::<Fallback>().is_some()),
+            _ => unreachable!(),
+        }
+    }
+
+    fn raw_query_response(data: &Worker_ComponentData) -> Worker_Op {
+        let mut op: Worker_Op = unsafe { mem::zeroed() };
+        op.op_type = Worker_OpType_WORKER_OP_TYPE_ENTITY_QUERY_RESPONSE as _;
+        op.op.entity_query_response.status_code = Worker_StatusCode_WORKER_STATUS_CODE_SUCCESS as _;
+        op.op.entity_query_response.result_count = 1;
+        op.op.entity_query_response.results = Box::into_raw(Box::new(Worker_Entity {
+            entity_id: 7,
+            component_count: 1,
+            components: data,
+        }));
+        op
+    }
+
+    fn query_status(raw: Worker_Op, registry: &ComponentRegistry) -> StatusCode<QueryResponse> {
+        let status_code = match WorkerOp::from_worker_sdk(&raw, registry) {
+            WorkerOp::EntityQueryResponse(op) => op.status_code,
+            _ => unreachable!(),
+        };
+        drop(unsafe { Box::from_raw(raw.op.entity_query_response.results as *mut Worker_Entity) });
+        status_code
+    }
+
+    #[test]
+    fn query_responses_deserialize_schema_data_with_the_connections_registry() {
+        let registry = ComponentRegistry::new().with_component::<Fallback>();
+        let data = Worker_ComponentData {
+            reserved: std::ptr::null_mut(),
+            component_id: Fallback::ID,
+            // The schema object is never dereferenced by `Fallback`.
+            schema_type: NonNull::dangling().as_ptr(),
+            user_handle: std::ptr::null_mut(),
+        };
+
+        match query_status(raw_query_response(&data), &registry) {
+            StatusCode::Success(QueryResponse::Snapshot(entities)) => {
+                assert!(entities[&EntityId::new(7)].get::<Fallback>().is_some())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn query_responses_with_unknown_components_are_errors() {
+        let handle = UserHandle::component_data::<Fallback>(Fallback);
+        let data = Worker_ComponentData {
+            reserved: std::ptr::null_mut(),
+            component_id: Fallback::ID,
+            schema_type: std::ptr::null_mut(),
+            user_handle: handle.as_ptr() as _,
+        };
+
+        match query_status(raw_query_response(&data), &ComponentRegistry::new()) {
+            StatusCode::InternalError(message) => assert_eq!(
+                "Could not read entity 7 in the query response: \
+                 Could not find a vtable implementation for component 4321",
+                message
+            ),
+            _ => unreachable!(),
+        }
+    }
 
     #[test]
     fn reserved_entity_id_range_iterator_contains_correct_count() {
@@ -670,4 +1474,84 @@ mod test {
             current_id += 1;
         }
     }
+
+    fn query_response(entities: Vec<(i64, Entity)>) -> EntityQueryResponseOp {
+        EntityQueryResponseOp {
+            request_id: RequestId::new(1),
+            status_code: StatusCode::Success(QueryResponse::Snapshot(
+                entities
+                    .into_iter()
+                    .map(|(id, entity)| (EntityId::new(id), entity))
+                    .collect(),
+            )),
+        }
+    }
+
+    fn entity_with(counter: Option<u32>, fallback: bool) -> Entity {
+        let mut entity = Entity::new();
+        if let Some(value) = counter {
+            entity.add(Counter { value }).unwrap();
+        }
+        if fallback {
+            entity.add(Fallback).unwrap();
+        }
+        entity
+    }
+
+    #[test]
+    fn query_response_entities_are_typed_and_ordered_by_id() {
+        let response = query_response(vec![
+            (7, entity_with(Some(2), true)),
+            (3, entity_with(Some(1), true)),
+        ]);
+
+        let counters = response
+            .entities::<Counter>()
+            .unwrap()
+            .map(|(id, counter)| (id.id, counter.value))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(3, 1), (7, 2)], counters);
+
+        let (id, counter, _) = response
+            .entities::<(Counter, Fallback)>()
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!((EntityId::new(3), 1), (id, counter.value));
+    }
+
+    #[test]
+    fn query_response_entities_report_missing_components_and_failures() {
+        let response = query_response(vec![
+            (1, entity_with(Some(1), true)),
+            (2, entity_with(Some(2), false)),
+        ]);
+        assert_eq!(
+            Some(EntityQueryError::MissingComponent {
+                entity_id: EntityId::new(2),
+                component_id: Fallback::ID,
+            }),
+            response.entities::<(Counter, Fallback)>().err()
+        );
+
+        let count = EntityQueryResponseOp {
+            request_id: RequestId::new(1),
+            status_code: StatusCode::Success(QueryResponse::Result(4)),
+        };
+        assert_eq!(
+            Some(EntityQueryError::CountResult(4)),
+            count.entities::<Counter>().err()
+        );
+
+        let failed = EntityQueryResponseOp {
+            request_id: RequestId::new(1),
+            status_code: StatusCode::Timeout("Too slow".to_owned()),
+        };
+        assert_eq!(
+            Some(EntityQueryError::Request(RequestError::Timeout(
+                "Too slow".to_owned()
+            ))),
+            failed.entities::<Counter>().err()
+        );
+    }
 }
@@ -1,8 +1,9 @@
-use crate::worker::{component::DATABASE, vtable};
+use crate::worker::{component::ComponentRegistry, vtable};
 use spatialos_sdk_sys::worker::*;
 use std::{
     ffi::{CStr, CString},
     ptr,
+    sync::Arc,
 };
 
 pub struct ConnectionParameters {
@@ -15,7 +16,11 @@ pub struct ConnectionParameters {
     pub protocol_logging: ProtocolLoggingParameters,
     pub enable_protocol_logging_at_startup: bool,
     pub thread_affinity: ThreadAffinityParameters,
+    /// Whether to emit add and remove component ops on authority changes, for components
+    /// that aren't otherwise in the worker's interest.
+    pub enable_dynamic_components: bool,
     use_internal_serialization: bool,
+    component_registry: Option<ComponentRegistry>,
 }
 
 impl ConnectionParameters {
@@ -66,6 +71,19 @@ impl ConnectionParameters {
         self
     }
 
+    /// Enables internal serialization using the vtables in `registry`, rather than the
+    /// global registry.
+    pub fn with_component_registry(mut self, registry: ComponentRegistry) -> Self {
+        self.use_internal_serialization = true;
+        self.component_registry = Some(registry);
+        self
+    }
+
+    pub fn with_dynamic_components(mut self) -> Self {
+        self.enable_dynamic_components = true;
+        self
+    }
+
     pub fn default() -> Self {
         ConnectionParameters {
             worker_type: CString::new("").unwrap(),
@@ -78,7 +96,9 @@ impl ConnectionParameters {
             protocol_logging: ProtocolLoggingParameters::default(),
             enable_protocol_logging_at_startup: false,
             thread_affinity: ThreadAffinityParameters::default(),
+            enable_dynamic_components: false,
             use_internal_serialization: false,
+            component_registry: None,
         }
     }
 
@@ -106,9 +126,16 @@ impl ConnectionParameters {
             },
         };
 
+        let component_registry = Arc::new(
+            self.component_registry
+                .clone()
+                .unwrap_or_else(ComponentRegistry::global),
+        );
+
         IntermediateConnectionParameters {
             params: self,
             protocol,
+            component_registry,
         }
     }
 }
@@ -425,9 +452,15 @@ impl ThreadAffinityParameters {
 pub(crate) struct IntermediateConnectionParameters<'a> {
     params: &'a ConnectionParameters,
     protocol: IntermediateProtocolType,
+    component_registry: Arc<ComponentRegistry>,
 }
 
 impl<'a> IntermediateConnectionParameters<'a> {
+    /// Returns the registry whose vtables are passed to the C API.
+    pub(crate) fn component_registry(&self) -> &Arc<ComponentRegistry> {
+        &self.component_registry
+    }
+
     pub(crate) fn as_raw(&self) -> Worker_ConnectionParameters {
         let partial_network_params = Worker_NetworkParameters {
             use_external_ip: self.params.network.use_external_ip as u8,
@@ -510,17 +543,17 @@ impl<'a> IntermediateConnectionParameters<'a> {
             protocol_logging: self.params.protocol_logging.to_worker_sdk(),
             enable_protocol_logging_at_startup: self.params.enable_protocol_logging_at_startup
                 as u8,
-            enable_dynamic_components: 0,
+            enable_dynamic_components: self.params.enable_dynamic_components as u8,
             thread_affinity: self.params.thread_affinity.to_worker_sdk(),
 
             component_vtable_count: if self.params.use_internal_serialization {
-                DATABASE.len() as u32
+                self.component_registry.len() as u32
             } else {
                 0
             },
 
             component_vtables: if self.params.use_internal_serialization {
-                DATABASE.to_worker_sdk()
+                self.component_registry.to_worker_sdk()
             } else {
                 ptr::null()
             },
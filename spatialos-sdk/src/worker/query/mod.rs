@@ -0,0 +1,975 @@
+use std::ops::{BitAnd, BitOr, Not};
+use std::path::Path;
+use std::ptr;
+
+use crate::worker::component::{Component, ComponentId, ComponentRegistry, ComponentSet};
+use crate::worker::entity::Entity;
+use crate::worker::entity_builder::POSITION_COMPONENT_ID;
+use crate::worker::internal::schema::{
+    SchemaComponentData, SchemaDouble, SchemaObject, SchemaObjectField, SchemaPrimitiveField,
+};
+use crate::worker::snapshot::{
+    SerializedComponent, SerializedEntity, SnapshotError, SnapshotInputStream,
+};
+use crate::worker::EntityId;
+
+use spatialos_sdk_sys::worker::{
+    Schema_DestroyComponentData, Worker_AndConstraint, Worker_ComponentConstraint,
+    Worker_Constraint, Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_AND,
+    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_COMPONENT,
+    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_ENTITY_ID,
+    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_NOT,
+    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_OR,
+    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_SPHERE, Worker_Constraint_Union,
+    Worker_EntityIdConstraint, Worker_EntityQuery, Worker_NotConstraint, Worker_OrConstraint,
+    Worker_SphereConstraint,
+};
+
+mod normalize;
+pub mod text;
+mod validation;
+
+pub use self::text::ParseQueryError;
+pub use self::validation::ConstraintError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultType {
+    Count,
+    Snapshot(Vec<ComponentId>),
+}
+
+impl ResultType {
+    /// Returns the snapshot result type for the components in `T`, which is either a single
+    /// component or a tuple of components.
+    ///
+    /// ```
+    /// # use spatialos_sdk::worker::{component::*, query::ResultType};
+    /// # fn example<Position: Component, Rotate: Component>() {
+    /// let result_type = ResultType::snapshot_of::<(Position, Rotate)>();
+    /// assert_eq!(ResultType::Snapshot(vec![Position::ID, Rotate::ID]), result_type);
+    /// # }
+    /// ```
+    pub fn snapshot_of<T: ComponentSet>() -> Self {
+        ResultType::Snapshot(T::component_ids())
+    }
+
+    pub(crate) fn to_u8(&self) -> u8 {
+        match self {
+            ResultType::Count => 1,
+            ResultType::Snapshot(_) => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct EntityQuery {
+    pub constraint: QueryConstraint,
+    pub result_type: ResultType,
+}
+
+impl EntityQuery {
+    pub fn new(constraint: QueryConstraint, result_type: ResultType) -> Self {
+        EntityQuery {
+            constraint,
+            result_type,
+        }
+    }
+
+    pub(crate) fn to_worker_sdk(&self) -> WrappedEntityQuery {
+        let (constraint, underlying_constraints) = self.constraint.to_worker_sdk();
+        match &self.result_type {
+            ResultType::Count => {
+                let worker_entity_query = Worker_EntityQuery {
+                    constraint,
+                    result_type: self.result_type.to_u8(),
+                    snapshot_result_type_component_id_count: 0,
+                    snapshot_result_type_component_ids: ptr::null(),
+                };
+
+                WrappedEntityQuery {
+                    query: worker_entity_query,
+                    _ids: None,
+                    _underlying_constraint_data: underlying_constraints,
+                }
+            }
+            ResultType::Snapshot(ids) => {
+                let worker_entity_query = Worker_EntityQuery {
+                    constraint,
+                    result_type: self.result_type.to_u8(),
+                    snapshot_result_type_component_id_count: ids.len() as u32,
+                    snapshot_result_type_component_ids: ids.as_ptr(),
+                };
+
+                WrappedEntityQuery {
+                    query: worker_entity_query,
+                    _ids: Some(ids.as_slice()),
+                    _underlying_constraint_data: underlying_constraints,
+                }
+            }
+        }
+    }
+}
+
+/// The result of evaluating an [`EntityQuery`] locally, matching its [`ResultType`].
+///
+/// [`EntityQuery`]: struct.EntityQuery.html
+/// [`ResultType`]: enum.ResultType.html
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocalQueryResult<E> {
+    /// The number of matching entities.
+    Count(usize),
+
+    /// The matching entities, in the order they were evaluated, with only the components
+    /// requested by the result type.
+    Snapshot(Vec<(EntityId, E)>),
+}
+
+impl EntityQuery {
+    /// Evaluates the query against `entities` without sending it to the runtime.
+    ///
+    /// Returns an error if a `Sphere` constraint is evaluated against an entity whose
+    /// `improbable.Position` can't be read.
+    pub fn evaluate<I, E>(&self, entities: I) -> Result<LocalQueryResult<E::Projection>, String>
+    where
+        I: IntoIterator<Item = (EntityId, E)>,
+        E: QueryableEntity,
+    {
+        self.evaluate_each(entities.into_iter().map(Ok), |message| message)
+    }
+
+    /// Evaluates the query against a stream of serialized entities, e.g. those read from a
+    /// snapshot, stopping at the first error reading them.
+    pub fn evaluate_serialized<I>(
+        &self,
+        entities: I,
+    ) -> Result<LocalQueryResult<SerializedEntity>, SnapshotError>
+    where
+        I: IntoIterator<Item = Result<SerializedEntity, SnapshotError>>,
+    {
+        self.evaluate_each(
+            entities
+                .into_iter()
+                .map(|entity| entity.map(|entity| (entity.id, entity))),
+            SnapshotError::InvalidData,
+        )
+    }
+
+    /// Evaluates the query against the entities in the snapshot at `path`. Only one entity is
+    /// held in memory at a time, along with the matching entities for a snapshot result type.
+    pub fn evaluate_snapshot<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<LocalQueryResult<SerializedEntity>, SnapshotError> {
+        // Reading with an empty registry keeps every component in its serialized form.
+        let mut stream = SnapshotInputStream::with_registry(path, &ComponentRegistry::new())?;
+        self.evaluate_serialized(stream.serialized_entities())
+    }
+
+    fn evaluate_each<I, E, Err, F>(
+        &self,
+        entities: I,
+        error: F,
+    ) -> Result<LocalQueryResult<E::Projection>, Err>
+    where
+        I: Iterator<Item = Result<(EntityId, E), Err>>,
+        E: QueryableEntity,
+        F: Fn(String) -> Err,
+    {
+        let mut count = 0;
+        let mut matched = Vec::new();
+        for entity in entities {
+            let (entity_id, entity) = entity?;
+            if !self
+                .constraint
+                .matches(entity_id, &entity)
+                .map_err(&error)?
+            {
+                continue;
+            }
+
+            match &self.result_type {
+                ResultType::Count => count += 1,
+                ResultType::Snapshot(component_ids) => {
+                    let projection = entity.project(component_ids).map_err(&error)?;
+                    matched.push((entity_id, projection));
+                }
+            }
+        }
+
+        Ok(match self.result_type {
+            ResultType::Count => LocalQueryResult::Count(count),
+            ResultType::Snapshot(_) => LocalQueryResult::Snapshot(matched),
+        })
+    }
+}
+
+pub(crate) struct WrappedEntityQuery<'a> {
+    pub query: Worker_EntityQuery,
+    _ids: Option<&'a [u32]>,
+    _underlying_constraint_data: Box<[Worker_Constraint]>,
+}
+
+/// Entry points for building a [`QueryConstraint`] fluently.
+///
+/// Constraints can be combined with [`and`], [`or`] and [`not`], or with the `&`, `|` and `!`
+/// operators:
+///
+/// ```
+/// # use spatialos_sdk::worker::{component::Component, query::*, EntityId};
+/// # fn example<Position: Component, Rotate: Component>() {
+/// let constraint = Query::has::<Position>().within_sphere((10.0, 10.0, 10.0), 250.0)
+///     & !Query::has::<Rotate>()
+///     | Query::entity(EntityId::new(10));
+/// let query = EntityQuery::new(constraint, ResultType::snapshot_of::<(Position, Rotate)>());
+/// # }
+/// ```
+///
+/// [`QueryConstraint`]: enum.QueryConstraint.html
+/// [`and`]: enum.QueryConstraint.html#method.and
+/// [`or`]: enum.QueryConstraint.html#method.or
+/// [`not`]: enum.QueryConstraint.html#method.not
+pub struct Query;
+
+impl Query {
+    /// Matches entities that have component `C`.
+    pub fn has<C: Component>() -> QueryConstraint {
+        QueryConstraint::Component(C::ID)
+    }
+
+    /// Matches entities that have the component with ID `component_id`.
+    pub fn has_component(component_id: ComponentId) -> QueryConstraint {
+        QueryConstraint::Component(component_id)
+    }
+
+    /// Matches the entity with ID `entity_id`.
+    pub fn entity(entity_id: EntityId) -> QueryConstraint {
+        QueryConstraint::EntityId(entity_id)
+    }
+
+    /// Matches entities whose position is within `radius` of `center`.
+    pub fn within_sphere(center: (f64, f64, f64), radius: f64) -> QueryConstraint {
+        let (x, y, z) = center;
+        QueryConstraint::Sphere(x, y, z, radius)
+    }
+
+    /// Matches entities that match every constraint in `constraints`.
+    pub fn all<I: IntoIterator<Item = QueryConstraint>>(constraints: I) -> QueryConstraint {
+        QueryConstraint::And(constraints.into_iter().collect())
+    }
+
+    /// Matches entities that match any constraint in `constraints`.
+    pub fn any<I: IntoIterator<Item = QueryConstraint>>(constraints: I) -> QueryConstraint {
+        QueryConstraint::Or(constraints.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryConstraint {
+    EntityId(EntityId),
+    Component(ComponentId),
+    Sphere(f64, f64, f64, f64),
+
+    And(Vec<QueryConstraint>),
+    Or(Vec<QueryConstraint>),
+    Not(Box<QueryConstraint>),
+}
+
+impl QueryConstraint {
+    /// Matches entities that match both `self` and `other`.
+    ///
+    /// Chained calls produce a single `And` constraint rather than nesting them.
+    pub fn and(self, other: QueryConstraint) -> QueryConstraint {
+        let mut constraints = match self {
+            QueryConstraint::And(constraints) => constraints,
+            constraint => vec![constraint],
+        };
+        match other {
+            QueryConstraint::And(others) => constraints.extend(others),
+            other => constraints.push(other),
+        }
+        QueryConstraint::And(constraints)
+    }
+
+    /// Matches entities that match either `self` or `other`.
+    ///
+    /// Chained calls produce a single `Or` constraint rather than nesting them.
+    pub fn or(self, other: QueryConstraint) -> QueryConstraint {
+        let mut constraints = match self {
+            QueryConstraint::Or(constraints) => constraints,
+            constraint => vec![constraint],
+        };
+        match other {
+            QueryConstraint::Or(others) => constraints.extend(others),
+            other => constraints.push(other),
+        }
+        QueryConstraint::Or(constraints)
+    }
+
+    /// Matches entities that don't match `self`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> QueryConstraint {
+        QueryConstraint::Not(Box::new(self))
+    }
+
+    /// Matches entities that match `self` and have component `C`.
+    pub fn has<C: Component>(self) -> QueryConstraint {
+        self.and(Query::has::<C>())
+    }
+
+    /// Matches entities that match `self` and whose position is within `radius` of `center`.
+    pub fn within_sphere(self, center: (f64, f64, f64), radius: f64) -> QueryConstraint {
+        self.and(Query::within_sphere(center, radius))
+    }
+
+    /// Returns `true` if the entity with ID `entity_id` matches the constraint.
+    ///
+    /// `Sphere` constraints are evaluated against the entity's `improbable.Position`. An
+    /// entity without a position never matches them, and an error is returned if the position
+    /// can't be read.
+    pub fn matches<E: QueryableEntity + ?Sized>(
+        &self,
+        entity_id: EntityId,
+        entity: &E,
+    ) -> Result<bool, String> {
+        Ok(match self {
+            QueryConstraint::EntityId(id) => *id == entity_id,
+            QueryConstraint::Component(component_id) => entity.has_component(*component_id),
+            QueryConstraint::Sphere(x, y, z, radius) => match entity.position()? {
+                Some((position_x, position_y, position_z)) => {
+                    let (dx, dy, dz) = (position_x - x, position_y - y, position_z - z);
+                    (dx * dx + dy * dy + dz * dz).sqrt() <= *radius
+                }
+                None => false,
+            },
+            QueryConstraint::And(constraints) => {
+                for constraint in constraints {
+                    if !constraint.matches(entity_id, entity)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            QueryConstraint::Or(constraints) => {
+                for constraint in constraints {
+                    if constraint.matches(entity_id, entity)? {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+            QueryConstraint::Not(constraint) => !constraint.matches(entity_id, entity)?,
+        })
+    }
+
+    // The general strategy with this is to pre-allocate the memory required to store all elements
+    // in the constraint tree. This means that we can insert points to indices in the array which
+    // will contain the correct data. This also means that the array lifetime should live as long
+    // as the struct passed down to the C layer.
+    pub(crate) fn to_worker_sdk(&self) -> (Worker_Constraint, Box<[Worker_Constraint]>) {
+        // First descend the tree and count how many constraints we need in the Vec.
+        let size = self.constraint_len_recursive();
+
+        // Allocate the vector required to store this data. Use a dummy placeholder for the data.
+        let dummy_constraint = Worker_Constraint {
+            constraint_type: Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_ENTITY_ID as u8,
+            constraint: Worker_Constraint_Union {
+                entity_id_constraint: Worker_EntityIdConstraint { entity_id: 0 },
+            },
+        };
+        let underlying_data = vec![dummy_constraint; size as usize];
+        let mut data = underlying_data.into_boxed_slice();
+
+        // Now go down the tree again, this time creating pointers to the correct vector element.
+        // Also fill the vector with the correct data as we go down the tree as well.
+        let (constraint, _) = self.to_worker_sdk_recursive(&mut data, 0);
+
+        // Return the constraint and the underlying data.
+        (constraint, data)
+    }
+
+    fn to_worker_sdk_recursive(
+        &self,
+        underlying_data: &mut [Worker_Constraint],
+        current_index: usize,
+    ) -> (Worker_Constraint, usize) {
+        match &self {
+            QueryConstraint::EntityId(id) => {
+                let constraint = Worker_Constraint {
+                    constraint_type: Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_ENTITY_ID as u8,
+                    constraint: Worker_Constraint_Union {
+                        entity_id_constraint: Worker_EntityIdConstraint { entity_id: id.id },
+                    },
+                };
+
+                (constraint, 0)
+            }
+            QueryConstraint::Component(component_id) => {
+                let constraint = Worker_Constraint {
+                    constraint_type: Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_COMPONENT as u8,
+                    constraint: Worker_Constraint_Union {
+                        component_constraint: Worker_ComponentConstraint {
+                            component_id: *component_id,
+                        },
+                    },
+                };
+
+                (constraint, 0)
+            }
+            QueryConstraint::Sphere(x, y, z, radius) => {
+                let constraint = Worker_Constraint {
+                    constraint_type: Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_SPHERE as u8,
+                    constraint: Worker_Constraint_Union {
+                        sphere_constraint: Worker_SphereConstraint {
+                            x: *x,
+                            y: *y,
+                            z: *z,
+                            radius: *radius,
+                        },
+                    },
+                };
+
+                (constraint, 0)
+            }
+            QueryConstraint::And(constraints) => {
+                let mut num_constraints_filled = constraints.len();
+                let mut next_index = current_index;
+                for constraint in constraints {
+                    let (worker_constraint, elements_filled) = constraint.to_worker_sdk_recursive(
+                        underlying_data,
+                        current_index + num_constraints_filled,
+                    );
+                    num_constraints_filled += elements_filled;
+
+                    underlying_data[next_index] = worker_constraint;
+                    next_index += 1;
+                }
+
+                let constraint = Worker_Constraint {
+                    constraint_type: Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_AND as u8,
+                    constraint: Worker_Constraint_Union {
+                        and_constraint: Worker_AndConstraint {
+                            constraint_count: constraints.len() as u32,
+                            constraints: &mut underlying_data[current_index]
+                                as *mut Worker_Constraint,
+                        },
+                    },
+                };
+
+                (constraint, num_constraints_filled)
+            }
+            QueryConstraint::Or(constraints) => {
+                let mut num_constraints_filled = constraints.len();
+                let mut next_index = current_index;
+                for constraint in constraints {
+                    let (worker_constraint, elements_filled) = constraint.to_worker_sdk_recursive(
+                        underlying_data,
+                        current_index + num_constraints_filled,
+                    );
+                    num_constraints_filled += elements_filled;
+
+                    underlying_data[next_index] = worker_constraint;
+                    next_index += 1;
+                }
+
+                let constraint = Worker_Constraint {
+                    constraint_type: Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_OR as u8,
+                    constraint: Worker_Constraint_Union {
+                        or_constraint: Worker_OrConstraint {
+                            constraint_count: constraints.len() as u32,
+                            constraints: &mut underlying_data[current_index]
+                                as *mut Worker_Constraint,
+                        },
+                    },
+                };
+
+                (constraint, num_constraints_filled)
+            }
+            QueryConstraint::Not(constraint) => {
+                let (worker_constraint, elements_filled) =
+                    constraint.to_worker_sdk_recursive(underlying_data, current_index + 1);
+                underlying_data[current_index] = worker_constraint;
+
+                let constraint = Worker_Constraint {
+                    constraint_type: Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_NOT as u8,
+                    constraint: Worker_Constraint_Union {
+                        not_constraint: Worker_NotConstraint {
+                            constraint: &mut underlying_data[current_index]
+                                as *mut Worker_Constraint,
+                        },
+                    },
+                };
+
+                (constraint, 1 + elements_filled)
+            }
+        }
+    }
+
+    fn constraint_len_recursive(&self) -> u32 {
+        match &self {
+            QueryConstraint::EntityId(_)
+            | QueryConstraint::Component(_)
+            | QueryConstraint::Sphere(..)
+            | QueryConstraint::Not(_) => 1,
+            QueryConstraint::And(constraints) | QueryConstraint::Or(constraints) => {
+                constraints
+                    .iter()
+                    .map(QueryConstraint::constraint_len_recursive)
+                    .sum::<u32>()
+                    + 1
+            }
+        }
+    }
+}
+
+impl BitAnd for QueryConstraint {
+    type Output = QueryConstraint;
+
+    fn bitand(self, other: QueryConstraint) -> QueryConstraint {
+        self.and(other)
+    }
+}
+
+impl BitOr for QueryConstraint {
+    type Output = QueryConstraint;
+
+    fn bitor(self, other: QueryConstraint) -> QueryConstraint {
+        self.or(other)
+    }
+}
+
+impl Not for QueryConstraint {
+    type Output = QueryConstraint;
+
+    fn not(self) -> QueryConstraint {
+        QueryConstraint::Not(Box::new(self))
+    }
+}
+
+/// An entity that queries can be evaluated against locally.
+///
+/// This is implemented for [`Entity`] and [`SerializedEntity`], and for the entities of a
+/// [`View`](../view/struct.View.html) through [`View::query`].
+///
+/// [`Entity`]: ../entity/struct.Entity.html
+/// [`SerializedEntity`]: ../snapshot/struct.SerializedEntity.html
+/// [`View::query`]: ../view/struct.View.html#method.query
+pub trait QueryableEntity {
+    /// The type of the entity returned for a snapshot result type.
+    type Projection;
+
+    fn has_component(&self, component_id: ComponentId) -> bool;
+
+    /// Returns the coordinates of the entity's `improbable.Position`, or `None` if it doesn't
+    /// have one.
+    fn position(&self) -> Result<Option<(f64, f64, f64)>, String>;
+
+    /// Returns the entity with only the components in `component_ids`.
+    fn project(&self, component_ids: &[ComponentId]) -> Result<Self::Projection, String>;
+}
+
+impl<E: QueryableEntity + ?Sized> QueryableEntity for &E {
+    type Projection = E::Projection;
+
+    fn has_component(&self, component_id: ComponentId) -> bool {
+        (**self).has_component(component_id)
+    }
+
+    fn position(&self) -> Result<Option<(f64, f64, f64)>, String> {
+        (**self).position()
+    }
+
+    fn project(&self, component_ids: &[ComponentId]) -> Result<Self::Projection, String> {
+        (**self).project(component_ids)
+    }
+}
+
+impl QueryableEntity for Entity {
+    type Projection = Entity;
+
+    fn has_component(&self, component_id: ComponentId) -> bool {
+        Entity::has_component(self, component_id)
+    }
+
+    fn position(&self) -> Result<Option<(f64, f64, f64)>, String> {
+        match self.serialize_component(POSITION_COMPONENT_ID) {
+            Some(data) => decode_position(&data?).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn project(&self, component_ids: &[ComponentId]) -> Result<Entity, String> {
+        Ok(Entity::project(self, component_ids))
+    }
+}
+
+impl QueryableEntity for SerializedEntity {
+    type Projection = SerializedEntity;
+
+    fn has_component(&self, component_id: ComponentId) -> bool {
+        self.components
+            .iter()
+            .any(|component| component.id == component_id)
+    }
+
+    fn position(&self) -> Result<Option<(f64, f64, f64)>, String> {
+        self.components
+            .iter()
+            .find(|component| component.id == POSITION_COMPONENT_ID)
+            .map(|component| decode_position(&component.data))
+            .transpose()
+    }
+
+    fn project(&self, component_ids: &[ComponentId]) -> Result<SerializedEntity, String> {
+        Ok(SerializedEntity {
+            id: self.id,
+            components: self
+                .components
+                .iter()
+                .filter(|component| component_ids.contains(&component.id))
+                .cloned()
+                .collect::<Vec<SerializedComponent>>(),
+        })
+    }
+}
+
+// Reads the coordinates of a serialized `improbable.Position`. Like `EntityBuilder`, this
+// relies on the well known shape of the component rather than on generated code.
+pub(crate) fn decode_position(data: &[u8]) -> Result<(f64, f64, f64), String> {
+    let mut position = SchemaComponentData::new();
+    let result = position.fields_mut().merge_from_buffer(data).map(|_| {
+        let coords = position.fields().field::<SchemaObject>(1).get_or_default();
+        (
+            coords.field::<SchemaDouble>(1).get_or_default(),
+            coords.field::<SchemaDouble>(2).get_or_default(),
+            coords.field::<SchemaDouble>(3).get_or_default(),
+        )
+    });
+    unsafe { Schema_DestroyComponentData(position.internal) };
+    result
+}
+
+// Since we are not doing floating point operations, this should be okay.
+#[allow(clippy::float_cmp)]
+#[cfg(test)]
+mod test {
+    use crate::worker::query::{EntityQuery, LocalQueryResult, Query, QueryConstraint, ResultType};
+    use crate::worker::snapshot::{SerializedComponent, SerializedEntity, SnapshotError};
+    use crate::worker::EntityId;
+    use std::slice::from_raw_parts;
+
+    use spatialos_sdk_sys::worker::{
+        Worker_Constraint, Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_AND,
+        Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_COMPONENT,
+        Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_ENTITY_ID,
+        Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_NOT,
+        Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_OR,
+        Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_SPHERE,
+    };
+
+    fn is_worker_query_valid(query: &EntityQuery) {
+        let worker_query = query.to_worker_sdk();
+
+        assert_eq!(query.result_type.to_u8(), worker_query.query.result_type);
+        if let ResultType::Snapshot(ids) = &query.result_type {
+            assert_eq!(
+                ids.len() as u32,
+                worker_query.query.snapshot_result_type_component_id_count
+            );
+            // TODO: Check the ids.
+        }
+
+        is_constraint_valid(&query.constraint, &worker_query.query.constraint);
+    }
+
+    fn is_constraint_valid(constraint: &QueryConstraint, worker_constraint: &Worker_Constraint) {
+        match constraint {
+            QueryConstraint::EntityId(id) => {
+                assert_eq!(
+                    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_ENTITY_ID as u8,
+                    worker_constraint.constraint_type
+                );
+                unsafe {
+                    let bindgen_constraint = worker_constraint.constraint.entity_id_constraint;
+                    assert_eq!(id.id, bindgen_constraint.entity_id);
+                }
+            }
+            QueryConstraint::Component(component_id) => {
+                assert_eq!(
+                    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_COMPONENT as u8,
+                    worker_constraint.constraint_type
+                );
+                unsafe {
+                    let bindgen_constraint = worker_constraint.constraint.component_constraint;
+                    assert_eq!(*component_id, bindgen_constraint.component_id);
+                }
+            }
+            QueryConstraint::Sphere(x, y, z, r) => {
+                assert_eq!(
+                    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_SPHERE as u8,
+                    worker_constraint.constraint_type
+                );
+                unsafe {
+                    let bindgen_constraint = worker_constraint.constraint.sphere_constraint;
+                    assert_eq!(*x, bindgen_constraint.x);
+                    assert_eq!(*y, bindgen_constraint.y);
+                    assert_eq!(*z, bindgen_constraint.z);
+                    assert_eq!(*r, bindgen_constraint.radius);
+                }
+            }
+            QueryConstraint::And(other_constraints) => {
+                assert_eq!(
+                    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_AND as u8,
+                    worker_constraint.constraint_type
+                );
+                unsafe {
+                    let bindgen_constraint = worker_constraint.constraint.and_constraint;
+                    assert_ne!(::std::ptr::null_mut(), bindgen_constraint.constraints);
+                    assert_eq!(
+                        other_constraints.len() as u32,
+                        bindgen_constraint.constraint_count
+                    );
+                    let data = from_raw_parts(
+                        bindgen_constraint.constraints,
+                        bindgen_constraint.constraint_count as usize,
+                    );
+                    for i in 0..other_constraints.len() {
+                        is_constraint_valid(&other_constraints[i], &data[i]);
+                    }
+                }
+            }
+            QueryConstraint::Or(other_constraints) => {
+                assert_eq!(
+                    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_OR as u8,
+                    worker_constraint.constraint_type
+                );
+                unsafe {
+                    let bindgen_constraint = worker_constraint.constraint.or_constraint;
+                    assert_ne!(::std::ptr::null_mut(), bindgen_constraint.constraints);
+                    assert_eq!(
+                        other_constraints.len() as u32,
+                        bindgen_constraint.constraint_count
+                    );
+                    let data = from_raw_parts(
+                        bindgen_constraint.constraints,
+                        bindgen_constraint.constraint_count as usize,
+                    );
+                    for i in 0..other_constraints.len() {
+                        is_constraint_valid(&other_constraints[i], &data[i]);
+                    }
+                }
+            }
+            QueryConstraint::Not(other_constraint) => {
+                assert_eq!(
+                    Worker_ConstraintType_WORKER_CONSTRAINT_TYPE_NOT as u8,
+                    worker_constraint.constraint_type
+                );
+                unsafe {
+                    let bindgen_constraint = worker_constraint.constraint.not_constraint;
+                    assert_ne!(::std::ptr::null_mut(), bindgen_constraint.constraint);
+
+                    is_constraint_valid(
+                        other_constraint,
+                        bindgen_constraint.constraint.as_ref().unwrap(),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn query_result_types_conversion() {
+        let query = EntityQuery {
+            constraint: QueryConstraint::Component(1),
+            result_type: ResultType::Count,
+        };
+
+        is_worker_query_valid(&query);
+
+        let query = EntityQuery {
+            constraint: QueryConstraint::Component(1),
+            result_type: ResultType::Snapshot(vec![0, 1, 2]),
+        };
+
+        is_worker_query_valid(&query);
+    }
+
+    #[test]
+    fn exhaustive_simple_query_conversion() {
+        let query = EntityQuery {
+            constraint: QueryConstraint::Component(1),
+            result_type: ResultType::Count,
+        };
+
+        is_worker_query_valid(&query);
+
+        let query = EntityQuery {
+            constraint: QueryConstraint::EntityId(EntityId::new(10)),
+            result_type: ResultType::Count,
+        };
+
+        is_worker_query_valid(&query);
+
+        let query = EntityQuery {
+            constraint: QueryConstraint::Sphere(1.0, 1.0, 1.0, 1.0),
+            result_type: ResultType::Count,
+        };
+
+        is_worker_query_valid(&query);
+    }
+
+    #[test]
+    fn exhaustive_simple_nested_query_conversion() {
+        let c1 = QueryConstraint::Component(1);
+        let c2 = QueryConstraint::EntityId(EntityId::new(10));
+        let constraints = vec![c1, c2];
+
+        let query = EntityQuery {
+            constraint: QueryConstraint::And(constraints.clone()),
+            result_type: ResultType::Count,
+        };
+
+        is_worker_query_valid(&query);
+
+        let query = EntityQuery {
+            constraint: QueryConstraint::Or(constraints),
+            result_type: ResultType::Count,
+        };
+
+        is_worker_query_valid(&query);
+
+        let query = EntityQuery {
+            constraint: QueryConstraint::Not(Box::new(QueryConstraint::EntityId(EntityId::new(
+                10,
+            )))),
+            result_type: ResultType::Count,
+        };
+
+        is_worker_query_valid(&query);
+    }
+
+    #[test]
+    fn complex_nested_query_conversion() {
+        let c1 = QueryConstraint::Component(1);
+        let c2 = QueryConstraint::EntityId(EntityId::new(10));
+        let constraints = vec![c1, c2];
+
+        let and = QueryConstraint::And(constraints.clone());
+        let or = QueryConstraint::Or(vec![and, QueryConstraint::Component(5)]);
+
+        let and = QueryConstraint::And(vec![
+            or,
+            QueryConstraint::Not(Box::new(QueryConstraint::Component(15))),
+        ]);
+
+        let query = EntityQuery {
+            constraint: and,
+            result_type: ResultType::Count,
+        };
+
+        is_worker_query_valid(&query);
+    }
+
+    #[test]
+    fn builder_flattens_chained_constraints() {
+        let constraint = Query::has_component(1)
+            .and(Query::has_component(2))
+            .and(Query::has_component(3).and(Query::has_component(4)))
+            .or(Query::entity(EntityId::new(5)))
+            .or(Query::has_component(6).not());
+
+        assert_eq!(
+            QueryConstraint::Or(vec![
+                QueryConstraint::And(vec![
+                    QueryConstraint::Component(1),
+                    QueryConstraint::Component(2),
+                    QueryConstraint::Component(3),
+                    QueryConstraint::Component(4),
+                ]),
+                QueryConstraint::EntityId(EntityId::new(5)),
+                QueryConstraint::Not(Box::new(QueryConstraint::Component(6))),
+            ]),
+            constraint
+        );
+    }
+
+    #[test]
+    fn operators_match_builder_methods() {
+        let a = || Query::has_component(1);
+        let b = || Query::within_sphere((1.0, 2.0, 3.0), 4.0);
+        let c = || Query::entity(EntityId::new(10));
+
+        assert_eq!(a().and(b()).or(c().not()), a() & b() | !c());
+        assert_eq!(a().within_sphere((1.0, 2.0, 3.0), 4.0), a() & b());
+        assert_eq!(Query::all(vec![a(), b()]), a() & b());
+        assert_eq!(Query::any(vec![a(), c()]), a() | c());
+
+        is_worker_query_valid(&EntityQuery::new(a() & b() | !c(), ResultType::Count));
+    }
+
+    fn entity(id: i64, components: &[u32]) -> SerializedEntity {
+        SerializedEntity {
+            id: EntityId::new(id),
+            components: components
+                .iter()
+                .map(|&id| SerializedComponent {
+                    id,
+                    data: vec![id as u8],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn constraints_are_matched_locally() {
+        let entity = entity(3, &[1, 2]);
+        let matches = |constraint: QueryConstraint| constraint.matches(entity.id, &entity).unwrap();
+
+        assert!(matches(Query::entity(EntityId::new(3))));
+        assert!(!matches(Query::entity(EntityId::new(4))));
+        assert!(matches(Query::has_component(1) & Query::has_component(2)));
+        assert!(!matches(Query::has_component(1) & Query::has_component(5)));
+        assert!(matches(Query::has_component(5) | Query::has_component(2)));
+        assert!(matches(!Query::has_component(5)));
+        assert!(matches(Query::all(vec![])));
+        assert!(!matches(Query::any(vec![])));
+
+        // Entities without a position never match a sphere.
+        assert!(!matches(Query::within_sphere((0.0, 0.0, 0.0), 1000.0)));
+    }
+
+    #[test]
+    fn local_evaluation_counts_or_projects_matching_entities() {
+        let entities = vec![entity(1, &[1, 2, 3]), entity(2, &[2]), entity(3, &[1, 3])];
+        let constraint = Query::has_component(1);
+
+        assert_eq!(
+            LocalQueryResult::Count(2),
+            EntityQuery::new(constraint.clone(), ResultType::Count)
+                .evaluate(entities.iter().map(|entity| (entity.id, entity)))
+                .unwrap()
+        );
+        assert_eq!(
+            LocalQueryResult::Snapshot(vec![
+                (EntityId::new(1), entity(1, &[1, 3])),
+                (EntityId::new(3), entity(3, &[1, 3])),
+            ]),
+            EntityQuery::new(constraint, ResultType::Snapshot(vec![1, 3]))
+                .evaluate_serialized(entities.into_iter().map(Ok))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn local_evaluation_stops_at_read_errors() {
+        let entities = vec![
+            Ok(entity(1, &[])),
+            Err(SnapshotError::InvalidData("Corrupt".to_owned())),
+        ];
+        match EntityQuery::new(Query::has_component(1), ResultType::Count)
+            .evaluate_serialized(entities)
+        {
+            Err(SnapshotError::InvalidData(_)) => {}
+            other => panic!("Expected InvalidData, got {:?}", other),
+        }
+    }
+}
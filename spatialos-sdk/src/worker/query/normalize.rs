@@ -0,0 +1,161 @@
+use crate::worker::query::QueryConstraint;
+
+impl QueryConstraint {
+    /// Returns an equivalent constraint with redundant terms removed.
+    ///
+    /// Nested `And`s and `Or`s are flattened, duplicate terms and double negations are removed,
+    /// and single-term `And`s and `Or`s are replaced by their term. Constants are folded, with
+    /// `And([])` matching every entity and `Or([])` matching none, so e.g. an `And` containing
+    /// both a term and its negation becomes `Or([])`.
+    ///
+    /// A constraint that normalizes to a constant is almost certainly a mistake, and is
+    /// rejected by [`validate`](#method.validate).
+    pub fn normalize(self) -> QueryConstraint {
+        match self {
+            QueryConstraint::And(constraints) => normalize_junction(constraints, true),
+            QueryConstraint::Or(constraints) => normalize_junction(constraints, false),
+            QueryConstraint::Not(constraint) => match constraint.normalize() {
+                QueryConstraint::Not(constraint) => *constraint,
+                QueryConstraint::And(constraints) if constraints.is_empty() => {
+                    QueryConstraint::Or(Vec::new())
+                }
+                QueryConstraint::Or(constraints) if constraints.is_empty() => {
+                    QueryConstraint::And(Vec::new())
+                }
+                constraint => QueryConstraint::Not(Box::new(constraint)),
+            },
+            constraint => constraint,
+        }
+    }
+}
+
+// Normalizes the terms of an `And` if `is_and` is set, or of an `Or` otherwise.
+fn normalize_junction(constraints: Vec<QueryConstraint>, is_and: bool) -> QueryConstraint {
+    // The constant that makes the whole junction constant: false for `And`, true for `Or`.
+    let absorbing = || junction_of(!is_and, Vec::new());
+
+    let mut terms: Vec<QueryConstraint> = Vec::new();
+    for constraint in constraints {
+        let nested = match constraint.normalize() {
+            QueryConstraint::And(nested) if is_and => nested,
+            QueryConstraint::Or(nested) if !is_and => nested,
+            QueryConstraint::And(ref nested) | QueryConstraint::Or(ref nested)
+                if nested.is_empty() =>
+            {
+                return absorbing();
+            }
+            constraint => vec![constraint],
+        };
+
+        for term in nested {
+            if !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+    }
+
+    let has_complement = terms.iter().any(|term| match term {
+        QueryConstraint::Not(negated) => terms.contains(negated),
+        _ => false,
+    });
+    if has_complement {
+        return absorbing();
+    }
+
+    if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        junction_of(is_and, terms)
+    }
+}
+
+fn junction_of(is_and: bool, terms: Vec<QueryConstraint>) -> QueryConstraint {
+    if is_and {
+        QueryConstraint::And(terms)
+    } else {
+        QueryConstraint::Or(terms)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::worker::query::{Query, QueryConstraint};
+    use crate::worker::EntityId;
+
+    fn a() -> QueryConstraint {
+        Query::has_component(1)
+    }
+
+    fn b() -> QueryConstraint {
+        Query::entity(EntityId::new(2))
+    }
+
+    fn c() -> QueryConstraint {
+        Query::within_sphere((0.0, 0.0, 0.0), 10.0)
+    }
+
+    fn always() -> QueryConstraint {
+        QueryConstraint::And(Vec::new())
+    }
+
+    fn never() -> QueryConstraint {
+        QueryConstraint::Or(Vec::new())
+    }
+
+    #[test]
+    fn nested_junctions_are_flattened_and_deduplicated() {
+        assert_eq!(
+            QueryConstraint::And(vec![a(), b(), c()]),
+            QueryConstraint::And(vec![
+                QueryConstraint::And(vec![a(), b()]),
+                QueryConstraint::And(vec![b(), QueryConstraint::And(vec![c()])]),
+            ])
+            .normalize()
+        );
+        assert_eq!(
+            QueryConstraint::Or(vec![a(), QueryConstraint::And(vec![b(), c()])]),
+            QueryConstraint::Or(vec![
+                QueryConstraint::Or(vec![a()]),
+                QueryConstraint::And(vec![b(), c(), c()]),
+                a(),
+            ])
+            .normalize()
+        );
+        assert_eq!(
+            a(),
+            QueryConstraint::Or(vec![QueryConstraint::And(vec![a()])]).normalize()
+        );
+    }
+
+    #[test]
+    fn double_negations_are_removed() {
+        assert_eq!(a(), (!!a()).normalize());
+        assert_eq!(!a(), (!!!a()).normalize());
+        assert_eq!(
+            !(a() & b()),
+            (!QueryConstraint::And(vec![!!a(), b()])).normalize()
+        );
+    }
+
+    #[test]
+    fn constants_are_folded() {
+        assert_eq!(a(), QueryConstraint::And(vec![a(), always()]).normalize());
+        assert_eq!(a(), QueryConstraint::Or(vec![a(), never()]).normalize());
+        assert_eq!(
+            never(),
+            QueryConstraint::And(vec![a(), never()]).normalize()
+        );
+        assert_eq!(
+            always(),
+            QueryConstraint::Or(vec![a(), always()]).normalize()
+        );
+        assert_eq!(never(), (!always()).normalize());
+        assert_eq!(always(), (!never()).normalize());
+        assert_eq!(never(), (a() & b() & !a()).normalize());
+        assert_eq!(always(), (b() | !!!b()).normalize());
+        assert_eq!(
+            b(),
+            (QueryConstraint::Or(vec![a() & !a()]) | b()).normalize()
+        );
+    }
+}
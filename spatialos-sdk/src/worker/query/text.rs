@@ -0,0 +1,677 @@
+//! A compact textual syntax for entity queries, e.g. for config files or a debug console.
+//!
+//! Constraints are written as:
+//!
+//! ```text
+//! constraint := constraint "or" constraint
+//!             | constraint "and" constraint
+//!             | "not" constraint
+//!             | "(" constraint ")"
+//!             | "entity(" entity_id ")"
+//!             | "component(" component_id | component_name ")"
+//!             | "sphere(" x "," y "," z "," radius ")"
+//!             | "all(" [constraint ("," constraint)*] ")"
+//!             | "any(" [constraint ("," constraint)*] ")"
+//! ```
+//!
+//! `not` binds tightest and `or` loosest, so `component(1) and not entity(2) or entity(3)`
+//! is `(component(1) and (not entity(2))) or entity(3)`. Queries add a result type in front
+//! of the constraint:
+//!
+//! ```text
+//! query := ("count" | "snapshot(" [component ("," component)*] ")") "where" constraint
+//! ```
+//!
+//! Displaying a constraint or query produces text that parses back to the same value.
+//! Component names like `improbable.Position` are resolved through a schema bundle with
+//! [`QueryConstraint::parse_with_definitions`], and displayed with
+//! [`QueryConstraint::display_with`].
+//!
+//! [`QueryConstraint::parse_with_definitions`]: ../enum.QueryConstraint.html#method.parse_with_definitions
+//! [`QueryConstraint::display_with`]: ../enum.QueryConstraint.html#method.display_with
+
+use crate::worker::{
+    component::ComponentId,
+    query::{EntityQuery, QueryConstraint, ResultType},
+    schema_value::SchemaDefinitions,
+    EntityId,
+};
+use std::{error::Error, fmt, str::FromStr};
+
+/// An error parsing a query, along with the byte offset in the text where it occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseQueryError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl Error for ParseQueryError {}
+
+impl QueryConstraint {
+    /// Parses a constraint, resolving component names through `definitions`.
+    pub fn parse_with_definitions(
+        text: &str,
+        definitions: &SchemaDefinitions,
+    ) -> Result<QueryConstraint, ParseQueryError> {
+        Parser::new(text, Some(definitions)).parse_all(Parser::constraint)
+    }
+
+    /// Returns a value that displays the constraint with component names from `definitions`
+    /// in place of the IDs of the components they define.
+    pub fn display_with<'a>(
+        &'a self,
+        definitions: &'a SchemaDefinitions,
+    ) -> impl fmt::Display + 'a {
+        Named {
+            value: self,
+            definitions,
+        }
+    }
+}
+
+impl EntityQuery {
+    /// Parses a query, resolving component names through `definitions`.
+    pub fn parse_with_definitions(
+        text: &str,
+        definitions: &SchemaDefinitions,
+    ) -> Result<EntityQuery, ParseQueryError> {
+        Parser::new(text, Some(definitions)).parse_all(Parser::query)
+    }
+
+    /// Returns a value that displays the query with component names from `definitions` in
+    /// place of the IDs of the components they define.
+    pub fn display_with<'a>(
+        &'a self,
+        definitions: &'a SchemaDefinitions,
+    ) -> impl fmt::Display + 'a {
+        Named {
+            value: self,
+            definitions,
+        }
+    }
+}
+
+/// Parses a constraint in which components are given by ID.
+impl FromStr for QueryConstraint {
+    type Err = ParseQueryError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Parser::new(text, None).parse_all(Parser::constraint)
+    }
+}
+
+/// Parses a query in which components are given by ID.
+impl FromStr for EntityQuery {
+    type Err = ParseQueryError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Parser::new(text, None).parse_all(Parser::query)
+    }
+}
+
+impl fmt::Display for QueryConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_constraint(f, self, None)
+    }
+}
+
+impl fmt::Display for ResultType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_result_type(f, self, None)
+    }
+}
+
+impl fmt::Display for EntityQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_query(f, self, None)
+    }
+}
+
+struct Named<'a, T> {
+    value: &'a T,
+    definitions: &'a SchemaDefinitions,
+}
+
+impl<'a> fmt::Display for Named<'a, QueryConstraint> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_constraint(f, self.value, Some(self.definitions))
+    }
+}
+
+impl<'a> fmt::Display for Named<'a, EntityQuery> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_query(f, self.value, Some(self.definitions))
+    }
+}
+
+fn write_query(
+    f: &mut fmt::Formatter<'_>,
+    query: &EntityQuery,
+    definitions: Option<&SchemaDefinitions>,
+) -> fmt::Result {
+    write_result_type(f, &query.result_type, definitions)?;
+    f.write_str(" where ")?;
+    write_constraint(f, &query.constraint, definitions)
+}
+
+fn write_result_type(
+    f: &mut fmt::Formatter<'_>,
+    result_type: &ResultType,
+    definitions: Option<&SchemaDefinitions>,
+) -> fmt::Result {
+    match result_type {
+        ResultType::Count => f.write_str("count"),
+        ResultType::Snapshot(component_ids) => {
+            f.write_str("snapshot(")?;
+            for (index, &component_id) in component_ids.iter().enumerate() {
+                if index > 0 {
+                    f.write_str(", ")?;
+                }
+                write_component(f, component_id, definitions)?;
+            }
+            f.write_str(")")
+        }
+    }
+}
+
+fn write_component(
+    f: &mut fmt::Formatter<'_>,
+    component_id: ComponentId,
+    definitions: Option<&SchemaDefinitions>,
+) -> fmt::Result {
+    match definitions.and_then(|definitions| definitions.component(component_id)) {
+        Some(definition) => f.write_str(&definition.qualified_name),
+        None => write!(f, "{}", component_id),
+    }
+}
+
+// `And` and `Or` constraints with at least two children are written with infix operators, and
+// with `all(...)` and `any(...)` otherwise, so that every constraint parses back unchanged.
+fn is_infix(constraint: &QueryConstraint) -> bool {
+    match constraint {
+        QueryConstraint::And(constraints) | QueryConstraint::Or(constraints) => {
+            constraints.len() > 1
+        }
+        _ => false,
+    }
+}
+
+fn write_constraint(
+    f: &mut fmt::Formatter<'_>,
+    constraint: &QueryConstraint,
+    definitions: Option<&SchemaDefinitions>,
+) -> fmt::Result {
+    let write_operand = |f: &mut fmt::Formatter<'_>, operand, parenthesize| {
+        if parenthesize {
+            f.write_str("(")?;
+            write_constraint(f, operand, definitions)?;
+            f.write_str(")")
+        } else {
+            write_constraint(f, operand, definitions)
+        }
+    };
+    let write_list = |f: &mut fmt::Formatter<'_>, name, constraints: &[QueryConstraint]| {
+        write!(f, "{}(", name)?;
+        for (index, operand) in constraints.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write_constraint(f, operand, definitions)?;
+        }
+        f.write_str(")")
+    };
+
+    match constraint {
+        QueryConstraint::EntityId(entity_id) => write!(f, "entity({})", entity_id.id),
+        QueryConstraint::Component(component_id) => {
+            f.write_str("component(")?;
+            write_component(f, *component_id, definitions)?;
+            f.write_str(")")
+        }
+        QueryConstraint::Sphere(x, y, z, radius) => {
+            write!(f, "sphere({}, {}, {}, {})", x, y, z, radius)
+        }
+        QueryConstraint::And(constraints) if constraints.len() > 1 => {
+            for (index, operand) in constraints.iter().enumerate() {
+                if index > 0 {
+                    f.write_str(" and ")?;
+                }
+                // Nested `and`s are parenthesized too, so they aren't merged when parsed.
+                write_operand(f, operand, is_infix(operand))?;
+            }
+            Ok(())
+        }
+        QueryConstraint::Or(constraints) if constraints.len() > 1 => {
+            for (index, operand) in constraints.iter().enumerate() {
+                if index > 0 {
+                    f.write_str(" or ")?;
+                }
+                let parenthesize = is_infix(operand) && matches!(operand, QueryConstraint::Or(_));
+                write_operand(f, operand, parenthesize)?;
+            }
+            Ok(())
+        }
+        QueryConstraint::And(constraints) => write_list(f, "all", constraints),
+        QueryConstraint::Or(constraints) => write_list(f, "any", constraints),
+        QueryConstraint::Not(operand) => {
+            f.write_str("not ")?;
+            write_operand(f, operand, is_infix(operand))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    OpenParen,
+    CloseParen,
+    Comma,
+    Word(&'a str),
+}
+
+impl<'a> fmt::Display for Token<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::OpenParen => f.write_str("'('"),
+            Token::CloseParen => f.write_str("')'"),
+            Token::Comma => f.write_str("','"),
+            Token::Word(word) => write!(f, "'{}'", word),
+        }
+    }
+}
+
+// Splits the text into parentheses, commas and words, where a word is anything else between
+// whitespace. Numbers and qualified component names are both single words.
+fn tokenize(text: &str) -> Vec<(usize, Token<'_>)> {
+    let is_word_char = |c: char| !c.is_whitespace() && !"(),".contains(c);
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            ',' => Token::Comma,
+            c if c.is_whitespace() => continue,
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(index, c)) = chars.peek() {
+                    if !is_word_char(c) {
+                        break;
+                    }
+                    end = index + c.len_utf8();
+                    chars.next();
+                }
+                Token::Word(&text[start..end])
+            }
+        };
+        tokens.push((start, token));
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    next: usize,
+    end: usize,
+    definitions: Option<&'a SchemaDefinitions>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str, definitions: Option<&'a SchemaDefinitions>) -> Self {
+        Parser {
+            tokens: tokenize(text),
+            next: 0,
+            end: text.len(),
+            definitions,
+        }
+    }
+
+    // Parses the whole text with `parse`, failing if anything is left over.
+    fn parse_all<T>(
+        mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseQueryError>,
+    ) -> Result<T, ParseQueryError> {
+        let value = parse(&mut self)?;
+        match self.tokens.get(self.next) {
+            Some((position, token)) => Err(ParseQueryError {
+                position: *position,
+                message: format!("Unexpected {}", token),
+            }),
+            None => Ok(value),
+        }
+    }
+
+    fn query(&mut self) -> Result<EntityQuery, ParseQueryError> {
+        let result_type = match self.word()? {
+            (_, "count") => ResultType::Count,
+            (_, "snapshot") => {
+                self.expect(Token::OpenParen)?;
+                let component_ids = self.list(Parser::component)?;
+                ResultType::Snapshot(component_ids)
+            }
+            (position, word) => {
+                return Err(ParseQueryError {
+                    position,
+                    message: format!("Expected 'count' or 'snapshot', found '{}'", word),
+                })
+            }
+        };
+        self.keyword("where")?;
+        let constraint = self.constraint()?;
+        Ok(EntityQuery::new(constraint, result_type))
+    }
+
+    fn constraint(&mut self) -> Result<QueryConstraint, ParseQueryError> {
+        self.infix("or", Parser::and, QueryConstraint::Or)
+    }
+
+    fn and(&mut self) -> Result<QueryConstraint, ParseQueryError> {
+        self.infix("and", Parser::unary, QueryConstraint::And)
+    }
+
+    // Parses operands separated by `operator`, combining two or more of them with `combine`.
+    fn infix(
+        &mut self,
+        operator: &str,
+        operand: fn(&mut Self) -> Result<QueryConstraint, ParseQueryError>,
+        combine: fn(Vec<QueryConstraint>) -> QueryConstraint,
+    ) -> Result<QueryConstraint, ParseQueryError> {
+        let mut operands = vec![operand(self)?];
+        while self.peek() == Some(&Token::Word(operator)) {
+            self.next += 1;
+            operands.push(operand(self)?);
+        }
+
+        if operands.len() == 1 {
+            Ok(operands.remove(0))
+        } else {
+            Ok(combine(operands))
+        }
+    }
+
+    fn unary(&mut self) -> Result<QueryConstraint, ParseQueryError> {
+        if self.peek() == Some(&Token::OpenParen) {
+            self.next += 1;
+            let constraint = self.constraint()?;
+            self.expect(Token::CloseParen)?;
+            return Ok(constraint);
+        }
+
+        let (position, word) = self.word()?;
+        if word == "not" {
+            return Ok(QueryConstraint::Not(Box::new(self.unary()?)));
+        }
+
+        self.expect(Token::OpenParen)?;
+        match word {
+            "entity" => {
+                let entity_id = self.number("an entity ID")?;
+                self.expect(Token::CloseParen)?;
+                Ok(QueryConstraint::EntityId(EntityId::new(entity_id)))
+            }
+            "component" => {
+                let component_id = self.component()?;
+                self.expect(Token::CloseParen)?;
+                Ok(QueryConstraint::Component(component_id))
+            }
+            "sphere" => {
+                let mut values = [0.0; 4];
+                for (index, value) in values.iter_mut().enumerate() {
+                    if index > 0 {
+                        self.expect(Token::Comma)?;
+                    }
+                    *value = self.number("a number")?;
+                }
+                self.expect(Token::CloseParen)?;
+                let [x, y, z, radius] = values;
+                Ok(QueryConstraint::Sphere(x, y, z, radius))
+            }
+            "all" => Ok(QueryConstraint::And(self.list(Parser::constraint)?)),
+            "any" => Ok(QueryConstraint::Or(self.list(Parser::constraint)?)),
+            _ => Err(ParseQueryError {
+                position,
+                message: format!("Unknown constraint '{}'", word),
+            }),
+        }
+    }
+
+    // Parses a component ID, or a component name if there are schema definitions.
+    fn component(&mut self) -> Result<ComponentId, ParseQueryError> {
+        let (position, word) = self.word()?;
+        if let Ok(component_id) = word.parse() {
+            return Ok(component_id);
+        }
+
+        match self.definitions {
+            Some(definitions) => definitions
+                .component_by_name(word)
+                .map(|definition| definition.component_id)
+                .ok_or_else(|| ParseQueryError {
+                    position,
+                    message: format!("Unknown component '{}'", word),
+                }),
+            None => Err(ParseQueryError {
+                position,
+                message: format!(
+                    "Expected a component ID, found '{}'. Component names can only be used \
+                     when parsing with schema definitions",
+                    word
+                ),
+            }),
+        }
+    }
+
+    // Parses a comma-separated list of items after an opening parenthesis, up to and including
+    // the closing parenthesis.
+    fn list<T>(
+        &mut self,
+        item: fn(&mut Self) -> Result<T, ParseQueryError>,
+    ) -> Result<Vec<T>, ParseQueryError> {
+        let mut items = Vec::new();
+        if self.peek() == Some(&Token::CloseParen) {
+            self.next += 1;
+            return Ok(items);
+        }
+
+        loop {
+            items.push(item(self)?);
+            if self.peek() == Some(&Token::Comma) {
+                self.next += 1;
+            } else {
+                self.expect(Token::CloseParen)?;
+                return Ok(items);
+            }
+        }
+    }
+
+    fn number<T: FromStr>(&mut self, expected: &str) -> Result<T, ParseQueryError> {
+        let (position, word) = self.word()?;
+        word.parse().map_err(|_| ParseQueryError {
+            position,
+            message: format!("Expected {}, found '{}'", expected, word),
+        })
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), ParseQueryError> {
+        match self.word()? {
+            (_, word) if word == keyword => Ok(()),
+            (position, word) => Err(ParseQueryError {
+                position,
+                message: format!("Expected '{}', found '{}'", keyword, word),
+            }),
+        }
+    }
+
+    fn word(&mut self) -> Result<(usize, &'a str), ParseQueryError> {
+        match self.advance()? {
+            (position, Token::Word(word)) => Ok((position, word)),
+            (position, token) => Err(ParseQueryError {
+                position,
+                message: format!("Unexpected {}", token),
+            }),
+        }
+    }
+
+    fn expect(&mut self, expected: Token<'_>) -> Result<(), ParseQueryError> {
+        match self.advance()? {
+            (_, token) if token == expected => Ok(()),
+            (position, token) => Err(ParseQueryError {
+                position,
+                message: format!("Expected {}, found {}", expected, token),
+            }),
+        }
+    }
+
+    fn advance(&mut self) -> Result<(usize, Token<'a>), ParseQueryError> {
+        let token = self.tokens.get(self.next).cloned().ok_or(ParseQueryError {
+            position: self.end,
+            message: "Unexpected end of query".to_owned(),
+        })?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::worker::query::Query;
+
+    fn definitions() -> SchemaDefinitions {
+        SchemaDefinitions::from_json(include_str!(
+            "../../../../spatialos-sdk-code-generator/data/test.sb.json"
+        ))
+        .unwrap()
+    }
+
+    fn round_trip(constraint: QueryConstraint) {
+        let text = constraint.to_string();
+        assert_eq!(Ok(constraint), text.parse(), "{}", text);
+    }
+
+    #[test]
+    fn constraints_are_displayed_compactly() {
+        let constraint = Query::has_component(1001)
+            & Query::within_sphere((0.0, -1.5, 0.0), 50.0)
+            & !Query::entity(EntityId::new(10));
+        assert_eq!(
+            "component(1001) and sphere(0, -1.5, 0, 50) and not entity(10)",
+            constraint.to_string()
+        );
+        assert_eq!(
+            Ok(constraint),
+            "component(1001)and sphere(0,-1.5,0,50) and not entity(10)".parse()
+        );
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        let a = || Query::has_component(1);
+        let b = || Query::entity(EntityId::new(2));
+        let c = || Query::entity(EntityId::new(3));
+
+        assert_eq!(
+            Ok(a() & !b() | c()),
+            "component(1) and not entity(2) or entity(3)".parse()
+        );
+        assert_eq!(
+            Ok(a() & (b() | c())),
+            "component(1) and (entity(2) or entity(3))".parse()
+        );
+        assert_eq!(Ok(!(a() & b())), "not (component(1) and entity(2))".parse());
+    }
+
+    #[test]
+    fn every_constraint_round_trips() {
+        let a = || Query::has_component(1);
+        let b = || Query::entity(EntityId::new(-2));
+        let c = || Query::within_sphere((0.1, 1e20, -0.0), 2.5);
+
+        round_trip(a() & !b() | c());
+        round_trip(a() & (b() | c()));
+        round_trip((a() | b()) | c());
+        round_trip(QueryConstraint::And(vec![a() & b(), c()]));
+        round_trip(QueryConstraint::Or(vec![a() & b(), c()]));
+        round_trip(!!(a() | c()));
+        round_trip(QueryConstraint::And(vec![]));
+        round_trip(QueryConstraint::Or(vec![a()]));
+        round_trip(!QueryConstraint::And(vec![b()]) & QueryConstraint::Or(vec![]));
+    }
+
+    #[test]
+    fn queries_round_trip_with_their_result_type() {
+        for text in &[
+            "count where component(1001)",
+            "snapshot() where entity(1) or entity(2)",
+            "snapshot(54, 1001) where not component(53)",
+        ] {
+            let query: EntityQuery = text.parse().unwrap();
+            assert_eq!(*text, query.to_string());
+        }
+    }
+
+    #[test]
+    fn component_names_are_resolved_through_definitions() {
+        let definitions = definitions();
+        let query = EntityQuery::parse_with_definitions(
+            "snapshot(example.Rotate) where component(example.Example) and not component(5)",
+            &definitions,
+        )
+        .unwrap();
+
+        assert_eq!(ResultType::Snapshot(vec![1001]), query.result_type);
+        assert_eq!(
+            Query::has_component(1000) & !Query::has_component(5),
+            query.constraint
+        );
+        assert_eq!(
+            "snapshot(example.Rotate) where component(example.Example) and not component(5)",
+            query.display_with(&definitions).to_string()
+        );
+        assert_eq!(
+            "component(example.Rotate)",
+            Query::has_component(1001)
+                .display_with(&definitions)
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn errors_report_their_position() {
+        let error = |text: &str| text.parse::<QueryConstraint>().unwrap_err();
+
+        assert_eq!(
+            ParseQueryError {
+                position: 16,
+                message: "Unknown constraint 'entiy'".to_owned(),
+            },
+            error("component(1) or entiy(3)")
+        );
+        assert_eq!(14, error("sphere(1, 2, 3) and component(1)").position);
+        assert_eq!(
+            "Unexpected end of query at position 12",
+            error("not entity(1").to_string()
+        );
+        assert_eq!(13, error("entity(1) and").position);
+        assert_eq!(10, error("entity(1) entity(2)").position);
+        assert!(error("component(example.Rotate)")
+            .message
+            .contains("schema definitions"));
+        assert!(QueryConstraint::parse_with_definitions(
+            "component(example.Missing)",
+            &definitions()
+        )
+        .unwrap_err()
+        .message
+        .contains("Unknown component"));
+    }
+}
@@ -0,0 +1,139 @@
+use crate::worker::{query::QueryConstraint, EntityId};
+use std::{error::Error, fmt};
+
+/// A constraint that can't be sent to the runtime, as found by
+/// [`QueryConstraint::validate`](enum.QueryConstraint.html#method.validate).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintError {
+    /// An `And` has no terms. The runtime doesn't reliably treat this as matching every
+    /// entity, so `Query::all` should only be used with at least one constraint.
+    EmptyAnd,
+
+    /// An `Or` has no terms. Rather than matching no entities, the runtime may match every
+    /// entity.
+    EmptyOr,
+
+    /// An `EntityId` constraint has an ID that isn't positive, so matches no entity.
+    InvalidEntityId(EntityId),
+
+    /// A `Sphere` constraint has a radius that is negative or not a number.
+    InvalidRadius(f64),
+
+    /// A `Sphere` constraint has a center with a coordinate that is infinite or not a number.
+    InvalidCenter(f64, f64, f64),
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstraintError::EmptyAnd => write!(f, "`And` constraint has no terms"),
+            ConstraintError::EmptyOr => write!(f, "`Or` constraint has no terms"),
+            ConstraintError::InvalidEntityId(entity_id) => {
+                write!(f, "Entity ID {} is not positive", entity_id.id)
+            }
+            ConstraintError::InvalidRadius(radius) => {
+                write!(f, "Sphere radius {} is not a non-negative number", radius)
+            }
+            ConstraintError::InvalidCenter(x, y, z) => {
+                write!(f, "Sphere center ({}, {}, {}) is not finite", x, y, z)
+            }
+        }
+    }
+}
+
+impl Error for ConstraintError {}
+
+impl QueryConstraint {
+    /// Checks that the constraint is meaningful to the runtime, returning the first problem
+    /// found in a depth-first walk of the constraint.
+    ///
+    /// Problems that can be fixed without changing which entities match, like double
+    /// negations, aren't reported; use [`normalize`](#method.normalize) to remove them.
+    pub fn validate(&self) -> Result<(), ConstraintError> {
+        match self {
+            QueryConstraint::EntityId(entity_id) => {
+                if !entity_id.is_valid() {
+                    return Err(ConstraintError::InvalidEntityId(*entity_id));
+                }
+            }
+            QueryConstraint::Component(_) => {}
+            QueryConstraint::Sphere(x, y, z, radius) => {
+                if !(x.is_finite() && y.is_finite() && z.is_finite()) {
+                    return Err(ConstraintError::InvalidCenter(*x, *y, *z));
+                }
+                if radius.is_nan() || *radius < 0.0 {
+                    return Err(ConstraintError::InvalidRadius(*radius));
+                }
+            }
+            QueryConstraint::And(constraints) if constraints.is_empty() => {
+                return Err(ConstraintError::EmptyAnd);
+            }
+            QueryConstraint::Or(constraints) if constraints.is_empty() => {
+                return Err(ConstraintError::EmptyOr);
+            }
+            QueryConstraint::And(constraints) | QueryConstraint::Or(constraints) => {
+                for constraint in constraints {
+                    constraint.validate()?;
+                }
+            }
+            QueryConstraint::Not(constraint) => constraint.validate()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::worker::query::Query;
+
+    #[test]
+    fn meaningful_constraints_are_valid() {
+        let constraint = Query::has_component(1)
+            & Query::within_sphere((-1.0, 0.0, 1e10), 0.0)
+            & !!Query::entity(EntityId::new(1));
+        assert_eq!(Ok(()), constraint.validate());
+    }
+
+    #[test]
+    fn nonsensical_constraints_are_rejected() {
+        let error = |constraint: QueryConstraint| constraint.validate().unwrap_err();
+
+        assert_eq!(ConstraintError::EmptyOr, error(Query::any(vec![])));
+        assert_eq!(
+            ConstraintError::EmptyAnd,
+            error(!Query::has_component(1) | Query::all(vec![]))
+        );
+        assert_eq!(
+            ConstraintError::InvalidEntityId(EntityId::new(0)),
+            error(Query::has_component(1) & !Query::entity(EntityId::new(0)))
+        );
+        assert_eq!(
+            ConstraintError::InvalidRadius(-5.0),
+            error(Query::within_sphere((0.0, 0.0, 0.0), -5.0))
+        );
+        assert!(
+            match error(Query::within_sphere((0.0, 0.0, 0.0), f64::NAN)) {
+                ConstraintError::InvalidRadius(radius) => radius.is_nan(),
+                _ => false,
+            }
+        );
+        assert_eq!(
+            ConstraintError::InvalidCenter(0.0, f64::INFINITY, 0.0),
+            error(Query::within_sphere((0.0, f64::INFINITY, 0.0), 1.0))
+        );
+    }
+
+    #[test]
+    fn constraints_that_normalize_to_constants_are_rejected() {
+        let a = || Query::has_component(1);
+        assert_eq!(
+            Err(ConstraintError::EmptyOr),
+            (a() & !a()).normalize().validate()
+        );
+        assert_eq!(
+            Err(ConstraintError::EmptyAnd),
+            (a() | !a()).normalize().validate()
+        );
+    }
+}
@@ -0,0 +1,731 @@
+//! Futures that resolve when the response to an outgoing request is received.
+//!
+//! The `send_*_request` methods on [`Connection`] only return a [`RequestId`], and the
+//! matching response op has to be found by hand. [`TrackedConnection`] wraps a connection
+//! and returns a [`ResponseFuture`] for each request instead, which is resolved when the
+//! response op is returned from [`Connection::get_op_list`].
+//!
+//! Response futures don't borrow the connection, and are only resolved while op lists are
+//! being fetched. A response future should therefore be awaited from a different task
+//! than the one fetching op lists, or polled with [`ResponseFuture::try_take`] from the
+//! worker's main loop.
+//!
+//! # Examples
+//!
+//! ```
+//! use spatialos_sdk::worker::{
+//!     connection::Connection,
+//!     mock::{MockConnection, MockOpList},
+//!     op::StatusCode,
+//!     requests::TrackedConnection,
+//!     EntityId,
+//! };
+//!
+//! let mut connection = TrackedConnection::new(MockConnection::new("TestWorker"));
+//! let entity_id = EntityId::new(1);
+//! let mut response = connection.delete_entity(entity_id);
+//!
+//! let request_id = connection.connection().delete_entity_requests[0].request_id;
+//! connection.connection_mut().push_op_list(
+//!     MockOpList::new().with_delete_entity_response(request_id, entity_id, StatusCode::Success(())),
+//! );
+//!
+//! assert_eq!(None, response.try_take());
+//! connection.get_op_list(0);
+//! assert_eq!(Some(Ok(())), response.try_take());
+//! ```
+
+use crate::worker::{
+    commands::*,
+    component::{Command, Component, UpdateParameters},
+    connection::{Connection, ConnectionStatus},
+    entity::Entity,
+    metrics::Metrics,
+    op::*,
+    query::EntityQuery,
+    EntityId, InterestOverride, LogLevel, RequestId,
+};
+use std::{
+    collections::HashMap,
+    ffi::NulError,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// The response to a request sent through a [`TrackedConnection`] or tracked with a
+/// [`RequestTracker`].
+///
+/// Resolves to the payload of a successful response, or to a [`RequestError`] describing
+/// why the request failed.
+pub struct ResponseFuture<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+struct Shared<T> {
+    result: Option<Result<T, RequestError>>,
+    waker: Option<Waker>,
+}
+
+impl<T> ResponseFuture<T> {
+    fn new() -> (Self, Completer<T>) {
+        let shared = Arc::new(Mutex::new(Shared {
+            result: None,
+            waker: None,
+        }));
+        let completer = Completer {
+            shared: shared.clone(),
+        };
+        (ResponseFuture { shared }, completer)
+    }
+
+    /// Returns the response if it has been received, without blocking.
+    ///
+    /// The response can only be taken once. Later calls return `None`.
+    pub fn try_take(&mut self) -> Option<Result<T, RequestError>> {
+        self.shared.lock().unwrap().result.take()
+    }
+}
+
+impl<T> Future for ResponseFuture<T> {
+    type Output = Result<T, RequestError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for ResponseFuture<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shared = self.shared.lock().unwrap();
+        f.debug_struct("ResponseFuture")
+            .field("is_ready", &shared.result.is_some())
+            .finish()
+    }
+}
+
+struct Completer<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Completer<T> {
+    fn complete(self, result: Result<T, RequestError>) {
+        let waker = {
+            let mut shared = self.shared.lock().unwrap();
+            shared.result = Some(result);
+            shared.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+type CommandCompleter = Box<dyn FnOnce(Result<&CommandResponseOp<'_>, RequestError>) + Send>;
+
+/// Matches response ops to the requests they belong to.
+///
+/// Each `track_*` method returns a [`ResponseFuture`] that is resolved when the response
+/// with the given request ID is passed to [`process`](#method.process). If a disconnect op
+/// is processed, all requests that are still pending fail with
+/// [`RequestError::InternalError`].
+///
+/// [`TrackedConnection`] covers the common case of tracking every request sent through a
+/// connection.
+#[derive(Default)]
+pub struct RequestTracker {
+    reserve_entity_ids:
+        HashMap<RequestId<ReserveEntityIdsRequest>, Completer<ReservedEntityIdRange>>,
+    create_entity: HashMap<RequestId<CreateEntityRequest>, Completer<EntityId>>,
+    delete_entity: HashMap<RequestId<DeleteEntityRequest>, Completer<()>>,
+    entity_query: HashMap<RequestId<EntityQueryRequest>, Completer<QueryResponse>>,
+    commands: HashMap<RequestId<OutgoingCommandRequest>, CommandCompleter>,
+}
+
+impl RequestTracker {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn track_reserve_entity_ids(
+        &mut self,
+        request_id: RequestId<ReserveEntityIdsRequest>,
+    ) -> ResponseFuture<ReservedEntityIdRange> {
+        track(&mut self.reserve_entity_ids, request_id)
+    }
+
+    pub fn track_create_entity(
+        &mut self,
+        request_id: RequestId<CreateEntityRequest>,
+    ) -> ResponseFuture<EntityId> {
+        track(&mut self.create_entity, request_id)
+    }
+
+    pub fn track_delete_entity(
+        &mut self,
+        request_id: RequestId<DeleteEntityRequest>,
+    ) -> ResponseFuture<()> {
+        track(&mut self.delete_entity, request_id)
+    }
+
+    pub fn track_entity_query(
+        &mut self,
+        request_id: RequestId<EntityQueryRequest>,
+    ) -> ResponseFuture<QueryResponse> {
+        track(&mut self.entity_query, request_id)
+    }
+
+    /// Tracks a command request sent to component `C`.
+    ///
+    /// If the response can't be read as a `C::CommandResponse`, the future resolves to
+    /// [`RequestError::InternalError`].
+    pub fn track_command<C>(
+        &mut self,
+        request_id: RequestId<OutgoingCommandRequest>,
+    ) -> ResponseFuture<C::CommandResponse>
+    where
+        C: Component + 'static,
+        C::CommandResponse: Clone + Send,
+    {
+        self.track_command_response(request_id, |response| {
+            response
+                .get::<C>()
+                .cloned()
+                .ok_or_else(|| format!("Expected a command response for component {}", C::ID))
+        })
+    }
+
+    /// Tracks a request for command `C`.
+    ///
+    /// If the response isn't a response to `C`, the future resolves to
+    /// [`RequestError::InternalError`].
+    pub fn track_schema_command<C>(
+        &mut self,
+        request_id: RequestId<OutgoingCommandRequest>,
+    ) -> ResponseFuture<C::Response>
+    where
+        C: Command + 'static,
+        C::Response: Clone + Send,
+    {
+        self.track_command_response(request_id, |response| {
+            response.get_command::<C>().cloned().ok_or_else(|| {
+                format!(
+                    "Expected a response to command {} of component {}",
+                    C::INDEX,
+                    C::Component::ID
+                )
+            })
+        })
+    }
+
+    fn track_command_response<T, F>(
+        &mut self,
+        request_id: RequestId<OutgoingCommandRequest>,
+        read_response: F,
+    ) -> ResponseFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&CommandResponse<'_>) -> Result<T, String> + Send + 'static,
+    {
+        let (future, completer) = ResponseFuture::new();
+        let complete = move |op: Result<&CommandResponseOp<'_>, RequestError>| {
+            let result = op.and_then(|op| match &op.response {
+                StatusCode::Success(response) => read_response(response).map_err(|message| {
+                    RequestError::InternalError(format!(
+                        "{}, received a response for component {}",
+                        message, op.component_id
+                    ))
+                }),
+                StatusCode::Timeout(message) => Err(RequestError::Timeout(message.clone())),
+                StatusCode::NotFound(message) => Err(RequestError::NotFound(message.clone())),
+                StatusCode::AuthorityLost(message) => {
+                    Err(RequestError::AuthorityLost(message.clone()))
+                }
+                StatusCode::PermissionDenied(message) => {
+                    Err(RequestError::PermissionDenied(message.clone()))
+                }
+                StatusCode::ApplicationError(message) => {
+                    Err(RequestError::ApplicationError(message.clone()))
+                }
+                StatusCode::InternalError(message) => {
+                    Err(RequestError::InternalError(message.clone()))
+                }
+            });
+            completer.complete(result);
+        };
+        self.commands.insert(request_id, Box::new(complete));
+        future
+    }
+
+    /// Returns the number of requests that haven't received a response yet.
+    pub fn pending_requests(&self) -> usize {
+        self.reserve_entity_ids.len()
+            + self.create_entity.len()
+            + self.delete_entity.len()
+            + self.entity_query.len()
+            + self.commands.len()
+    }
+
+    /// Resolves the futures of all responses in `op_list`.
+    pub fn process(&mut self, op_list: &OpList) {
+        for op in op_list {
+            self.process_op(&op);
+        }
+    }
+
+    /// Resolves the future of the request `op` is a response to, if it is being tracked.
+    pub fn process_op(&mut self, op: &WorkerOp<'_>) {
+        match op {
+            WorkerOp::ReserveEntityIdsResponse(op) => {
+                if let Some(completer) = self.reserve_entity_ids.remove(&op.request_id) {
+                    completer.complete(op.status_code.clone().into_result());
+                }
+            }
+            WorkerOp::CreateEntityResponse(op) => {
+                if let Some(completer) = self.create_entity.remove(&op.request_id) {
+                    completer.complete(op.status_code.clone().into_result());
+                }
+            }
+            WorkerOp::DeleteEntityResponse(op) => {
+                if let Some(completer) = self.delete_entity.remove(&op.request_id) {
+                    completer.complete(op.status_code.clone().into_result());
+                }
+            }
+            WorkerOp::EntityQueryResponse(op) => {
+                if let Some(completer) = self.entity_query.remove(&op.request_id) {
+                    completer.complete(op.status_code.clone().into_result());
+                }
+            }
+            WorkerOp::CommandResponse(op) => {
+                if let Some(complete) = self.commands.remove(&op.request_id) {
+                    complete(Ok(op));
+                }
+            }
+            WorkerOp::Disconnect(op) => self.fail_all(&op.reason),
+            _ => {}
+        }
+    }
+
+    fn fail_all(&mut self, reason: &str) {
+        let error = RequestError::InternalError(format!("Disconnected: {}", reason));
+        fail(&mut self.reserve_entity_ids, &error);
+        fail(&mut self.create_entity, &error);
+        fail(&mut self.delete_entity, &error);
+        fail(&mut self.entity_query, &error);
+        for (_, complete) in self.commands.drain() {
+            complete(Err(error.clone()));
+        }
+    }
+}
+
+fn track<R, T>(
+    pending: &mut HashMap<RequestId<R>, Completer<T>>,
+    request_id: RequestId<R>,
+) -> ResponseFuture<T> {
+    let (future, completer) = ResponseFuture::new();
+    pending.insert(request_id, completer);
+    future
+}
+
+fn fail<R, T>(pending: &mut HashMap<RequestId<R>, Completer<T>>, error: &RequestError) {
+    for (_, completer) in pending.drain() {
+        completer.complete(Err(error.clone()));
+    }
+}
+
+impl fmt::Debug for RequestTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestTracker")
+            .field("pending_requests", &self.pending_requests())
+            .finish()
+    }
+}
+
+/// A connection that returns a [`ResponseFuture`] for every request it sends.
+///
+/// `TrackedConnection` implements [`Connection`] by forwarding to the wrapped connection,
+/// so it can be used anywhere a connection is expected. Op lists returned from
+/// [`get_op_list`](#method.get_op_list) are passed to a [`RequestTracker`] before being
+/// returned, and still contain the response ops.
+///
+/// Requests sent through the `send_*_request` methods of the [`Connection`] trait aren't
+/// tracked.
+#[derive(Debug)]
+pub struct TrackedConnection<C> {
+    connection: C,
+    tracker: RequestTracker,
+    timeout_millis: Option<u32>,
+}
+
+impl<C: Connection> TrackedConnection<C> {
+    pub fn new(connection: C) -> Self {
+        TrackedConnection {
+            connection,
+            tracker: RequestTracker::new(),
+            timeout_millis: None,
+        }
+    }
+
+    pub fn connection(&self) -> &C {
+        &self.connection
+    }
+
+    pub fn connection_mut(&mut self) -> &mut C {
+        &mut self.connection
+    }
+
+    pub fn into_inner(self) -> C {
+        self.connection
+    }
+
+    pub fn tracker(&self) -> &RequestTracker {
+        &self.tracker
+    }
+
+    /// Sets the timeout used for requests sent through this connection. If `None`, the
+    /// default timeout of the worker SDK is used.
+    pub fn set_request_timeout(&mut self, timeout_millis: Option<u32>) {
+        self.timeout_millis = timeout_millis;
+    }
+
+    pub fn reserve_entity_ids(&mut self, number: u32) -> ResponseFuture<ReservedEntityIdRange> {
+        let request_id = self
+            .connection
+            .send_reserve_entity_ids_request(ReserveEntityIdsRequest(number), self.timeout_millis);
+        self.tracker.track_reserve_entity_ids(request_id)
+    }
+
+    pub fn create_entity(
+        &mut self,
+        entity: Entity,
+        entity_id: Option<EntityId>,
+    ) -> ResponseFuture<EntityId> {
+        let request_id =
+            self.connection
+                .send_create_entity_request(entity, entity_id, self.timeout_millis);
+        self.tracker.track_create_entity(request_id)
+    }
+
+    pub fn delete_entity(&mut self, entity_id: EntityId) -> ResponseFuture<()> {
+        let request_id = self
+            .connection
+            .send_delete_entity_request(DeleteEntityRequest(entity_id), self.timeout_millis);
+        self.tracker.track_delete_entity(request_id)
+    }
+
+    pub fn entity_query(&mut self, query: EntityQuery) -> ResponseFuture<QueryResponse> {
+        let request_id = self
+            .connection
+            .send_entity_query_request(EntityQueryRequest(query), self.timeout_millis);
+        self.tracker.track_entity_query(request_id)
+    }
+
+    pub fn command_request<Co>(
+        &mut self,
+        entity_id: EntityId,
+        request: Co::CommandRequest,
+        params: CommandParameters,
+    ) -> ResponseFuture<Co::CommandResponse>
+    where
+        Co: Component + 'static,
+        Co::CommandResponse: Clone + Send,
+    {
+        let request_id = self.connection.send_command_request::<Co>(
+            entity_id,
+            request,
+            self.timeout_millis,
+            params,
+        );
+        self.tracker.track_command::<Co>(request_id)
+    }
+
+    /// Sends a request for command `Co`, resolving to the command's response type.
+    pub fn send_command<Co>(
+        &mut self,
+        entity_id: EntityId,
+        request: Co::Request,
+        params: CommandParameters,
+    ) -> ResponseFuture<Co::Response>
+    where
+        Co: Command + 'static,
+        Co::Response: Clone + Send,
+    {
+        let request_id = self.connection.send_command_request::<Co::Component>(
+            entity_id,
+            Co::into_request(request),
+            self.timeout_millis,
+            params,
+        );
+        self.tracker.track_schema_command::<Co>(request_id)
+    }
+}
+
+impl<C: Connection> Connection for TrackedConnection<C> {
+    fn send_log_message(
+        &mut self,
+        level: LogLevel,
+        logger_name: &str,
+        message: &str,
+        entity_id: Option<EntityId>,
+    ) {
+        self.connection
+            .send_log_message(level, logger_name, message, entity_id)
+    }
+
+    fn send_metrics(&mut self, metrics: &Metrics) {
+        self.connection.send_metrics(metrics)
+    }
+
+    fn send_reserve_entity_ids_request(
+        &mut self,
+        payload: ReserveEntityIdsRequest,
+        timeout_millis: Option<u32>,
+    ) -> RequestId<ReserveEntityIdsRequest> {
+        self.connection
+            .send_reserve_entity_ids_request(payload, timeout_millis)
+    }
+
+    fn send_create_entity_request(
+        &mut self,
+        entity: Entity,
+        entity_id: Option<EntityId>,
+        timeout_millis: Option<u32>,
+    ) -> RequestId<CreateEntityRequest> {
+        self.connection
+            .send_create_entity_request(entity, entity_id, timeout_millis)
+    }
+
+    fn send_delete_entity_request(
+        &mut self,
+        payload: DeleteEntityRequest,
+        timeout_millis: Option<u32>,
+    ) -> RequestId<DeleteEntityRequest> {
+        self.connection
+            .send_delete_entity_request(payload, timeout_millis)
+    }
+
+    fn send_entity_query_request(
+        &mut self,
+        payload: EntityQueryRequest,
+        timeout_millis: Option<u32>,
+    ) -> RequestId<EntityQueryRequest> {
+        self.connection
+            .send_entity_query_request(payload, timeout_millis)
+    }
+
+    fn send_command_request<Co: Component>(
+        &mut self,
+        entity_id: EntityId,
+        request: Co::CommandRequest,
+        timeout_millis: Option<u32>,
+        params: CommandParameters,
+    ) -> RequestId<OutgoingCommandRequest> {
+        self.connection
+            .send_command_request::<Co>(entity_id, request, timeout_millis, params)
+    }
+
+    fn send_command_response<Co: Component>(
+        &mut self,
+        request_id: RequestId<IncomingCommandRequest>,
+        response: Co::CommandResponse,
+    ) {
+        self.connection
+            .send_command_response::<Co>(request_id, response)
+    }
+
+    fn send_command_failure(
+        &mut self,
+        request_id: RequestId<IncomingCommandRequest>,
+        message: &str,
+    ) -> Result<(), NulError> {
+        self.connection.send_command_failure(request_id, message)
+    }
+
+    fn send_component_update<Co: Component>(
+        &mut self,
+        entity_id: EntityId,
+        update: Co::Update,
+        parameters: UpdateParameters,
+    ) {
+        self.connection
+            .send_component_update::<Co>(entity_id, update, parameters)
+    }
+
+    fn send_component_interest(
+        &mut self,
+        entity_id: EntityId,
+        interest_overrides: &[InterestOverride],
+    ) {
+        self.connection
+            .send_component_interest(entity_id, interest_overrides)
+    }
+
+    fn send_authority_loss_imminent_acknowledgement(
+        &mut self,
+        entity_id: EntityId,
+        component_id: u32,
+    ) {
+        self.connection
+            .send_authority_loss_imminent_acknowledgement(entity_id, component_id)
+    }
+
+    fn set_protocol_logging_enabled(&mut self, enabled: bool) {
+        self.connection.set_protocol_logging_enabled(enabled)
+    }
+
+    fn get_connection_status(&mut self) -> ConnectionStatus {
+        self.connection.get_connection_status()
+    }
+
+    fn get_worker_flag(&mut self, name: &str) -> Option<String> {
+        self.connection.get_worker_flag(name)
+    }
+
+    fn get_op_list(&mut self, timeout_millis: u32) -> OpList {
+        let op_list = self.connection.get_op_list(timeout_millis);
+        self.tracker.process(&op_list);
+        op_list
+    }
+
+    fn get_worker_id(&self) -> &str {
+        self.connection.get_worker_id()
+    }
+
+    fn get_worker_attributes(&self) -> &[String] {
+        self.connection.get_worker_attributes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::worker::mock::{
+        test::{Counter, Increment},
+        MockConnection, MockOpList,
+    };
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        task::Wake,
+    };
+
+    #[derive(Default)]
+    struct CountingWaker {
+        wakes: AtomicUsize,
+    }
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.wakes.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn connection() -> TrackedConnection<MockConnection> {
+        TrackedConnection::new(MockConnection::new("TestWorker"))
+    }
+
+    #[test]
+    fn futures_are_woken_when_responses_arrive() {
+        let mut connection = connection();
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future = connection.reserve_entity_ids(3);
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+
+        let request_id = connection.connection().reserve_entity_ids_requests[0].request_id;
+        connection.connection_mut().push_op_list(
+            MockOpList::new().with_reserve_entity_ids_response(
+                request_id,
+                StatusCode::Success((EntityId::new(5), 3)),
+            ),
+        );
+        connection.get_op_list(0);
+
+        assert_eq!(1, counter.wakes.load(Ordering::SeqCst));
+        match Pin::new(&mut future).poll(&mut cx) {
+            Poll::Ready(Ok(range)) => assert_eq!(
+                vec![EntityId::new(5), EntityId::new(6), EntityId::new(7)],
+                range.collect::<Vec<_>>()
+            ),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(0, connection.tracker().pending_requests());
+    }
+
+    #[test]
+    fn responses_are_matched_by_request_id() {
+        let mut connection = connection();
+        let mut first = connection.create_entity(Entity::new(), None);
+        let mut second = connection.create_entity(Entity::new(), None);
+
+        let first_id = connection.connection().create_entity_requests[0].request_id;
+        let second_id = connection.connection().create_entity_requests[1].request_id;
+        connection.connection_mut().push_op_list(
+            MockOpList::new()
+                .with_create_entity_response(second_id, StatusCode::Success(EntityId::new(2)))
+                .with_create_entity_response(first_id, StatusCode::Timeout("Timed out".into())),
+        );
+        connection.get_op_list(0);
+
+        assert_eq!(
+            Some(Err(RequestError::Timeout("Timed out".into()))),
+            first.try_take()
+        );
+        assert_eq!(Some(Ok(EntityId::new(2))), second.try_take());
+    }
+
+    #[test]
+    fn command_responses_are_typed() {
+        let mut connection = connection();
+        let entity_id = EntityId::new(1);
+        let mut response = connection.command_request::<Counter>(
+            entity_id,
+            Increment(1),
+            CommandParameters::new(),
+        );
+
+        let request_id = connection.connection().command_requests[0].request_id;
+        connection.connection_mut().push_op_list(
+            MockOpList::new().with_command_response::<Counter>(
+                request_id,
+                entity_id,
+                StatusCode::Success(Counter { value: 2 }),
+            ),
+        );
+        connection.get_op_list(0);
+
+        assert_eq!(Some(Ok(Counter { value: 2 })), response.try_take());
+    }
+
+    #[test]
+    fn pending_requests_fail_on_disconnect() {
+        let mut connection = connection();
+        let mut delete = connection.delete_entity(EntityId::new(1));
+        let mut command = connection.command_request::<Counter>(
+            EntityId::new(1),
+            Increment(1),
+            CommandParameters::new(),
+        );
+
+        connection
+            .connection_mut()
+            .push_op_list(MockOpList::new().with_disconnect("Shutting down"));
+        connection.get_op_list(0);
+
+        let error = RequestError::InternalError("Disconnected: Shutting down".into());
+        assert_eq!(Some(Err(error.clone())), delete.try_take());
+        assert_eq!(Some(Err(error)), command.try_take());
+        assert_eq!(0, connection.tracker().pending_requests());
+    }
+}
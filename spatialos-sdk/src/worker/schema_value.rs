@@ -0,0 +1,694 @@
+//! Reading and writing schema data without generated code.
+//!
+//! Generated [`Component`](../component/trait.Component.html) types can only handle the
+//! components that were known when the worker was compiled. A [`SchemaValue`] instead
+//! describes schema data as a tree of values, which is decoded from and encoded to the
+//! schema objects using the definitions in a schema bundle. This allows tools such as
+//! inspectors and snapshot editors to handle any component in a project.
+//!
+//! Objects are represented as a map from field ID to value. Names of fields, types and
+//! components can be looked up in the [`SchemaDefinitions`] that were used to decode them.
+
+use crate::worker::{
+    component::{ComponentId, EventIndex},
+    internal::schema::*,
+    EntityId,
+};
+use std::collections::{BTreeMap, HashMap};
+
+pub use spatialos_sdk_schema_bundle as schema_bundle;
+use spatialos_sdk_schema_bundle::*;
+
+/// A dynamically typed schema value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaValue {
+    Bool(bool),
+    Float(f32),
+    Double(f64),
+    Int32(i32),
+    Int64(i64),
+    Uint32(u32),
+    Uint64(u64),
+    Sint32(i32),
+    Sint64(i64),
+    Fixed32(u32),
+    Fixed64(u64),
+    Sfixed32(i32),
+    Sfixed64(i64),
+    String(String),
+    Bytes(Vec<u8>),
+    EntityId(EntityId),
+    Enum(u32),
+    Object(BTreeMap<FieldId, SchemaValue>),
+    Option(Option<Box<SchemaValue>>),
+    List(Vec<SchemaValue>),
+    Map(Vec<(SchemaValue, SchemaValue)>),
+}
+
+impl SchemaValue {
+    /// Returns the value of a field if `self` is an object that contains the field.
+    pub fn field(&self, field_id: FieldId) -> Option<&SchemaValue> {
+        match self {
+            SchemaValue::Object(fields) => fields.get(&field_id),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value of a field if `self` is an object that
+    /// contains the field.
+    pub fn field_mut(&mut self, field_id: FieldId) -> Option<&mut SchemaValue> {
+        match self {
+            SchemaValue::Object(fields) => fields.get_mut(&field_id),
+            _ => None,
+        }
+    }
+}
+
+/// A dynamically typed component update.
+///
+/// Only the fields that are set by the update are present. Option, list and map fields
+/// that are cleared by the update are set to `None` or an empty collection.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaUpdateValue {
+    pub fields: BTreeMap<FieldId, SchemaValue>,
+    pub events: BTreeMap<EventIndex, Vec<SchemaValue>>,
+}
+
+/// The definitions of a schema bundle, indexed for decoding and encoding [`SchemaValue`]s.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaDefinitions {
+    types: HashMap<String, TypeDefinition>,
+    enums: HashMap<String, EnumDefinition>,
+    components: HashMap<ComponentId, ComponentDefinition>,
+    component_names: HashMap<String, ComponentId>,
+}
+
+impl SchemaDefinitions {
+    pub fn new(bundle: SchemaBundle) -> Self {
+        let mut definitions = SchemaDefinitions::default();
+        for file in bundle.schema_files {
+            for type_definition in file.types {
+                definitions
+                    .types
+                    .insert(type_definition.qualified_name.clone(), type_definition);
+            }
+            for enum_definition in file.enums {
+                definitions
+                    .enums
+                    .insert(enum_definition.qualified_name.clone(), enum_definition);
+            }
+            for component in file.components {
+                definitions
+                    .component_names
+                    .insert(component.qualified_name.clone(), component.component_id);
+                definitions
+                    .components
+                    .insert(component.component_id, component);
+            }
+        }
+        definitions
+    }
+
+    /// Loads the definitions from the JSON schema bundle produced by the schema compiler.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        load_bundle(json)
+            .map(SchemaDefinitions::new)
+            .map_err(|e| format!("Failed to parse schema bundle: {}", e))
+    }
+
+    pub fn component(&self, component_id: ComponentId) -> Option<&ComponentDefinition> {
+        self.components.get(&component_id)
+    }
+
+    /// Looks up a component by its fully qualified name, e.g. `improbable.Position`.
+    pub fn component_by_name(&self, qualified_name: &str) -> Option<&ComponentDefinition> {
+        self.component_names
+            .get(qualified_name)
+            .and_then(|id| self.components.get(id))
+    }
+
+    /// Returns an iterator over all components in the bundle, in arbitrary order.
+    pub fn components(&self) -> impl Iterator<Item = &ComponentDefinition> {
+        self.components.values()
+    }
+
+    pub fn type_definition(&self, qualified_name: &str) -> Option<&TypeDefinition> {
+        self.types.get(qualified_name)
+    }
+
+    pub fn enum_definition(&self, qualified_name: &str) -> Option<&EnumDefinition> {
+        self.enums.get(qualified_name)
+    }
+
+    /// Returns the fields of a component's data, which are either declared inline or by the
+    /// component's data type.
+    pub fn component_fields(&self, component_id: ComponentId) -> Option<&[FieldDefinition]> {
+        let component = self.component(component_id)?;
+        match component.data_definition {
+            Some(ref data_type) => self
+                .type_definition(data_type)
+                .map(|definition| &definition.fields[..]),
+            None => Some(&component.fields[..]),
+        }
+    }
+
+    pub fn decode_component_data(
+        &self,
+        component_id: ComponentId,
+        data: &SchemaComponentData,
+    ) -> Result<SchemaValue, String> {
+        let fields = self.require_component_fields(component_id)?;
+        self.decode_fields(fields, &data.fields())
+    }
+
+    pub fn encode_component_data(
+        &self,
+        component_id: ComponentId,
+        value: &SchemaValue,
+    ) -> Result<SchemaComponentData, String> {
+        let fields = self.require_component_fields(component_id)?;
+        let mut data = SchemaComponentData::new();
+        self.encode_fields(fields, value, &data.fields_mut())?;
+        Ok(data)
+    }
+
+    pub fn decode_component_update(
+        &self,
+        component_id: ComponentId,
+        update: &SchemaComponentUpdate,
+    ) -> Result<SchemaUpdateValue, String> {
+        let component = self.require_component(component_id)?;
+        let fields = self.require_component_fields(component_id)?;
+
+        let mut output = SchemaUpdateValue::default();
+        let object = update.fields();
+        for field in fields {
+            let id = field.field_id;
+            let value = if field_count(self, &object, field)? > 0 {
+                self.decode_field(field, &object)?
+            } else if update.is_field_cleared(id) {
+                match field.field_type {
+                    FieldDefinition_FieldType::Option { .. } => SchemaValue::Option(None),
+                    FieldDefinition_FieldType::List { .. } => SchemaValue::List(Vec::new()),
+                    FieldDefinition_FieldType::Map { .. } => SchemaValue::Map(Vec::new()),
+                    FieldDefinition_FieldType::Singular { .. } => continue,
+                }
+            } else {
+                continue;
+            };
+            output.fields.insert(id, value);
+        }
+
+        let events = update.events();
+        for event in &component.events {
+            let definition = self.require_type(&event.type_reference)?;
+            let count = events.field::<SchemaObject>(event.event_index).count();
+            if count == 0 {
+                continue;
+            }
+
+            let mut values = Vec::with_capacity(count);
+            for i in 0..count {
+                let object = events.field::<SchemaObject>(event.event_index).index(i);
+                values.push(self.decode_fields(&definition.fields, &object)?);
+            }
+            output.events.insert(event.event_index, values);
+        }
+
+        Ok(output)
+    }
+
+    pub fn encode_component_update(
+        &self,
+        component_id: ComponentId,
+        value: &SchemaUpdateValue,
+    ) -> Result<SchemaComponentUpdate, String> {
+        let component = self.require_component(component_id)?;
+        let fields = self.require_component_fields(component_id)?;
+
+        let mut update = SchemaComponentUpdate::new();
+        let object = update.fields_mut();
+        for (id, field_value) in &value.fields {
+            let field = find_field(fields, *id)?;
+            self.encode_field(field, field_value, &object)?;
+
+            let cleared = match field_value {
+                SchemaValue::Option(value) => value.is_none(),
+                SchemaValue::List(values) => values.is_empty(),
+                SchemaValue::Map(values) => values.is_empty(),
+                _ => false,
+            };
+            if cleared {
+                update.add_cleared_field(*id);
+            }
+        }
+
+        let events = update.events_mut();
+        for (index, values) in &value.events {
+            let event = component
+                .events
+                .iter()
+                .find(|event| event.event_index == *index)
+                .ok_or_else(|| {
+                    format!(
+                        "Component {} has no event with index {}",
+                        component.qualified_name, index
+                    )
+                })?;
+            let definition = self.require_type(&event.type_reference)?;
+            for event_value in values {
+                let object = events.field::<SchemaObject>(*index).add();
+                self.encode_fields(&definition.fields, event_value, &object)?;
+            }
+        }
+
+        Ok(update)
+    }
+
+    /// Decodes an object of the given type, e.g. the object of a command request.
+    pub fn decode_object(
+        &self,
+        type_name: &str,
+        object: &SchemaObject,
+    ) -> Result<SchemaValue, String> {
+        let definition = self.require_type(type_name)?;
+        self.decode_fields(&definition.fields, object)
+    }
+
+    /// Encodes a value of the given type into `object`.
+    pub fn encode_object(
+        &self,
+        type_name: &str,
+        value: &SchemaValue,
+        object: &mut SchemaObject,
+    ) -> Result<(), String> {
+        let definition = self.require_type(type_name)?;
+        self.encode_fields(&definition.fields, value, object)
+    }
+
+    fn require_component(&self, component_id: ComponentId) -> Result<&ComponentDefinition, String> {
+        self.component(component_id)
+            .ok_or_else(|| format!("Unknown component ID {}", component_id))
+    }
+
+    fn require_component_fields(
+        &self,
+        component_id: ComponentId,
+    ) -> Result<&[FieldDefinition], String> {
+        self.component_fields(component_id)
+            .ok_or_else(|| format!("Unknown component ID {}", component_id))
+    }
+
+    fn require_type(&self, type_name: &str) -> Result<&TypeDefinition, String> {
+        self.type_definition(type_name)
+            .ok_or_else(|| format!("Unknown type {}", type_name))
+    }
+
+    fn decode_fields(
+        &self,
+        fields: &[FieldDefinition],
+        object: &SchemaObject,
+    ) -> Result<SchemaValue, String> {
+        let mut output = BTreeMap::new();
+        for field in fields {
+            output.insert(field.field_id, self.decode_field(field, object)?);
+        }
+        Ok(SchemaValue::Object(output))
+    }
+
+    fn decode_field(
+        &self,
+        field: &FieldDefinition,
+        object: &SchemaObject,
+    ) -> Result<SchemaValue, String> {
+        let id = field.field_id;
+        match field.field_type {
+            FieldDefinition_FieldType::Singular { ref type_reference } => {
+                if self.count(object, id, type_reference)? > 0 {
+                    self.decode_element(object, id, type_reference, 0)
+                } else {
+                    self.decode_default(object, id, type_reference)
+                }
+            }
+            FieldDefinition_FieldType::Option { ref inner_type } => {
+                if self.count(object, id, inner_type)? > 0 {
+                    let value = self.decode_element(object, id, inner_type, 0)?;
+                    Ok(SchemaValue::Option(Some(Box::new(value))))
+                } else {
+                    Ok(SchemaValue::Option(None))
+                }
+            }
+            FieldDefinition_FieldType::List { ref inner_type } => {
+                let count = self.count(object, id, inner_type)?;
+                let mut values = Vec::with_capacity(count);
+                for i in 0..count {
+                    values.push(self.decode_element(object, id, inner_type, i)?);
+                }
+                Ok(SchemaValue::List(values))
+            }
+            FieldDefinition_FieldType::Map {
+                ref key_type,
+                ref value_type,
+            } => {
+                let count = object.field::<SchemaObject>(id).count();
+                let mut entries = Vec::with_capacity(count);
+                for i in 0..count {
+                    let pair = object.field::<SchemaObject>(id).index(i);
+                    let key = self.decode_default(&pair, 1, key_type)?;
+                    let value = self.decode_default(&pair, 2, value_type)?;
+                    entries.push((key, value));
+                }
+                Ok(SchemaValue::Map(entries))
+            }
+        }
+    }
+
+    fn count(
+        &self,
+        object: &SchemaObject,
+        field_id: FieldId,
+        type_reference: &TypeReference,
+    ) -> Result<usize, String> {
+        match type_reference {
+            TypeReference::Primitive(primitive) => primitive_count(object, field_id, primitive),
+            TypeReference::Enum(_) => Ok(object.field::<SchemaEnum>(field_id).count()),
+            TypeReference::Type(_) => Ok(object.field::<SchemaObject>(field_id).count()),
+        }
+    }
+
+    // Decodes the element at `index` of a field.
+    fn decode_element(
+        &self,
+        object: &SchemaObject,
+        field_id: FieldId,
+        type_reference: &TypeReference,
+        index: usize,
+    ) -> Result<SchemaValue, String> {
+        match type_reference {
+            TypeReference::Primitive(primitive) => {
+                primitive_index(object, field_id, primitive, index)
+            }
+            TypeReference::Enum(_) => Ok(SchemaValue::Enum(
+                object.field::<SchemaEnum>(field_id).index(index),
+            )),
+            TypeReference::Type(type_name) => {
+                let inner = object.field::<SchemaObject>(field_id).index(index);
+                self.decode_object(type_name, &inner)
+            }
+        }
+    }
+
+    // Decodes a singular field, falling back to the default value if it isn't present.
+    fn decode_default(
+        &self,
+        object: &SchemaObject,
+        field_id: FieldId,
+        type_reference: &TypeReference,
+    ) -> Result<SchemaValue, String> {
+        match type_reference {
+            TypeReference::Primitive(primitive) => primitive_default(object, field_id, primitive),
+            TypeReference::Enum(_) => Ok(SchemaValue::Enum(
+                object.field::<SchemaEnum>(field_id).get_or_default(),
+            )),
+            TypeReference::Type(type_name) => {
+                let inner = object.field::<SchemaObject>(field_id).get_or_default();
+                self.decode_object(type_name, &inner)
+            }
+        }
+    }
+
+    fn encode_fields(
+        &self,
+        fields: &[FieldDefinition],
+        value: &SchemaValue,
+        object: &SchemaObject,
+    ) -> Result<(), String> {
+        let values = match value {
+            SchemaValue::Object(values) => values,
+            other => return Err(format!("Expected an object, found {:?}", other)),
+        };
+
+        for (id, field_value) in values {
+            self.encode_field(find_field(fields, *id)?, field_value, object)?;
+        }
+        Ok(())
+    }
+
+    fn encode_field(
+        &self,
+        field: &FieldDefinition,
+        value: &SchemaValue,
+        object: &SchemaObject,
+    ) -> Result<(), String> {
+        let id = field.field_id;
+        match (&field.field_type, value) {
+            (FieldDefinition_FieldType::Singular { type_reference }, value) => {
+                self.encode_element(object, id, type_reference, value)
+            }
+            (FieldDefinition_FieldType::Option { inner_type }, SchemaValue::Option(value)) => {
+                match value {
+                    Some(value) => self.encode_element(object, id, inner_type, value),
+                    None => Ok(()),
+                }
+            }
+            (FieldDefinition_FieldType::List { inner_type }, SchemaValue::List(values)) => {
+                for value in values {
+                    self.encode_element(object, id, inner_type, value)?;
+                }
+                Ok(())
+            }
+            (
+                FieldDefinition_FieldType::Map {
+                    key_type,
+                    value_type,
+                },
+                SchemaValue::Map(entries),
+            ) => {
+                for (key, value) in entries {
+                    let pair = object.field::<SchemaObject>(id).add();
+                    self.encode_element(&pair, 1, key_type, key)?;
+                    self.encode_element(&pair, 2, value_type, value)?;
+                }
+                Ok(())
+            }
+            (_, value) => Err(format!(
+                "Value {:?} doesn't match the type of field {}",
+                value, field.name
+            )),
+        }
+    }
+
+    fn encode_element(
+        &self,
+        object: &SchemaObject,
+        field_id: FieldId,
+        type_reference: &TypeReference,
+        value: &SchemaValue,
+    ) -> Result<(), String> {
+        match (type_reference, value) {
+            (TypeReference::Primitive(primitive), value) => {
+                primitive_add(object, field_id, primitive, value)
+            }
+            (TypeReference::Enum(_), SchemaValue::Enum(value)) => {
+                object.field::<SchemaEnum>(field_id).add(*value);
+                Ok(())
+            }
+            (TypeReference::Type(type_name), value) => {
+                let definition = self.require_type(type_name)?;
+                let inner = object.field::<SchemaObject>(field_id).add();
+                self.encode_fields(&definition.fields, value, &inner)
+            }
+            (TypeReference::Enum(enum_name), value) => Err(format!(
+                "Expected a value of enum {}, found {:?}",
+                enum_name, value
+            )),
+        }
+    }
+}
+
+fn find_field(fields: &[FieldDefinition], field_id: FieldId) -> Result<&FieldDefinition, String> {
+    fields
+        .iter()
+        .find(|field| field.field_id == field_id)
+        .ok_or_else(|| format!("Unknown field ID {}", field_id))
+}
+
+// Returns the number of elements a field has, which for map fields is the number of entries.
+fn field_count(
+    definitions: &SchemaDefinitions,
+    object: &SchemaObject,
+    field: &FieldDefinition,
+) -> Result<usize, String> {
+    match field.field_type {
+        FieldDefinition_FieldType::Singular { ref type_reference } => {
+            definitions.count(object, field.field_id, type_reference)
+        }
+        FieldDefinition_FieldType::Option { ref inner_type }
+        | FieldDefinition_FieldType::List { ref inner_type } => {
+            definitions.count(object, field.field_id, inner_type)
+        }
+        FieldDefinition_FieldType::Map { .. } => {
+            Ok(object.field::<SchemaObject>(field.field_id).count())
+        }
+    }
+}
+
+fn unsupported_primitive(primitive: &PrimitiveType) -> String {
+    format!("Primitive type {:?} is not supported", primitive)
+}
+
+macro_rules! impl_primitive_accessors {
+    ($($variant:ident => $schema_type:ty),*) => {
+        fn primitive_count(
+            object: &SchemaObject,
+            field_id: FieldId,
+            primitive: &PrimitiveType,
+        ) -> Result<usize, String> {
+            match primitive {
+                $(PrimitiveType::$variant => Ok(object.field::<$schema_type>(field_id).count()),)*
+                PrimitiveType::String => Ok(object.field::<SchemaString>(field_id).count()),
+                PrimitiveType::Bytes => Ok(object.field::<SchemaBytes>(field_id).count()),
+                other => Err(unsupported_primitive(other)),
+            }
+        }
+
+        fn primitive_index(
+            object: &SchemaObject,
+            field_id: FieldId,
+            primitive: &PrimitiveType,
+            index: usize,
+        ) -> Result<SchemaValue, String> {
+            match primitive {
+                $(PrimitiveType::$variant => Ok(SchemaValue::$variant(
+                    object.field::<$schema_type>(field_id).index(index),
+                )),)*
+                PrimitiveType::String => Ok(SchemaValue::String(
+                    object.field::<SchemaString>(field_id).index(index),
+                )),
+                PrimitiveType::Bytes => Ok(SchemaValue::Bytes(
+                    object.field::<SchemaBytes>(field_id).index(index),
+                )),
+                other => Err(unsupported_primitive(other)),
+            }
+        }
+
+        fn primitive_default(
+            object: &SchemaObject,
+            field_id: FieldId,
+            primitive: &PrimitiveType,
+        ) -> Result<SchemaValue, String> {
+            match primitive {
+                $(PrimitiveType::$variant => Ok(SchemaValue::$variant(
+                    object.field::<$schema_type>(field_id).get_or_default(),
+                )),)*
+                PrimitiveType::String => Ok(SchemaValue::String(
+                    object.field::<SchemaString>(field_id).get_or_default(),
+                )),
+                PrimitiveType::Bytes => Ok(SchemaValue::Bytes(
+                    object.field::<SchemaBytes>(field_id).get_or_default(),
+                )),
+                other => Err(unsupported_primitive(other)),
+            }
+        }
+
+        fn primitive_add(
+            object: &SchemaObject,
+            field_id: FieldId,
+            primitive: &PrimitiveType,
+            value: &SchemaValue,
+        ) -> Result<(), String> {
+            match (primitive, value) {
+                $((PrimitiveType::$variant, SchemaValue::$variant(value)) => {
+                    object.field::<$schema_type>(field_id).add(*value)
+                })*
+                (PrimitiveType::String, SchemaValue::String(value)) => {
+                    object.field::<SchemaString>(field_id).add(value)
+                }
+                (PrimitiveType::Bytes, SchemaValue::Bytes(value)) => {
+                    object.field::<SchemaBytes>(field_id).add(value)
+                }
+                (PrimitiveType::Invalid, _) | (PrimitiveType::Entity, _) => {
+                    return Err(unsupported_primitive(primitive))
+                }
+                (primitive, value) => {
+                    return Err(format!(
+                        "Expected a value of type {:?}, found {:?}",
+                        primitive, value
+                    ))
+                }
+            }
+            Ok(())
+        }
+    };
+}
+
+impl_primitive_accessors!(
+    Bool => SchemaBool,
+    Float => SchemaFloat,
+    Double => SchemaDouble,
+    Int32 => SchemaInt32,
+    Int64 => SchemaInt64,
+    Uint32 => SchemaUint32,
+    Uint64 => SchemaUint64,
+    Sint32 => SchemaSint32,
+    Sint64 => SchemaSint64,
+    Fixed32 => SchemaFixed32,
+    Fixed64 => SchemaFixed64,
+    Sfixed32 => SchemaSfixed32,
+    Sfixed64 => SchemaSfixed64,
+    EntityId => SchemaEntityId
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn definitions() -> SchemaDefinitions {
+        SchemaDefinitions::from_json(include_str!(
+            "../../../spatialos-sdk-code-generator/data/test.sb.json"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn definitions_are_indexed_by_name_and_id() {
+        let definitions = definitions();
+
+        let component = definitions.component(1000).unwrap();
+        assert_eq!("example.Example", component.qualified_name);
+        assert_eq!(
+            Some(1000),
+            definitions
+                .component_by_name("example.Example")
+                .map(|component| component.component_id)
+        );
+        assert!(definitions
+            .type_definition("example.TestType.Inner")
+            .is_some());
+        assert!(definitions.enum_definition("example.TestEnum").is_some());
+        assert_eq!(
+            component.fields.len(),
+            definitions.component_fields(1000).unwrap().len()
+        );
+        assert!(definitions.component(1).is_none());
+    }
+
+    #[test]
+    fn invalid_bundles_are_rejected() {
+        assert!(SchemaDefinitions::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn fields_are_looked_up_on_objects() {
+        let mut fields = BTreeMap::new();
+        fields.insert(1, SchemaValue::Int32(5));
+        let mut value = SchemaValue::Object(fields);
+
+        assert_eq!(Some(&SchemaValue::Int32(5)), value.field(1));
+        assert_eq!(None, value.field(2));
+        assert_eq!(None, SchemaValue::Int32(5).field(1));
+
+        *value.field_mut(1).unwrap() = SchemaValue::Int32(6);
+        assert_eq!(Some(&SchemaValue::Int32(6)), value.field(1));
+    }
+}
@@ -0,0 +1,166 @@
+//! Helpers referenced by generated code when it's generated with serde support enabled.
+//!
+//! These are used through `#[serde(with = "...")]` attributes and aren't normally called directly.
+
+/// Serializes a map as a sequence of key-value pairs.
+///
+/// Used for maps whose keys can't be represented as strings, such as maps keyed by schema types.
+pub mod map_as_pairs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<K, V, S>(map: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let pairs = Vec::<(K, V)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+/// Equivalent to [`map_as_pairs`](../map_as_pairs/index.html) for map fields in component updates.
+pub mod option_map_as_pairs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<K, V, S>(
+        map: &Option<BTreeMap<K, V>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        match map {
+            Some(map) => serializer.serialize_some(&Pairs(map)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<Option<BTreeMap<K, V>>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let pairs = Option::<Vec<(K, V)>>::deserialize(deserializer)?;
+        Ok(pairs.map(|pairs| pairs.into_iter().collect()))
+    }
+
+    struct Pairs<'a, K, V>(&'a BTreeMap<K, V>);
+
+    impl<'a, K: Serialize, V: Serialize> Serialize for Pairs<'a, K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::map_as_pairs::serialize(self.0, serializer)
+        }
+    }
+}
+
+/// Serializes an option field in a component update.
+///
+/// The outer `Option` is expected to be skipped when it's `None`, so that a missing value means
+/// the field wasn't updated and `null` means the field was cleared.
+pub mod double_option {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(value: &Option<Option<T>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        match value {
+            Some(inner) => inner.serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::worker::EntityId;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Update {
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "super::double_option"
+        )]
+        target: Option<Option<EntityId>>,
+
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "super::option_map_as_pairs"
+        )]
+        weights: Option<BTreeMap<Vec<u8>, u32>>,
+    }
+
+    #[test]
+    fn entity_id_is_serialized_as_an_integer() {
+        assert_eq!("5", serde_json::to_string(&EntityId::new(5)).unwrap());
+        assert_eq!(EntityId::new(5), serde_json::from_str("5").unwrap());
+    }
+
+    #[test]
+    fn update_fields_distinguish_unset_and_cleared() {
+        let cases = vec![
+            (Update::default(), "{}"),
+            (
+                Update {
+                    target: Some(None),
+                    ..Update::default()
+                },
+                r#"{"target":null}"#,
+            ),
+            (
+                Update {
+                    target: Some(Some(EntityId::new(3))),
+                    ..Update::default()
+                },
+                r#"{"target":3}"#,
+            ),
+        ];
+
+        for (update, json) in cases {
+            assert_eq!(json, serde_json::to_string(&update).unwrap());
+            assert_eq!(update, serde_json::from_str::<Update>(json).unwrap());
+        }
+    }
+
+    #[test]
+    fn maps_can_be_serialized_as_pairs() {
+        let mut weights = BTreeMap::new();
+        weights.insert(vec![1, 2], 7);
+        let update = Update {
+            weights: Some(weights),
+            ..Update::default()
+        };
+
+        let json = serde_json::to_string(&update).unwrap();
+        assert_eq!(r#"{"weights":[[[1,2],7]]}"#, json);
+        assert_eq!(update, serde_json::from_str::<Update>(&json).unwrap());
+    }
+}
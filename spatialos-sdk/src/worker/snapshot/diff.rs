@@ -0,0 +1,487 @@
+//! Comparing the entities in two snapshots.
+//!
+//! Components are compared in their JSON form (see the [`json`] module), so changes are
+//! reported by component and field name, using the definitions of a schema bundle. Changes to
+//! fields of nested types are reported with a path such as `coords.x`, and elements of lists
+//! and maps with their index, such as `attribute_set[1]`.
+//!
+//! [`json`]: ../json/index.html
+
+use crate::worker::{
+    component::{ComponentId, ComponentRegistry},
+    schema_value::SchemaDefinitions,
+    snapshot::{json, SerializedComponent, SerializedEntity, SnapshotError, SnapshotInputStream},
+    EntityId,
+};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path,
+};
+
+/// The differences between two snapshots.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotDiff {
+    /// Entities that are only in the new snapshot.
+    pub added_entities: Vec<EntityId>,
+
+    /// Entities that are only in the old snapshot.
+    pub removed_entities: Vec<EntityId>,
+
+    /// Entities that are in both snapshots, but with different components.
+    pub changed_entities: Vec<EntityChanges>,
+}
+
+/// The differences between the two versions of an entity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityChanges {
+    pub entity_id: EntityId,
+
+    /// The qualified names of the components that were added to the entity.
+    pub added_components: Vec<String>,
+
+    /// The qualified names of the components that were removed from the entity.
+    pub removed_components: Vec<String>,
+
+    /// Changes to the fields of components that are in both versions of the entity.
+    pub changed_fields: Vec<FieldChange>,
+}
+
+/// A change to the value of a field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// The qualified name of the component the field belongs to.
+    pub component: String,
+
+    /// The path to the field within the component, e.g. `coords.x`.
+    pub path: String,
+
+    /// The old value, or `None` if the field wasn't set.
+    pub old: Option<Value>,
+
+    /// The new value, or `None` if the field is no longer set.
+    pub new: Option<Value>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_entities.is_empty()
+            && self.removed_entities.is_empty()
+            && self.changed_entities.is_empty()
+    }
+
+    /// Returns the diff as a JSON value, for tools that process it further.
+    pub fn to_json(&self) -> Value {
+        let ids = |ids: &[EntityId]| Value::from(ids.iter().map(|id| id.id).collect::<Vec<_>>());
+        let changed_entities = self
+            .changed_entities
+            .iter()
+            .map(|entity| {
+                let changed_fields = entity
+                    .changed_fields
+                    .iter()
+                    .map(|change| {
+                        let mut object = Map::new();
+                        object.insert("component".to_owned(), Value::from(&*change.component));
+                        object.insert("path".to_owned(), Value::from(&*change.path));
+                        object.insert("old".to_owned(), change.old.clone().unwrap_or(Value::Null));
+                        object.insert("new".to_owned(), change.new.clone().unwrap_or(Value::Null));
+                        Value::Object(object)
+                    })
+                    .collect();
+
+                let mut object = Map::new();
+                object.insert("id".to_owned(), Value::from(entity.entity_id.id));
+                object.insert(
+                    "added_components".to_owned(),
+                    Value::from(entity.added_components.clone()),
+                );
+                object.insert(
+                    "removed_components".to_owned(),
+                    Value::from(entity.removed_components.clone()),
+                );
+                object.insert("changed_fields".to_owned(), Value::Array(changed_fields));
+                Value::Object(object)
+            })
+            .collect();
+
+        let mut document = Map::new();
+        document.insert("added_entities".to_owned(), ids(&self.added_entities));
+        document.insert("removed_entities".to_owned(), ids(&self.removed_entities));
+        document.insert(
+            "changed_entities".to_owned(),
+            Value::Array(changed_entities),
+        );
+        Value::Object(document)
+    }
+}
+
+/// Formats the diff for people to read, with one line per change.
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for id in &self.added_entities {
+            writeln!(f, "+ entity {}", id.id)?;
+        }
+        for id in &self.removed_entities {
+            writeln!(f, "- entity {}", id.id)?;
+        }
+        for entity in &self.changed_entities {
+            writeln!(f, "~ entity {}", entity.entity_id.id)?;
+            for component in &entity.added_components {
+                writeln!(f, "    + {}", component)?;
+            }
+            for component in &entity.removed_components {
+                writeln!(f, "    - {}", component)?;
+            }
+            for change in &entity.changed_fields {
+                let show = |value: &Option<Value>| match value {
+                    Some(value) => value.to_string(),
+                    None => "(unset)".to_owned(),
+                };
+                writeln!(
+                    f,
+                    "    ~ {}.{}: {} -> {}",
+                    change.component,
+                    change.path,
+                    show(&change.old),
+                    show(&change.new)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares the snapshots at `old` and `new`.
+///
+/// The entities of `old` are held in memory, keyed by entity ID, while `new` is streamed
+/// and compared one entity at a time. Memory use is therefore proportional to the size of
+/// the old snapshot plus the number of entities in the new one, so pass the smaller snapshot
+/// as `old` where possible.
+pub fn diff<P: AsRef<Path>, Q: AsRef<Path>>(
+    definitions: &SchemaDefinitions,
+    old: P,
+    new: Q,
+) -> Result<SnapshotDiff, SnapshotError> {
+    let old = read_snapshot(old)?;
+
+    // An empty registry keeps every component in its serialized form.
+    let mut new = SnapshotInputStream::with_registry(new, &ComponentRegistry::new())?;
+    diff_stream(definitions, old, new.serialized_entities())
+}
+
+/// Compares two sets of entities. If an entity ID appears more than once, the last entity with
+/// that ID is used.
+///
+/// As with [`diff`](fn.diff.html), only the entities of `old` are held in memory.
+pub fn diff_entities<A, B>(
+    definitions: &SchemaDefinitions,
+    old: A,
+    new: B,
+) -> Result<SnapshotDiff, SnapshotError>
+where
+    A: IntoIterator<Item = SerializedEntity>,
+    B: IntoIterator<Item = SerializedEntity>,
+{
+    diff_stream(definitions, old, new.into_iter().map(Ok))
+}
+
+fn diff_stream<A, B>(
+    definitions: &SchemaDefinitions,
+    old: A,
+    new: B,
+) -> Result<SnapshotDiff, SnapshotError>
+where
+    A: IntoIterator<Item = SerializedEntity>,
+    B: IntoIterator<Item = Result<SerializedEntity, SnapshotError>>,
+{
+    let old = by_id(old);
+
+    let mut new_ids = BTreeSet::new();
+    let mut changed_entities = BTreeMap::new();
+    for new_entity in new {
+        let new_entity = new_entity?;
+        new_ids.insert(new_entity.id);
+
+        let old_entity = match old.get(&new_entity.id) {
+            Some(old_entity) => old_entity,
+            None => continue,
+        };
+        // A later entity with the same ID replaces the changes found for an earlier one.
+        match diff_entity(definitions, old_entity, &new_entity)? {
+            Some(changes) => changed_entities.insert(new_entity.id, changes),
+            None => changed_entities.remove(&new_entity.id),
+        };
+    }
+
+    Ok(SnapshotDiff {
+        added_entities: new_ids
+            .iter()
+            .filter(|id| !old.contains_key(id))
+            .cloned()
+            .collect(),
+        removed_entities: old
+            .keys()
+            .filter(|id| !new_ids.contains(id))
+            .cloned()
+            .collect(),
+        changed_entities: changed_entities.into_values().collect(),
+    })
+}
+
+fn diff_entity(
+    definitions: &SchemaDefinitions,
+    old: &SerializedEntity,
+    new: &SerializedEntity,
+) -> Result<Option<EntityChanges>, SnapshotError> {
+    let old_components = components_by_id(old);
+    let new_components = components_by_id(new);
+    let mut changes = EntityChanges {
+        entity_id: new.id,
+        added_components: Vec::new(),
+        removed_components: Vec::new(),
+        changed_fields: Vec::new(),
+    };
+
+    for (&id, old_component) in &old_components {
+        let new_component = match new_components.get(&id) {
+            Some(new_component) => new_component,
+            None => {
+                changes
+                    .removed_components
+                    .push(component_name(definitions, old.id, id)?);
+                continue;
+            }
+        };
+
+        // Identical data can't have changed fields, so only decode components that differ.
+        if old_component.data == new_component.data {
+            continue;
+        }
+
+        let name = component_name(definitions, old.id, id)?;
+        let to_json = |component| {
+            json::component_to_json(definitions, component).map_err(|message| {
+                SnapshotError::InvalidData(format!("Entity {}: {}", old.id.id, message))
+            })
+        };
+        let mut fields = Vec::new();
+        diff_values(
+            "",
+            Some(&to_json(old_component)?),
+            Some(&to_json(new_component)?),
+            &mut fields,
+        );
+        changes
+            .changed_fields
+            .extend(fields.into_iter().map(|(path, old, new)| FieldChange {
+                component: name.clone(),
+                path,
+                old,
+                new,
+            }));
+    }
+
+    for &id in new_components.keys() {
+        if !old_components.contains_key(&id) {
+            changes
+                .added_components
+                .push(component_name(definitions, new.id, id)?);
+        }
+    }
+
+    let unchanged = changes.added_components.is_empty()
+        && changes.removed_components.is_empty()
+        && changes.changed_fields.is_empty();
+    Ok(if unchanged { None } else { Some(changes) })
+}
+
+// Appends the differences between `old` and `new` to `changes`, as (path, old, new) tuples.
+fn diff_values(
+    path: &str,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    changes: &mut Vec<(String, Option<Value>, Option<Value>)>,
+) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_values(&path, old.get(key), new.get(key), changes);
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for index in 0..old.len().max(new.len()) {
+                let path = format!("{}[{}]", path, index);
+                diff_values(&path, old.get(index), new.get(index), changes);
+            }
+        }
+        (old, new) if old != new => {
+            changes.push((path.to_owned(), old.cloned(), new.cloned()));
+        }
+        _ => {}
+    }
+}
+
+fn read_snapshot<P: AsRef<Path>>(path: P) -> Result<Vec<SerializedEntity>, SnapshotError> {
+    // An empty registry keeps every component in its serialized form.
+    let mut stream = SnapshotInputStream::with_registry(path, &ComponentRegistry::new())?;
+    stream.serialized_entities().collect()
+}
+
+fn by_id<I: IntoIterator<Item = SerializedEntity>>(
+    entities: I,
+) -> BTreeMap<EntityId, SerializedEntity> {
+    entities
+        .into_iter()
+        .map(|entity| (entity.id, entity))
+        .collect()
+}
+
+fn components_by_id(entity: &SerializedEntity) -> BTreeMap<ComponentId, &SerializedComponent> {
+    entity
+        .components
+        .iter()
+        .map(|component| (component.id, component))
+        .collect()
+}
+
+fn component_name(
+    definitions: &SchemaDefinitions,
+    entity_id: EntityId,
+    component_id: ComponentId,
+) -> Result<String, SnapshotError> {
+    definitions
+        .component(component_id)
+        .map(|component| component.qualified_name.clone())
+        .ok_or_else(|| {
+            SnapshotError::InvalidData(format!(
+                "Entity {} has component {}, which is not in the schema bundle",
+                entity_id.id, component_id
+            ))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn definitions() -> SchemaDefinitions {
+        SchemaDefinitions::from_json(include_str!(
+            "../../../../spatialos-sdk-code-generator/data/test.sb.json"
+        ))
+        .unwrap()
+    }
+
+    fn entity(id: i64, components: &[ComponentId]) -> SerializedEntity {
+        SerializedEntity {
+            id: EntityId::new(id),
+            components: components
+                .iter()
+                .map(|&id| SerializedComponent {
+                    id,
+                    data: vec![1, 2, 3],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn added_and_removed_entities_and_components_are_reported() {
+        let old = vec![entity(1, &[1000, 1001]), entity(2, &[]), entity(3, &[1000])];
+        let new = vec![entity(3, &[1000]), entity(1, &[1000, 2001]), entity(4, &[])];
+
+        let diff = diff_entities(&definitions(), old, new).unwrap();
+        assert_eq!(
+            SnapshotDiff {
+                added_entities: vec![EntityId::new(4)],
+                removed_entities: vec![EntityId::new(2)],
+                changed_entities: vec![EntityChanges {
+                    entity_id: EntityId::new(1),
+                    added_components: vec!["example.EntityIdTest".to_owned()],
+                    removed_components: vec!["example.Rotate".to_owned()],
+                    changed_fields: vec![],
+                }],
+            },
+            diff
+        );
+        assert_eq!(
+            "+ entity 4\n- entity 2\n~ entity 1\n    + example.EntityIdTest\n    - example.Rotate\n",
+            diff.to_string()
+        );
+        assert_eq!(
+            json!({
+                "added_entities": [4],
+                "removed_entities": [2],
+                "changed_entities": [{
+                    "id": 1,
+                    "added_components": ["example.EntityIdTest"],
+                    "removed_components": ["example.Rotate"],
+                    "changed_fields": [],
+                }],
+            }),
+            diff.to_json()
+        );
+    }
+
+    #[test]
+    fn identical_snapshots_have_an_empty_diff() {
+        let entities = vec![entity(1, &[1000]), entity(2, &[])];
+        assert!(diff_entities(&definitions(), entities.clone(), entities)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn later_entities_with_the_same_id_replace_earlier_ones() {
+        let old = vec![entity(1, &[1000]), entity(2, &[])];
+        let new = vec![entity(1, &[]), entity(2, &[1000]), entity(1, &[1000])];
+
+        let diff = diff_entities(&definitions(), old, new).unwrap();
+        assert!(diff.added_entities.is_empty());
+        assert!(diff.removed_entities.is_empty());
+        assert_eq!(
+            vec![EntityId::new(2)],
+            diff.changed_entities
+                .iter()
+                .map(|changes| changes.entity_id)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn values_are_compared_by_field_path() {
+        let old = json!({
+            "angle": 1.0,
+            "center": { "x": 0.0, "y": 0.0 },
+            "targets": [1, 2],
+        });
+        let new = json!({
+            "angle": 1.0,
+            "center": { "x": 0.5, "y": 0.0 },
+            "targets": [1, 3, 4],
+            "name": "rotator",
+        });
+
+        let mut changes = Vec::new();
+        diff_values("", Some(&old), Some(&new), &mut changes);
+        assert_eq!(
+            vec![
+                ("center.x".to_owned(), Some(json!(0.0)), Some(json!(0.5))),
+                ("name".to_owned(), None, Some(json!("rotator"))),
+                ("targets[1]".to_owned(), Some(json!(2)), Some(json!(3))),
+                ("targets[2]".to_owned(), None, Some(json!(4))),
+            ],
+            changes
+        );
+    }
+}
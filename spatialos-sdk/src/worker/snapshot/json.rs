@@ -0,0 +1,577 @@
+//! Converting snapshots to and from JSON.
+//!
+//! Components are decoded with the [`SchemaDefinitions`] of a schema bundle, such as the
+//! `build/schema/bundle.json` file written by `cargo spatial codegen`, so that components and
+//! fields are rendered by name. A snapshot is converted to a document of the form:
+//!
+//! ```json
+//! {
+//!   "entities": [
+//!     {
+//!       "id": 1,
+//!       "components": {
+//!         "improbable.Position": { "coords": { "x": 0.0, "y": 0.0, "z": 0.0 } }
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Numbers, booleans and strings map to their JSON equivalents. Enums are written as the name of
+//! their value, bytes as an array of numbers, entity IDs as a number, empty options as `null` and
+//! maps as an array of `{ "key": ..., "value": ... }` objects, since schema map keys aren't
+//! necessarily strings. Fields that are missing when converting from JSON take their default
+//! value.
+//!
+//! [`SchemaDefinitions`]: ../../schema_value/struct.SchemaDefinitions.html
+
+use crate::worker::{
+    component::ComponentId,
+    internal::schema::SchemaComponentData,
+    schema_value::{schema_bundle::*, SchemaDefinitions, SchemaValue},
+    snapshot::{SerializedComponent, SerializedEntity, SnapshotError},
+    EntityId,
+};
+use serde_json::{Map, Number, Value};
+use spatialos_sdk_sys::worker::Schema_DestroyComponentData;
+use std::{collections::BTreeMap, convert::TryFrom};
+
+/// Converts a sequence of entities, such as those read from a snapshot, to a JSON document.
+pub fn snapshot_to_json<I>(
+    definitions: &SchemaDefinitions,
+    entities: I,
+) -> Result<Value, SnapshotError>
+where
+    I: IntoIterator<Item = Result<SerializedEntity, SnapshotError>>,
+{
+    let entities = entities
+        .into_iter()
+        .map(|entity| entity_to_json(definitions, &entity?))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut document = Map::new();
+    document.insert("entities".to_owned(), Value::Array(entities));
+    Ok(Value::Object(document))
+}
+
+/// Converts a JSON document produced by [`snapshot_to_json`](fn.snapshot_to_json.html) back to
+/// the entities it describes.
+pub fn snapshot_from_json(
+    definitions: &SchemaDefinitions,
+    document: &Value,
+) -> Result<Vec<SerializedEntity>, SnapshotError> {
+    document
+        .get("entities")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid_data("Expected an object with an \"entities\" array".to_owned()))?
+        .iter()
+        .map(|entity| entity_from_json(definitions, entity))
+        .collect()
+}
+
+pub fn entity_to_json(
+    definitions: &SchemaDefinitions,
+    entity: &SerializedEntity,
+) -> Result<Value, SnapshotError> {
+    let mut components = Map::new();
+    for component in &entity.components {
+        let definition = definitions.component(component.id).ok_or_else(|| {
+            invalid_data(format!(
+                "Entity {} has component {}, which is not in the schema bundle",
+                entity.id.id, component.id
+            ))
+        })?;
+        let value = component_to_json(definitions, component)
+            .map_err(|message| invalid_data(format!("Entity {}: {}", entity.id.id, message)))?;
+        components.insert(definition.qualified_name.clone(), value);
+    }
+
+    let mut object = Map::new();
+    object.insert("id".to_owned(), Value::from(entity.id.id));
+    object.insert("components".to_owned(), Value::Object(components));
+    Ok(Value::Object(object))
+}
+
+pub fn entity_from_json(
+    definitions: &SchemaDefinitions,
+    json: &Value,
+) -> Result<SerializedEntity, SnapshotError> {
+    let id = json
+        .get("id")
+        .and_then(Value::as_i64)
+        .map(EntityId::new)
+        .ok_or_else(|| invalid_data("Expected an entity with an integer \"id\"".to_owned()))?;
+
+    let mut components = Vec::new();
+    if let Some(json_components) = json.get("components") {
+        let json_components = json_components.as_object().ok_or_else(|| {
+            invalid_data(format!(
+                "Entity {}: expected \"components\" to be an object",
+                id.id
+            ))
+        })?;
+        for (name, value) in json_components {
+            let definition = definitions.component_by_name(name).ok_or_else(|| {
+                invalid_data(format!(
+                    "Entity {}: component {} is not in the schema bundle",
+                    id.id, name
+                ))
+            })?;
+            let component = component_from_json(definitions, definition.component_id, value)
+                .map_err(|message| invalid_data(format!("Entity {}: {}", id.id, message)))?;
+            components.push(component);
+        }
+    }
+    components.sort_by_key(|component| component.id);
+
+    Ok(SerializedEntity { id, components })
+}
+
+/// Converts the data of a component to a JSON object keyed by field name.
+pub fn component_to_json(
+    definitions: &SchemaDefinitions,
+    component: &SerializedComponent,
+) -> Result<Value, String> {
+    let fields = component_fields(definitions, component.id)?;
+
+    let mut data = SchemaComponentData::new();
+    let value = data
+        .fields_mut()
+        .merge_from_buffer(&component.data)
+        .and_then(|_| definitions.decode_component_data(component.id, &data));
+    unsafe { Schema_DestroyComponentData(data.internal) };
+
+    object_to_json(definitions, fields, &value?)
+}
+
+/// Converts a JSON object keyed by field name to the data of a component.
+pub fn component_from_json(
+    definitions: &SchemaDefinitions,
+    component_id: ComponentId,
+    json: &Value,
+) -> Result<SerializedComponent, String> {
+    let fields = component_fields(definitions, component_id)?;
+    let value = object_from_json(definitions, fields, json)?;
+
+    let data = definitions.encode_component_data(component_id, &value)?;
+    let serialized = data.fields().serialize();
+    unsafe { Schema_DestroyComponentData(data.internal) };
+
+    Ok(SerializedComponent {
+        id: component_id,
+        data: serialized?,
+    })
+}
+
+fn component_fields(
+    definitions: &SchemaDefinitions,
+    component_id: ComponentId,
+) -> Result<&[FieldDefinition], String> {
+    definitions
+        .component_fields(component_id)
+        .ok_or_else(|| format!("Unknown component {}", component_id))
+}
+
+fn object_to_json(
+    definitions: &SchemaDefinitions,
+    fields: &[FieldDefinition],
+    value: &SchemaValue,
+) -> Result<Value, String> {
+    let mut object = Map::new();
+    for field in fields {
+        if let Some(field_value) = value.field(field.field_id) {
+            let json = field_to_json(definitions, &field.field_type, field_value)
+                .map_err(|message| format!("{}: {}", field.name, message))?;
+            object.insert(field.name.clone(), json);
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+fn field_to_json(
+    definitions: &SchemaDefinitions,
+    field_type: &FieldDefinition_FieldType,
+    value: &SchemaValue,
+) -> Result<Value, String> {
+    match (field_type, value) {
+        (FieldDefinition_FieldType::Singular { type_reference }, value) => {
+            value_to_json(definitions, type_reference, value)
+        }
+        (FieldDefinition_FieldType::Option { inner_type }, SchemaValue::Option(value)) => {
+            match value {
+                Some(value) => value_to_json(definitions, inner_type, value),
+                None => Ok(Value::Null),
+            }
+        }
+        (FieldDefinition_FieldType::List { inner_type }, SchemaValue::List(values)) => values
+            .iter()
+            .map(|value| value_to_json(definitions, inner_type, value))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        (
+            FieldDefinition_FieldType::Map {
+                key_type,
+                value_type,
+            },
+            SchemaValue::Map(entries),
+        ) => entries
+            .iter()
+            .map(|(key, value)| {
+                let mut entry = Map::new();
+                entry.insert("key".to_owned(), value_to_json(definitions, key_type, key)?);
+                entry.insert(
+                    "value".to_owned(),
+                    value_to_json(definitions, value_type, value)?,
+                );
+                Ok(Value::Object(entry))
+            })
+            .collect::<Result<_, String>>()
+            .map(Value::Array),
+        (field_type, value) => Err(format!("{:?} does not match {:?}", value, field_type)),
+    }
+}
+
+fn value_to_json(
+    definitions: &SchemaDefinitions,
+    type_reference: &TypeReference,
+    value: &SchemaValue,
+) -> Result<Value, String> {
+    match (type_reference, value) {
+        (TypeReference::Enum(name), SchemaValue::Enum(value)) => {
+            let definition = require_enum(definitions, name)?;
+            definition
+                .values
+                .iter()
+                .find(|enum_value| enum_value.value == *value)
+                .map(|enum_value| Value::String(enum_value.name.clone()))
+                .ok_or_else(|| format!("{} is not a value of enum {}", value, name))
+        }
+        (TypeReference::Type(name), SchemaValue::Object(_)) => {
+            let definition = require_type(definitions, name)?;
+            object_to_json(definitions, &definition.fields, value)
+        }
+        (TypeReference::Primitive(_), value) => primitive_to_json(value),
+        (type_reference, value) => Err(format!("{:?} does not match {:?}", value, type_reference)),
+    }
+}
+
+fn primitive_to_json(value: &SchemaValue) -> Result<Value, String> {
+    Ok(match *value {
+        SchemaValue::Bool(value) => Value::Bool(value),
+        // Formatting the `f32` first gives its shortest representation, rather than the digits of
+        // the nearest `f64`.
+        SchemaValue::Float(value) => float_to_json(value.to_string().parse().unwrap_or(f64::NAN))?,
+        SchemaValue::Double(value) => float_to_json(value)?,
+        SchemaValue::Int32(value) | SchemaValue::Sint32(value) | SchemaValue::Sfixed32(value) => {
+            Value::from(value)
+        }
+        SchemaValue::Int64(value) | SchemaValue::Sint64(value) | SchemaValue::Sfixed64(value) => {
+            Value::from(value)
+        }
+        SchemaValue::Uint32(value) | SchemaValue::Fixed32(value) => Value::from(value),
+        SchemaValue::Uint64(value) | SchemaValue::Fixed64(value) => Value::from(value),
+        SchemaValue::String(ref value) => Value::String(value.clone()),
+        SchemaValue::Bytes(ref value) => {
+            Value::Array(value.iter().map(|&byte| Value::from(byte)).collect())
+        }
+        SchemaValue::EntityId(value) => Value::from(value.id),
+        ref value => return Err(format!("{:?} is not a primitive value", value)),
+    })
+}
+
+fn float_to_json(value: f64) -> Result<Value, String> {
+    Number::from_f64(value)
+        .map(Value::Number)
+        .ok_or_else(|| format!("{} can't be represented in JSON", value))
+}
+
+fn object_from_json(
+    definitions: &SchemaDefinitions,
+    fields: &[FieldDefinition],
+    json: &Value,
+) -> Result<SchemaValue, String> {
+    let json = json
+        .as_object()
+        .ok_or_else(|| expected("an object", json))?;
+
+    let mut values = BTreeMap::new();
+    for (name, json) in json {
+        let field = fields
+            .iter()
+            .find(|field| field.name == *name)
+            .ok_or_else(|| format!("Unknown field {}", name))?;
+        let value = field_from_json(definitions, &field.field_type, json)
+            .map_err(|message| format!("{}: {}", name, message))?;
+        values.insert(field.field_id, value);
+    }
+    Ok(SchemaValue::Object(values))
+}
+
+fn field_from_json(
+    definitions: &SchemaDefinitions,
+    field_type: &FieldDefinition_FieldType,
+    json: &Value,
+) -> Result<SchemaValue, String> {
+    match field_type {
+        FieldDefinition_FieldType::Singular { type_reference } => {
+            value_from_json(definitions, type_reference, json)
+        }
+        FieldDefinition_FieldType::Option { inner_type } => match json {
+            Value::Null => Ok(SchemaValue::Option(None)),
+            json => value_from_json(definitions, inner_type, json)
+                .map(|value| SchemaValue::Option(Some(Box::new(value)))),
+        },
+        FieldDefinition_FieldType::List { inner_type } => json
+            .as_array()
+            .ok_or_else(|| expected("an array", json))?
+            .iter()
+            .map(|json| value_from_json(definitions, inner_type, json))
+            .collect::<Result<_, _>>()
+            .map(SchemaValue::List),
+        FieldDefinition_FieldType::Map {
+            key_type,
+            value_type,
+        } => json
+            .as_array()
+            .ok_or_else(|| expected("an array", json))?
+            .iter()
+            .map(|entry| match (entry.get("key"), entry.get("value")) {
+                (Some(key), Some(value)) => Ok((
+                    value_from_json(definitions, key_type, key)?,
+                    value_from_json(definitions, value_type, value)?,
+                )),
+                _ => Err(expected("an object with a \"key\" and a \"value\"", entry)),
+            })
+            .collect::<Result<_, _>>()
+            .map(SchemaValue::Map),
+    }
+}
+
+fn value_from_json(
+    definitions: &SchemaDefinitions,
+    type_reference: &TypeReference,
+    json: &Value,
+) -> Result<SchemaValue, String> {
+    match type_reference {
+        TypeReference::Enum(name) => {
+            let definition = require_enum(definitions, name)?;
+            let value_name = json.as_str().ok_or_else(|| expected("a string", json))?;
+            definition
+                .values
+                .iter()
+                .find(|enum_value| enum_value.name == value_name)
+                .map(|enum_value| SchemaValue::Enum(enum_value.value))
+                .ok_or_else(|| format!("{} is not a value of enum {}", value_name, name))
+        }
+        TypeReference::Type(name) => {
+            let definition = require_type(definitions, name)?;
+            object_from_json(definitions, &definition.fields, json)
+        }
+        TypeReference::Primitive(primitive) => primitive_from_json(primitive, json),
+    }
+}
+
+fn primitive_from_json(primitive: &PrimitiveType, json: &Value) -> Result<SchemaValue, String> {
+    let int = || json.as_i64().ok_or_else(|| expected("an integer", json));
+    let uint = || {
+        json.as_u64()
+            .ok_or_else(|| expected("an unsigned integer", json))
+    };
+    let int32 = || int().and_then(|value| i32::try_from(value).map_err(|_| out_of_range(json)));
+    let uint32 = || uint().and_then(|value| u32::try_from(value).map_err(|_| out_of_range(json)));
+    let float = || json.as_f64().ok_or_else(|| expected("a number", json));
+
+    Ok(match primitive {
+        PrimitiveType::Bool => {
+            SchemaValue::Bool(json.as_bool().ok_or_else(|| expected("a boolean", json))?)
+        }
+        PrimitiveType::Float => SchemaValue::Float(float()? as f32),
+        PrimitiveType::Double => SchemaValue::Double(float()?),
+        PrimitiveType::Int32 => SchemaValue::Int32(int32()?),
+        PrimitiveType::Int64 => SchemaValue::Int64(int()?),
+        PrimitiveType::Uint32 => SchemaValue::Uint32(uint32()?),
+        PrimitiveType::Uint64 => SchemaValue::Uint64(uint()?),
+        PrimitiveType::Sint32 => SchemaValue::Sint32(int32()?),
+        PrimitiveType::Sint64 => SchemaValue::Sint64(int()?),
+        PrimitiveType::Fixed32 => SchemaValue::Fixed32(uint32()?),
+        PrimitiveType::Fixed64 => SchemaValue::Fixed64(uint()?),
+        PrimitiveType::Sfixed32 => SchemaValue::Sfixed32(int32()?),
+        PrimitiveType::Sfixed64 => SchemaValue::Sfixed64(int()?),
+        PrimitiveType::String => SchemaValue::String(
+            json.as_str()
+                .ok_or_else(|| expected("a string", json))?
+                .to_owned(),
+        ),
+        PrimitiveType::Bytes => SchemaValue::Bytes(
+            json.as_array()
+                .ok_or_else(|| expected("an array of bytes", json))?
+                .iter()
+                .map(|byte| {
+                    byte.as_u64()
+                        .and_then(|byte| u8::try_from(byte).ok())
+                        .ok_or_else(|| expected("a byte", byte))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        PrimitiveType::EntityId => SchemaValue::EntityId(EntityId::new(int()?)),
+        PrimitiveType::Invalid | PrimitiveType::Entity => {
+            return Err(format!("{:?} fields are not supported", primitive))
+        }
+    })
+}
+
+fn require_enum<'a>(
+    definitions: &'a SchemaDefinitions,
+    name: &str,
+) -> Result<&'a EnumDefinition, String> {
+    definitions
+        .enum_definition(name)
+        .ok_or_else(|| format!("Unknown enum {}", name))
+}
+
+fn require_type<'a>(
+    definitions: &'a SchemaDefinitions,
+    name: &str,
+) -> Result<&'a TypeDefinition, String> {
+    definitions
+        .type_definition(name)
+        .ok_or_else(|| format!("Unknown type {}", name))
+}
+
+fn expected(description: &str, json: &Value) -> String {
+    format!("Expected {}, found {}", description, json)
+}
+
+fn out_of_range(json: &Value) -> String {
+    format!("{} is out of range", json)
+}
+
+fn invalid_data(message: String) -> SnapshotError {
+    SnapshotError::InvalidData(message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn definitions() -> SchemaDefinitions {
+        SchemaDefinitions::from_json(include_str!(
+            "../../../../spatialos-sdk-code-generator/data/test.sb.json"
+        ))
+        .unwrap()
+    }
+
+    fn field(name: &str, field_id: u32, field_type: FieldDefinition_FieldType) -> FieldDefinition {
+        FieldDefinition {
+            source_reference: SourceReference { line: 0, column: 0 },
+            annotations: Vec::new(),
+            name: name.to_owned(),
+            field_id,
+            transient: false,
+            field_type,
+        }
+    }
+
+    fn fields() -> Vec<FieldDefinition> {
+        use FieldDefinition_FieldType::*;
+
+        vec![
+            field(
+                "position",
+                1,
+                Singular {
+                    type_reference: TypeReference::Type("example.Vector3d".to_owned()),
+                },
+            ),
+            field(
+                "mode",
+                2,
+                Option {
+                    inner_type: TypeReference::Enum("example.TestEnum".to_owned()),
+                },
+            ),
+            field(
+                "targets",
+                3,
+                List {
+                    inner_type: TypeReference::Primitive(PrimitiveType::EntityId),
+                },
+            ),
+            field(
+                "weights",
+                4,
+                Map {
+                    key_type: TypeReference::Primitive(PrimitiveType::Bytes),
+                    value_type: TypeReference::Primitive(PrimitiveType::Float),
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn objects_are_rendered_by_field_name_and_round_trip() {
+        let definitions = definitions();
+        let fields = fields();
+        let json = json!({
+            "position": { "x": 1.5, "y": 0.0, "z": -2.0 },
+            "mode": "SECOND",
+            "targets": [3, 4],
+            "weights": [{ "key": [1, 2], "value": 0.1 }],
+        });
+
+        let value = object_from_json(&definitions, &fields, &json).unwrap();
+        assert_eq!(
+            Some(&SchemaValue::List(vec![
+                SchemaValue::EntityId(EntityId::new(3)),
+                SchemaValue::EntityId(EntityId::new(4)),
+            ])),
+            value.field(3)
+        );
+        assert_eq!(json, object_to_json(&definitions, &fields, &value).unwrap());
+    }
+
+    #[test]
+    fn empty_options_are_null() {
+        let definitions = definitions();
+        let value = object_from_json(&definitions, &fields(), &json!({ "mode": null })).unwrap();
+        assert_eq!(Some(&SchemaValue::Option(None)), value.field(2));
+        assert_eq!(
+            json!({ "mode": null }),
+            object_to_json(&definitions, &fields(), &value).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_reported_with_their_field() {
+        let definitions = definitions();
+        let cases = vec![
+            (json!({ "unknown": 1 }), "Unknown field unknown"),
+            (
+                json!({ "mode": "THIRD" }),
+                "mode: THIRD is not a value of enum",
+            ),
+            (
+                json!({ "position": { "x": "1" } }),
+                "position: x: Expected a number",
+            ),
+            (
+                json!({ "weights": [{ "key": [256], "value": 1 }] }),
+                "weights: Expected a byte",
+            ),
+        ];
+
+        for (json, message) in cases {
+            let error = object_from_json(&definitions, &fields(), &json).unwrap_err();
+            assert!(error.starts_with(message), "{}", error);
+        }
+    }
+
+    #[test]
+    fn documents_without_entities_are_rejected() {
+        match snapshot_from_json(&definitions(), &json!({})) {
+            Err(SnapshotError::InvalidData(_)) => {}
+            other => panic!("Expected InvalidData, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,515 @@
+//! Reading and writing SpatialOS snapshots.
+//!
+//! Snapshots are read and written by the `Worker_Snapshot*` functions of the C API, so every
+//! tool that uses this module links the native worker SDK. There is no pure-Rust
+//! implementation of the snapshot file format, as that format isn't publicly specified.
+//! Component data is converted to and from the C API's schema objects through the vtables in
+//! a [`ComponentRegistry`].
+
+use crate::{
+    worker::component::{ComponentId, ComponentRegistry, HandleKind, UserHandle},
+    worker::entity::Entity,
+    worker::internal::{schema::SchemaComponentData, utils::cstr_to_string},
+    worker::EntityId,
+};
+use spatialos_sdk_sys::worker::*;
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+use tempfile::NamedTempFile;
+
+pub mod diff;
+pub mod json;
+pub mod transform;
+pub mod validation;
+
+pub use self::validation::validate;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    BadState(String),
+    InvalidData(String),
+    EntitySerializationFailure(String),
+    EOF,
+}
+
+impl From<Worker_SnapshotState> for SnapshotError {
+    fn from(state: Worker_SnapshotState) -> SnapshotError {
+        match Worker_StreamState::from(state.stream_state) {
+            Worker_StreamState_WORKER_STREAM_STATE_BAD => {
+                SnapshotError::BadState(cstr_to_string(state.error_message))
+            }
+            Worker_StreamState_WORKER_STREAM_STATE_INVALID_DATA => {
+                SnapshotError::InvalidData(cstr_to_string(state.error_message))
+            }
+            Worker_StreamState_WORKER_STREAM_STATE_EOF => SnapshotError::EOF,
+            _ => SnapshotError::BadState(cstr_to_string(state.error_message)),
+        }
+    }
+}
+
+/// A component in its serialized form, i.e. the schema wire format encoding of its fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializedComponent {
+    pub id: ComponentId,
+    pub data: Vec<u8>,
+}
+
+/// An entity whose components are kept in their serialized form.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SerializedEntity {
+    pub id: EntityId,
+    pub components: Vec<SerializedComponent>,
+}
+
+impl SerializedEntity {
+    /// Serializes the components of `entity` through their vtables.
+    pub fn from_entity(id: EntityId, entity: &Entity) -> Result<Self, SnapshotError> {
+        let components = entity
+            .serialize_components()
+            .map_err(SnapshotError::EntitySerializationFailure)?
+            .into_iter()
+            .map(|(id, data)| SerializedComponent { id, data })
+            .collect();
+
+        Ok(SerializedEntity { id, components })
+    }
+
+    /// Deserializes the components using the vtables in `registry`.
+    pub fn to_entity(&self, registry: &ComponentRegistry) -> Result<Entity, SnapshotError> {
+        let mut entity = Entity::new();
+        for component in &self.components {
+            let vtable = registry.get_vtable(component.id).cloned().ok_or_else(|| {
+                SnapshotError::EntitySerializationFailure(format!(
+                    "Could not find a vtable implementation for component {}",
+                    component.id
+                ))
+            })?;
+
+            let mut data = SchemaComponentData::new();
+            if let Err(message) = data.fields_mut().merge_from_buffer(&component.data) {
+                unsafe { Schema_DestroyComponentData(data.internal) };
+                return Err(SnapshotError::InvalidData(message));
+            }
+
+            unsafe { entity.add_serialized_with_vtable(component.id, data, vtable) }
+                .map_err(SnapshotError::EntitySerializationFailure)?;
+        }
+
+        Ok(entity)
+    }
+}
+
+/// Writes entities to a snapshot.
+///
+/// The C API can only write snapshots to a file given by its path, not to a `Write`, so
+/// [`in_memory`] is only a file-backed shim: the snapshot is written to a temporary file,
+/// which is copied out by [`finish_into`] and deleted once the stream is dropped. Nothing is
+/// kept in memory until then. Writing directly to a file with [`new`] avoids the copy.
+///
+/// [`in_memory`]: #method.in_memory
+/// [`finish_into`]: #method.finish_into
+/// [`new`]: #method.new
+pub struct SnapshotOutputStream {
+    // Null once the stream has been finished.
+    ptr: *mut Worker_SnapshotOutputStream,
+
+    // The file being written, used to read the snapshot back in `finish_into`.
+    path: PathBuf,
+
+    // The temporary file backing an in-memory snapshot.
+    spool: Option<NamedTempFile>,
+}
+
+impl SnapshotOutputStream {
+    pub fn new<P: AsRef<Path>>(filename: P) -> Result<Self, SnapshotError> {
+        SnapshotOutputStream::with_registry(filename, &ComponentRegistry::global())
+    }
+
+    /// Creates a snapshot whose contents can be retrieved with [`into_bytes`] or
+    /// [`finish_into`] once all entities have been written.
+    ///
+    /// Despite the name, the snapshot is written to a temporary file until it's finished,
+    /// so this needs a writable temporary directory and disk space for the whole snapshot.
+    ///
+    /// [`into_bytes`]: #method.into_bytes
+    /// [`finish_into`]: #method.finish_into
+    pub fn in_memory() -> Result<Self, SnapshotError> {
+        SnapshotOutputStream::in_memory_with_registry(&ComponentRegistry::global())
+    }
+
+    /// Creates an in-memory snapshot using the vtables in `registry` instead of the global
+    /// registry.
+    pub fn in_memory_with_registry(registry: &ComponentRegistry) -> Result<Self, SnapshotError> {
+        let spool = NamedTempFile::new().map_err(io_error)?;
+        let mut stream = SnapshotOutputStream::with_registry(spool.path(), registry)?;
+        stream.spool = Some(spool);
+        Ok(stream)
+    }
+
+    /// Opens a snapshot using the vtables in `registry` instead of the global registry.
+    pub fn with_registry<P: AsRef<Path>>(
+        filename: P,
+        registry: &ComponentRegistry,
+    ) -> Result<Self, SnapshotError> {
+        let filename_cstr = path_to_cstring(filename.as_ref())?;
+
+        let params = Worker_SnapshotParameters {
+            component_vtable_count: registry.len() as u32,
+            component_vtables: registry.to_worker_sdk(),
+            default_component_vtable: std::ptr::null(),
+        };
+        let stream_ptr =
+            unsafe { Worker_SnapshotOutputStream_Create(filename_cstr.as_ptr(), &params) };
+
+        let state = unsafe { Worker_SnapshotOutputStream_GetState(stream_ptr) };
+        match Worker_StreamState::from(state.stream_state) {
+            Worker_StreamState_WORKER_STREAM_STATE_GOOD => Ok(SnapshotOutputStream {
+                ptr: stream_ptr,
+                path: filename.as_ref().to_owned(),
+                spool: None,
+            }),
+            _ => {
+                unsafe { Worker_SnapshotOutputStream_Destroy(stream_ptr) };
+                Err(SnapshotError::from(state))
+            }
+        }
+    }
+
+    pub fn write_entity(&mut self, id: EntityId, entity: &Entity) -> Result<(), SnapshotError> {
+        let components = entity.raw_component_data();
+        let wrk_entity = Worker_Entity {
+            entity_id: id.id,
+            components: components.components.as_ptr(),
+            component_count: components.components.len() as u32,
+        };
+
+        let state = unsafe {
+            Worker_SnapshotOutputStream_WriteEntity(self.ptr, &wrk_entity);
+            Worker_SnapshotOutputStream_GetState(self.ptr)
+        };
+        match Worker_StreamState::from(state.stream_state) {
+            Worker_StreamState_WORKER_STREAM_STATE_GOOD => Ok(()),
+            _ => Err(SnapshotError::from(state)),
+        }
+    }
+
+    /// Writes an entity whose components are in their serialized form, without needing vtables
+    /// for them.
+    pub fn write_serialized(&mut self, entity: &SerializedEntity) -> Result<(), SnapshotError> {
+        let mut schema_data = Vec::with_capacity(entity.components.len());
+        let mut result = Ok(());
+        for component in &entity.components {
+            let mut data = SchemaComponentData::new();
+            let merged = data.fields_mut().merge_from_buffer(&component.data);
+            schema_data.push(data);
+            if let Err(message) = merged {
+                result = Err(SnapshotError::InvalidData(message));
+                break;
+            }
+        }
+
+        if result.is_ok() {
+            let components: Vec<Worker_ComponentData> = entity
+                .components
+                .iter()
+                .zip(&schema_data)
+                .map(|(component, data)| Worker_ComponentData {
+                    reserved: std::ptr::null_mut(),
+                    component_id: component.id,
+                    schema_type: data.internal,
+                    user_handle: std::ptr::null_mut(),
+                })
+                .collect();
+            let wrk_entity = Worker_Entity {
+                entity_id: entity.id.id,
+                components: components.as_ptr(),
+                component_count: components.len() as u32,
+            };
+
+            let state = unsafe {
+                Worker_SnapshotOutputStream_WriteEntity(self.ptr, &wrk_entity);
+                Worker_SnapshotOutputStream_GetState(self.ptr)
+            };
+            if Worker_StreamState::from(state.stream_state)
+                != Worker_StreamState_WORKER_STREAM_STATE_GOOD
+            {
+                result = Err(SnapshotError::from(state));
+            }
+        }
+
+        // The entity is only borrowed by the C API, so the serialized data is still ours.
+        for data in schema_data {
+            unsafe { Schema_DestroyComponentData(data.internal) };
+        }
+        result
+    }
+
+    /// Finishes writing the snapshot and copies its contents to `writer`.
+    ///
+    /// The contents are read back from the file the snapshot was written to, as the C API
+    /// can't write to `writer` directly.
+    pub fn finish_into<W: Write>(mut self, mut writer: W) -> Result<W, SnapshotError> {
+        // Destroying the stream flushes the snapshot to the file.
+        unsafe { Worker_SnapshotOutputStream_Destroy(self.ptr) };
+        self.ptr = std::ptr::null_mut();
+
+        let mut file = File::open(&self.path).map_err(io_error)?;
+        io::copy(&mut file, &mut writer).map_err(io_error)?;
+        Ok(writer)
+    }
+
+    /// Finishes writing the snapshot and returns its contents.
+    pub fn into_bytes(self) -> Result<Vec<u8>, SnapshotError> {
+        self.finish_into(Vec::new())
+    }
+}
+
+impl Drop for SnapshotOutputStream {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { Worker_SnapshotOutputStream_Destroy(self.ptr) };
+        }
+    }
+}
+
+/// Reads entities from a snapshot.
+///
+/// The C API can only read snapshots from a file given by its path, not from a `Read`, so
+/// [`from_reader`] is only a file-backed shim: the snapshot is copied in full to a temporary
+/// file before any entities are read, and the file is deleted once the stream is dropped.
+/// Entities are not streamed from the reader. Opening a file directly with [`new`] avoids
+/// the copy.
+///
+/// [`from_reader`]: #method.from_reader
+/// [`new`]: #method.new
+pub struct SnapshotInputStream {
+    ptr: *mut Worker_SnapshotInputStream,
+
+    // The vtables the C API uses to deserialize components, needed to manage their data.
+    registry: ComponentRegistry,
+
+    // Set once reading fails, after which iteration stops.
+    failed: bool,
+
+    // The temporary file holding a snapshot that was read from memory.
+    spool: Option<NamedTempFile>,
+}
+
+impl SnapshotInputStream {
+    pub fn new<P: AsRef<Path>>(filename: P) -> Result<Self, SnapshotError> {
+        SnapshotInputStream::with_registry(filename, &ComponentRegistry::global())
+    }
+
+    /// Reads a snapshot from `reader`, e.g. a snapshot embedded with `include_bytes!` or one
+    /// being decompressed from an archive.
+    ///
+    /// All of `reader` is copied to a temporary file before this returns, so this needs a
+    /// writable temporary directory and disk space for the whole snapshot.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, SnapshotError> {
+        SnapshotInputStream::from_reader_with_registry(reader, &ComponentRegistry::global())
+    }
+
+    /// Reads a snapshot from `reader` using the vtables in `registry` instead of the global
+    /// registry.
+    pub fn from_reader_with_registry<R: Read>(
+        mut reader: R,
+        registry: &ComponentRegistry,
+    ) -> Result<Self, SnapshotError> {
+        let mut spool = NamedTempFile::new().map_err(io_error)?;
+        io::copy(&mut reader, spool.as_file_mut()).map_err(io_error)?;
+        spool.as_file_mut().flush().map_err(io_error)?;
+
+        let mut stream = SnapshotInputStream::with_registry(spool.path(), registry)?;
+        stream.spool = Some(spool);
+        Ok(stream)
+    }
+
+    /// Opens a snapshot using the vtables in `registry` instead of the global registry.
+    pub fn with_registry<P: AsRef<Path>>(
+        filename: P,
+        registry: &ComponentRegistry,
+    ) -> Result<Self, SnapshotError> {
+        let filename_cstr = path_to_cstring(filename.as_ref())?;
+
+        let params = Worker_SnapshotParameters {
+            component_vtable_count: registry.len() as u32,
+            component_vtables: registry.to_worker_sdk(),
+            default_component_vtable: std::ptr::null(),
+        };
+
+        let stream_ptr =
+            unsafe { Worker_SnapshotInputStream_Create(filename_cstr.as_ptr(), &params) };
+
+        let state = unsafe { Worker_SnapshotInputStream_GetState(stream_ptr) };
+        match Worker_StreamState::from(state.stream_state) {
+            Worker_StreamState_WORKER_STREAM_STATE_GOOD => Ok(SnapshotInputStream {
+                ptr: stream_ptr,
+                registry: registry.clone(),
+                failed: false,
+                spool: None,
+            }),
+            _ => {
+                unsafe { Worker_SnapshotInputStream_Destroy(stream_ptr) };
+                Err(SnapshotError::from(state))
+            }
+        }
+    }
+
+    pub fn has_next(&mut self) -> bool {
+        unsafe { Worker_SnapshotInputStream_HasNext(self.ptr) != 0 }
+    }
+
+    /// Reads the next entity in the snapshot, along with its ID.
+    pub fn read_entity(&mut self) -> Result<(EntityId, Entity), SnapshotError> {
+        let entity_ptr = unsafe { Worker_SnapshotInputStream_ReadEntity(self.ptr) };
+        let state = unsafe { Worker_SnapshotInputStream_GetState(self.ptr) };
+
+        match Worker_StreamState::from(state.stream_state) {
+            Worker_StreamState_WORKER_STREAM_STATE_GOOD => unsafe {
+                let raw_entity = &*entity_ptr;
+                let entity =
+                    Entity::from_worker_sdk(raw_entity, |id| self.registry.get_vtable(id).cloned())
+                        .map_err(SnapshotError::EntitySerializationFailure)?;
+                Ok((EntityId::new(raw_entity.entity_id), entity))
+            },
+            _ => Err(SnapshotError::from(state)),
+        }
+    }
+
+    /// Reads the next entity in the snapshot with its components in their serialized form.
+    ///
+    /// Components without a vtable in the stream's registry are read without being
+    /// deserialized, so opening the stream with an empty registry allows reading any
+    /// component.
+    pub fn read_serialized(&mut self) -> Result<SerializedEntity, SnapshotError> {
+        let entity_ptr = unsafe { Worker_SnapshotInputStream_ReadEntity(self.ptr) };
+        let state = unsafe { Worker_SnapshotInputStream_GetState(self.ptr) };
+        if Worker_StreamState::from(state.stream_state)
+            != Worker_StreamState_WORKER_STREAM_STATE_GOOD
+        {
+            return Err(SnapshotError::from(state));
+        }
+
+        let raw_entity = unsafe { &*entity_ptr };
+        let raw_components = unsafe {
+            std::slice::from_raw_parts(raw_entity.components, raw_entity.component_count as usize)
+        };
+        let components = raw_components
+            .iter()
+            .map(|component| {
+                Ok(SerializedComponent {
+                    id: component.component_id,
+                    data: self.serialize_component(component)?,
+                })
+            })
+            .collect::<Result<_, String>>()
+            .map_err(SnapshotError::EntitySerializationFailure)?;
+
+        Ok(SerializedEntity {
+            id: EntityId::new(raw_entity.entity_id),
+            components,
+        })
+    }
+
+    /// Returns an iterator over the remaining entities in the snapshot, read with
+    /// [`read_serialized`](#method.read_serialized). Iteration stops after the first error.
+    pub fn serialized_entities(
+        &mut self,
+    ) -> impl Iterator<Item = Result<SerializedEntity, SnapshotError>> + '_ {
+        std::iter::from_fn(move || {
+            if self.failed || !self.has_next() {
+                return None;
+            }
+
+            let result = self.read_serialized();
+            self.failed = result.is_err();
+            Some(result)
+        })
+    }
+
+    fn serialize_component(&self, component: &Worker_ComponentData) -> Result<Vec<u8>, String> {
+        if !component.schema_type.is_null() {
+            let data = SchemaComponentData {
+                internal: component.schema_type,
+            };
+            return data.fields().serialize();
+        }
+
+        let id = component.component_id;
+        if component.user_handle.is_null() {
+            return Err(format!("Component {} has no data", id));
+        }
+        let vtable = self.registry.get_vtable(id).cloned().ok_or_else(|| {
+            format!(
+                "Could not find a vtable implementation for component {}",
+                id
+            )
+        })?;
+        let handle = unsafe {
+            UserHandle::copy_with_vtable(
+                HandleKind::ComponentData,
+                id,
+                component.user_handle,
+                vtable,
+            )
+        };
+        handle.serialize_data()
+    }
+}
+
+/// Reads the remaining entities in the snapshot. Iteration stops after the first error.
+impl Iterator for SnapshotInputStream {
+    type Item = Result<(EntityId, Entity), SnapshotError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || !self.has_next() {
+            return None;
+        }
+
+        let result = self.read_entity();
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+impl Drop for SnapshotInputStream {
+    fn drop(&mut self) {
+        unsafe { Worker_SnapshotInputStream_Destroy(self.ptr) }
+    }
+}
+
+fn path_to_cstring(path: &Path) -> Result<CString, SnapshotError> {
+    path.to_str()
+        .and_then(|path| CString::new(path).ok())
+        .ok_or_else(|| {
+            SnapshotError::BadState(format!(
+                "Snapshot path {} can't be passed to the C API",
+                path.display()
+            ))
+        })
+}
+
+fn io_error(error: io::Error) -> SnapshotError {
+    SnapshotError::BadState(error.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paths_that_cannot_be_passed_to_the_c_api_are_rejected() {
+        match SnapshotInputStream::new("snapshot\0.snapshot") {
+            Err(SnapshotError::BadState(_)) => {}
+            other => panic!("Expected BadState, got {:?}", other.map(|_| ())),
+        }
+        match SnapshotOutputStream::new("snapshot\0.snapshot") {
+            Err(SnapshotError::BadState(_)) => {}
+            other => panic!("Expected BadState, got {:?}", other.map(|_| ())),
+        }
+    }
+}
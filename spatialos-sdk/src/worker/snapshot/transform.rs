@@ -0,0 +1,444 @@
+//! Rewriting snapshots, e.g. to migrate them to a new version of the schema.
+//!
+//! A [`SnapshotTransformer`] streams entities from one snapshot to another, applying the
+//! transforms registered on it along the way. Only one entity is held in memory at a time, so
+//! snapshots of any size can be transformed.
+//!
+//! Components are copied without being decoded unless a field transform is registered for
+//! them. In that case the component is converted to its JSON form (see the [`json`] module)
+//! using the input definitions, transformed, and converted back using the output definitions.
+//! This allows fields to be renamed or added when the schema changes:
+//!
+//! ```no_run
+//! use serde_json::json;
+//! use spatialos_sdk::worker::{schema_value::SchemaDefinitions, snapshot::transform::*};
+//!
+//! # fn load(path: &str) -> SchemaDefinitions { unimplemented!() }
+//! let transformer = SnapshotTransformer::new(load("old_bundle.json"))
+//!     .with_output_definitions(load("bundle.json"))
+//!     .with_renamed_field(1001, "angle", "rotation")
+//!     .with_field_default(1001, "speed", json!(1.0))
+//!     .with_remapped_component(1002, 1502)
+//!     .with_dropped_component(1003)
+//!     .with_filter(|entity| entity.id.id != 5);
+//!
+//! transformer
+//!     .transform_snapshot("old.snapshot", "default.snapshot")
+//!     .expect("Failed to transform snapshot");
+//! ```
+//!
+//! [`SnapshotTransformer`]: struct.SnapshotTransformer.html
+//! [`json`]: ../json/index.html
+
+use crate::worker::{
+    component::{ComponentId, ComponentRegistry},
+    entity_builder::ENTITY_ACL_COMPONENT_ID,
+    internal::schema::{SchemaComponentData, SchemaObject, SchemaUint32},
+    internal::schema::{SchemaObjectField, SchemaPrimitiveField},
+    schema_value::SchemaDefinitions,
+    snapshot::{
+        json, validation::COMPONENT_WRITE_ACL_FIELD, SerializedComponent, SerializedEntity,
+        SnapshotError, SnapshotInputStream, SnapshotOutputStream,
+    },
+};
+use serde_json::{Map, Value};
+use spatialos_sdk_sys::worker::Schema_DestroyComponentData;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+/// The field of `improbable.EntityAcl` that lists the attribute sets with read access.
+const READ_ACL_FIELD: u32 = 1;
+
+type FieldTransform = Box<dyn Fn(&mut Map<String, Value>) -> Result<(), String>>;
+type EntityFilter = Box<dyn Fn(&SerializedEntity) -> bool>;
+
+/// Counts of the entities handled by a transformer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransformSummary {
+    pub entities_read: usize,
+    pub entities_written: usize,
+}
+
+/// Applies a set of transforms to the entities of a snapshot.
+///
+/// Filters are applied to entities before they're transformed. Component IDs always refer to
+/// the IDs in the input snapshot, even if the component is remapped to a different ID.
+pub struct SnapshotTransformer {
+    input_definitions: SchemaDefinitions,
+    output_definitions: Option<SchemaDefinitions>,
+    field_transforms: HashMap<ComponentId, Vec<FieldTransform>>,
+    remapped_components: HashMap<ComponentId, ComponentId>,
+    dropped_components: HashSet<ComponentId>,
+    filters: Vec<EntityFilter>,
+}
+
+impl SnapshotTransformer {
+    /// Creates a transformer that doesn't change any entities, using `definitions` to decode
+    /// and encode components that have field transforms.
+    pub fn new(definitions: SchemaDefinitions) -> Self {
+        SnapshotTransformer {
+            input_definitions: definitions,
+            output_definitions: None,
+            field_transforms: HashMap::new(),
+            remapped_components: HashMap::new(),
+            dropped_components: HashSet::new(),
+            filters: Vec::new(),
+        }
+    }
+
+    /// Encodes transformed components using `definitions`, e.g. those of the new version of
+    /// the schema, instead of the definitions they were decoded with.
+    pub fn with_output_definitions(mut self, definitions: SchemaDefinitions) -> Self {
+        self.output_definitions = Some(definitions);
+        self
+    }
+
+    /// Applies `transform` to the fields of every instance of a component. The fields are
+    /// given as a JSON object keyed by field name.
+    pub fn with_component_transform<F>(mut self, component_id: ComponentId, transform: F) -> Self
+    where
+        F: Fn(&mut Map<String, Value>) -> Result<(), String> + 'static,
+    {
+        self.field_transforms
+            .entry(component_id)
+            .or_default()
+            .push(Box::new(transform));
+        self
+    }
+
+    /// Moves the value of field `from` of a component to field `to`.
+    pub fn with_renamed_field<S: Into<String>, T: Into<String>>(
+        self,
+        component_id: ComponentId,
+        from: S,
+        to: T,
+    ) -> Self {
+        let from = from.into();
+        let to = to.into();
+        self.with_component_transform(component_id, move |fields| {
+            if let Some(value) = fields.remove(&from) {
+                fields.insert(to.clone(), value);
+            }
+            Ok(())
+        })
+    }
+
+    /// Sets field `field` of a component to `value` if it doesn't already have a value.
+    pub fn with_field_default<S: Into<String>>(
+        self,
+        component_id: ComponentId,
+        field: S,
+        value: Value,
+    ) -> Self {
+        let field = field.into();
+        self.with_component_transform(component_id, move |fields| {
+            fields.entry(field.clone()).or_insert_with(|| value.clone());
+            Ok(())
+        })
+    }
+
+    /// Removes a component from every entity, along with its entry in the entity's
+    /// `improbable.EntityAcl`.
+    pub fn with_dropped_component(mut self, component_id: ComponentId) -> Self {
+        self.dropped_components.insert(component_id);
+        self
+    }
+
+    /// Writes every instance of component `from` with the ID `to`, and moves its entry in the
+    /// entity's `improbable.EntityAcl` to `to`.
+    ///
+    /// Unless the component also has field transforms, its data is copied as it is, so the
+    /// two components must have the same fields. Entities that already have component `to`
+    /// can't be transformed.
+    pub fn with_remapped_component(mut self, from: ComponentId, to: ComponentId) -> Self {
+        self.remapped_components.insert(from, to);
+        self
+    }
+
+    /// Only keeps the entities for which `filter` returns `true`.
+    pub fn with_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&SerializedEntity) -> bool + 'static,
+    {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Transforms a single entity, returning `None` if it's removed by a filter.
+    ///
+    /// Fails with `SnapshotError::InvalidData` if a component can't be transformed, or if a
+    /// remapped component would replace one the entity already has.
+    pub fn transform_entity(
+        &self,
+        entity: SerializedEntity,
+    ) -> Result<Option<SerializedEntity>, SnapshotError> {
+        if !self.filters.iter().all(|filter| filter(&entity)) {
+            return Ok(None);
+        }
+
+        let id = entity.id;
+        let mut components = Vec::with_capacity(entity.components.len());
+        for component in entity.components {
+            if self.dropped_components.contains(&component.id) {
+                continue;
+            }
+
+            let component = self.transform_component(component).map_err(|message| {
+                SnapshotError::InvalidData(format!("Entity {}: {}", id.id, message))
+            })?;
+            components.push(component);
+        }
+        components.sort_by_key(|component| component.id);
+
+        if let Some(pair) = components.windows(2).find(|pair| pair[0].id == pair[1].id) {
+            return Err(SnapshotError::InvalidData(format!(
+                "Entity {}: more than one component would be written with ID {}",
+                id.id, pair[0].id
+            )));
+        }
+
+        if !self.dropped_components.is_empty() || !self.remapped_components.is_empty() {
+            if let Some(acl) = components
+                .iter_mut()
+                .find(|component| component.id == ENTITY_ACL_COMPONENT_ID)
+            {
+                acl.data = self.rewrite_acl(&acl.data).map_err(|message| {
+                    SnapshotError::InvalidData(format!("Entity {}: ACL: {}", id.id, message))
+                })?;
+            }
+        }
+
+        Ok(Some(SerializedEntity { id, components }))
+    }
+
+    /// Transforms each of `entities` and passes the ones that aren't filtered out to `write`.
+    ///
+    /// Stops at the first error, whether it's from reading, transforming or writing.
+    pub fn transform_entities<I, W>(
+        &self,
+        entities: I,
+        mut write: W,
+    ) -> Result<TransformSummary, SnapshotError>
+    where
+        I: IntoIterator<Item = Result<SerializedEntity, SnapshotError>>,
+        W: FnMut(&SerializedEntity) -> Result<(), SnapshotError>,
+    {
+        let mut summary = TransformSummary::default();
+        for entity in entities {
+            summary.entities_read += 1;
+            if let Some(entity) = self.transform_entity(entity?)? {
+                write(&entity)?;
+                summary.entities_written += 1;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Transforms the snapshot at `input`, writing the result to `output`.
+    pub fn transform_snapshot<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input: P,
+        output: Q,
+    ) -> Result<TransformSummary, SnapshotError> {
+        // Empty registries keep every component in its serialized form.
+        let mut input = SnapshotInputStream::with_registry(input, &ComponentRegistry::new())?;
+        let mut output = SnapshotOutputStream::with_registry(output, &ComponentRegistry::new())?;
+        let entities = input.serialized_entities();
+        self.transform_entities(entities, |entity| output.write_serialized(entity))
+    }
+
+    fn transform_component(
+        &self,
+        component: SerializedComponent,
+    ) -> Result<SerializedComponent, String> {
+        let output_id = self
+            .remapped_components
+            .get(&component.id)
+            .cloned()
+            .unwrap_or(component.id);
+
+        let transforms = match self.field_transforms.get(&component.id) {
+            Some(transforms) => transforms,
+            None => {
+                return Ok(SerializedComponent {
+                    id: output_id,
+                    data: component.data,
+                })
+            }
+        };
+
+        let mut fields = match json::component_to_json(&self.input_definitions, &component)? {
+            Value::Object(fields) => fields,
+            _ => unreachable!("Components are always converted to JSON objects"),
+        };
+        for transform in transforms {
+            transform(&mut fields)
+                .map_err(|message| format!("component {}: {}", component.id, message))?;
+        }
+
+        let output_definitions = self
+            .output_definitions
+            .as_ref()
+            .unwrap_or(&self.input_definitions);
+        json::component_from_json(output_definitions, output_id, &Value::Object(fields))
+    }
+
+    // Removes the write ACL entries of dropped components from the data of an
+    // `improbable.EntityAcl`, and moves those of remapped components to their new IDs.
+    fn rewrite_acl(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut acl = SchemaComponentData::new();
+        let mut rewritten = SchemaComponentData::new();
+        let result = acl.fields_mut().merge_from_buffer(data).and_then(|_| {
+            let fields = acl.fields();
+            let mut output = rewritten.fields_mut();
+            fields.shallow_copy_field(READ_ACL_FIELD, &mut output);
+
+            let entries = fields.field::<SchemaObject>(COMPONENT_WRITE_ACL_FIELD);
+            let mut written = HashSet::new();
+            for index in 0..entries.count() {
+                let entry = entries.index(index);
+                let component_id = entry.field::<SchemaUint32>(1).get_or_default();
+                if self.dropped_components.contains(&component_id) {
+                    continue;
+                }
+
+                let component_id = self
+                    .remapped_components
+                    .get(&component_id)
+                    .cloned()
+                    .unwrap_or(component_id);
+                if !written.insert(component_id) {
+                    return Err(format!(
+                        "more than one write ACL entry would be written for component {}",
+                        component_id
+                    ));
+                }
+
+                let mut rewritten_entry = output
+                    .field::<SchemaObject>(COMPONENT_WRITE_ACL_FIELD)
+                    .add();
+                rewritten_entry.field::<SchemaUint32>(1).add(component_id);
+                entry.shallow_copy_field(2, &mut rewritten_entry);
+            }
+
+            // The rewritten ACL shares objects with `acl`, so it must be serialized first.
+            output.serialize()
+        });
+        unsafe {
+            Schema_DestroyComponentData(rewritten.internal);
+            Schema_DestroyComponentData(acl.internal);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::worker::EntityId;
+
+    fn transformer() -> SnapshotTransformer {
+        SnapshotTransformer::new(
+            SchemaDefinitions::from_json(include_str!(
+                "../../../../spatialos-sdk-code-generator/data/test.sb.json"
+            ))
+            .unwrap(),
+        )
+    }
+
+    fn entity(id: i64, components: &[ComponentId]) -> SerializedEntity {
+        SerializedEntity {
+            id: EntityId::new(id),
+            components: components
+                .iter()
+                .map(|&id| SerializedComponent {
+                    id,
+                    data: vec![id as u8],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn components_are_dropped_and_remapped_without_decoding() {
+        let transformer = transformer()
+            .with_dropped_component(53)
+            .with_remapped_component(54, 60);
+
+        assert_eq!(
+            Some(SerializedEntity {
+                id: EntityId::new(1),
+                components: vec![
+                    SerializedComponent {
+                        id: 55,
+                        data: vec![55],
+                    },
+                    SerializedComponent {
+                        id: 60,
+                        data: vec![54],
+                    },
+                ],
+            }),
+            transformer
+                .transform_entity(entity(1, &[53, 54, 55]))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn remapping_onto_an_existing_component_is_an_error() {
+        let transformer = transformer().with_remapped_component(54, 60);
+
+        match transformer.transform_entity(entity(1, &[54, 60])) {
+            Err(SnapshotError::InvalidData(message)) => assert_eq!(
+                "Entity 1: more than one component would be written with ID 60",
+                message
+            ),
+            other => panic!("Expected an InvalidData error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn filtered_entities_are_not_written() {
+        let transformer = transformer()
+            .with_filter(|entity| entity.id.id % 2 == 1)
+            .with_filter(|entity| !entity.components.is_empty());
+        let entities = vec![
+            Ok(entity(1, &[54])),
+            Ok(entity(2, &[54])),
+            Ok(entity(3, &[])),
+        ];
+
+        let mut written = Vec::new();
+        let summary = transformer
+            .transform_entities(entities, |entity| {
+                written.push(entity.id);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(vec![EntityId::new(1)], written);
+        assert_eq!(
+            TransformSummary {
+                entities_read: 3,
+                entities_written: 1,
+            },
+            summary
+        );
+    }
+
+    #[test]
+    fn write_errors_stop_the_transform() {
+        let entities = vec![Ok(entity(1, &[])), Ok(entity(2, &[]))];
+        let mut calls = 0;
+        let result = transformer().transform_entities(entities, |_| {
+            calls += 1;
+            Err(SnapshotError::BadState("Disk full".to_owned()))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(1, calls);
+    }
+}
@@ -0,0 +1,303 @@
+//! Checking snapshots for problems that would stop the runtime from loading them.
+//!
+//! A snapshot can be read back without errors and still be rejected by the runtime, e.g.
+//! because two entities share an ID or an entity has no `Position`. [`validate`] streams
+//! through a snapshot and reports each of these problems along with the entity it was found on.
+//!
+//! [`validate`]: fn.validate.html
+
+use crate::worker::{
+    component::{ComponentId, ComponentRegistry},
+    entity_builder::{ENTITY_ACL_COMPONENT_ID, POSITION_COMPONENT_ID},
+    internal::schema::{SchemaComponentData, SchemaObject, SchemaUint32},
+    internal::schema::{SchemaObjectField, SchemaPrimitiveField},
+    snapshot::{SerializedEntity, SnapshotError, SnapshotInputStream},
+    EntityId,
+};
+use spatialos_sdk_sys::worker::Schema_DestroyComponentData;
+use std::{collections::HashSet, fmt, path::Path};
+
+/// The components every entity must have to be loaded by the runtime.
+const REQUIRED_COMPONENTS: [(ComponentId, &str); 2] = [
+    (POSITION_COMPONENT_ID, "improbable.Position"),
+    (ENTITY_ACL_COMPONENT_ID, "improbable.EntityAcl"),
+];
+
+/// The field of `improbable.EntityAcl` that maps component IDs to their write ACL.
+pub(crate) const COMPONENT_WRITE_ACL_FIELD: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// The entity ID is not positive.
+    InvalidEntityId,
+
+    /// An earlier entity in the snapshot has the same ID.
+    DuplicateEntityId,
+
+    /// The entity doesn't have a component that every entity requires.
+    MissingComponent(ComponentId),
+
+    /// The entity has a component that isn't known to the validator.
+    UnknownComponent(ComponentId),
+
+    /// The entity's ACL grants write access to a component that the entity doesn't have.
+    AclForMissingComponent(ComponentId),
+
+    /// The entity's ACL couldn't be decoded.
+    InvalidAcl(String),
+}
+
+/// A problem with a single entity in a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub entity_id: EntityId,
+    pub kind: ValidationErrorKind,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Entity {}: ", self.entity_id.id)?;
+        match &self.kind {
+            ValidationErrorKind::InvalidEntityId => write!(f, "entity IDs must be positive"),
+            ValidationErrorKind::DuplicateEntityId => write!(f, "duplicate entity ID"),
+            ValidationErrorKind::MissingComponent(id) => {
+                let name = REQUIRED_COMPONENTS
+                    .iter()
+                    .find(|(required_id, _)| required_id == id)
+                    .map(|(_, name)| *name)
+                    .unwrap_or("component");
+                write!(f, "missing required {} ({})", name, id)
+            }
+            ValidationErrorKind::UnknownComponent(id) => write!(f, "unknown component {}", id),
+            ValidationErrorKind::AclForMissingComponent(id) => write!(
+                f,
+                "ACL grants write access to component {}, which the entity doesn't have",
+                id
+            ),
+            ValidationErrorKind::InvalidAcl(message) => write!(f, "invalid ACL: {}", message),
+        }
+    }
+}
+
+/// The result of validating a snapshot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The number of entities that were checked.
+    pub entity_count: usize,
+
+    /// The problems that were found, in the order the entities appear in the snapshot.
+    pub errors: Vec<ValidationError>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Checks entities one at a time, keeping track of the IDs it has seen.
+#[derive(Debug, Clone)]
+pub struct Validator {
+    known_components: HashSet<ComponentId>,
+    seen_ids: HashSet<EntityId>,
+}
+
+impl Validator {
+    /// Creates a validator that treats the components in the global registry as known.
+    pub fn new() -> Self {
+        Validator::with_known_components(ComponentRegistry::global().component_ids())
+    }
+
+    /// Creates a validator that treats `known_components` as the known components, e.g. the
+    /// components defined in a schema bundle.
+    pub fn with_known_components<I: IntoIterator<Item = ComponentId>>(known_components: I) -> Self {
+        Validator {
+            known_components: known_components.into_iter().collect(),
+            seen_ids: HashSet::new(),
+        }
+    }
+
+    /// Returns the problems with `entity`.
+    pub fn check_entity(&mut self, entity: &SerializedEntity) -> Vec<ValidationError> {
+        let mut kinds = Vec::new();
+
+        if !entity.id.is_valid() {
+            kinds.push(ValidationErrorKind::InvalidEntityId);
+        }
+        if !self.seen_ids.insert(entity.id) {
+            kinds.push(ValidationErrorKind::DuplicateEntityId);
+        }
+
+        let has_component = |id| entity.components.iter().any(|component| component.id == id);
+        for &(id, _) in REQUIRED_COMPONENTS.iter() {
+            if !has_component(id) {
+                kinds.push(ValidationErrorKind::MissingComponent(id));
+            }
+        }
+
+        for component in &entity.components {
+            if !self.known_components.contains(&component.id) {
+                kinds.push(ValidationErrorKind::UnknownComponent(component.id));
+            }
+        }
+
+        if let Some(acl) = entity
+            .components
+            .iter()
+            .find(|component| component.id == ENTITY_ACL_COMPONENT_ID)
+        {
+            match write_acl_components(&acl.data) {
+                Ok(ids) => kinds.extend(
+                    ids.into_iter()
+                        .filter(|&id| !has_component(id))
+                        .map(ValidationErrorKind::AclForMissingComponent),
+                ),
+                Err(message) => kinds.push(ValidationErrorKind::InvalidAcl(message)),
+            }
+        }
+
+        kinds
+            .into_iter()
+            .map(|kind| ValidationError {
+                entity_id: entity.id,
+                kind,
+            })
+            .collect()
+    }
+
+    /// Checks every entity in `entities`, stopping at the first error reading them.
+    pub fn check_entities<I>(mut self, entities: I) -> Result<ValidationReport, SnapshotError>
+    where
+        I: IntoIterator<Item = Result<SerializedEntity, SnapshotError>>,
+    {
+        let mut report = ValidationReport::default();
+        for entity in entities {
+            report.errors.extend(self.check_entity(&entity?));
+            report.entity_count += 1;
+        }
+        Ok(report)
+    }
+
+    /// Checks every entity in the snapshot at `path`.
+    pub fn check_snapshot<P: AsRef<Path>>(
+        self,
+        path: P,
+    ) -> Result<ValidationReport, SnapshotError> {
+        // Reading with an empty registry keeps every component in its serialized form, so
+        // components without a vtable can still be read and reported.
+        let mut stream = SnapshotInputStream::with_registry(path, &ComponentRegistry::new())?;
+        self.check_entities(stream.serialized_entities())
+    }
+}
+
+impl Default for Validator {
+    fn default() -> Self {
+        Validator::new()
+    }
+}
+
+/// Checks the snapshot at `path`, treating the components in the global registry as known.
+pub fn validate<P: AsRef<Path>>(path: P) -> Result<ValidationReport, SnapshotError> {
+    Validator::new().check_snapshot(path)
+}
+
+// Returns the IDs of the components that an `improbable.EntityAcl` grants write access to.
+fn write_acl_components(data: &[u8]) -> Result<Vec<ComponentId>, String> {
+    let mut acl = SchemaComponentData::new();
+    let result = acl.fields_mut().merge_from_buffer(data).map(|_| {
+        let fields = acl.fields();
+        let entries = fields.field::<SchemaObject>(COMPONENT_WRITE_ACL_FIELD);
+        (0..entries.count())
+            .map(|index| {
+                entries
+                    .index(index)
+                    .field::<SchemaUint32>(1)
+                    .get_or_default()
+            })
+            .collect()
+    });
+    unsafe { Schema_DestroyComponentData(acl.internal) };
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::worker::snapshot::SerializedComponent;
+
+    const METADATA_COMPONENT_ID: ComponentId = 53;
+
+    fn entity(id: i64, components: &[ComponentId]) -> SerializedEntity {
+        SerializedEntity {
+            id: EntityId::new(id),
+            components: components
+                .iter()
+                .map(|&id| SerializedComponent {
+                    id,
+                    data: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn kinds(errors: Vec<ValidationError>) -> Vec<ValidationErrorKind> {
+        errors.into_iter().map(|error| error.kind).collect()
+    }
+
+    #[test]
+    fn invalid_and_duplicate_ids_are_reported() {
+        let mut validator = Validator::with_known_components(vec![POSITION_COMPONENT_ID]);
+
+        assert!(validator
+            .check_entity(&entity(1, &[POSITION_COMPONENT_ID]))
+            .iter()
+            .all(|error| error.kind
+                == ValidationErrorKind::MissingComponent(ENTITY_ACL_COMPONENT_ID)));
+        assert!(
+            kinds(validator.check_entity(&entity(1, &[POSITION_COMPONENT_ID])))
+                .contains(&ValidationErrorKind::DuplicateEntityId)
+        );
+        assert!(
+            kinds(validator.check_entity(&entity(0, &[POSITION_COMPONENT_ID])))
+                .contains(&ValidationErrorKind::InvalidEntityId)
+        );
+    }
+
+    #[test]
+    fn missing_and_unknown_components_are_reported() {
+        let mut validator = Validator::with_known_components(vec![POSITION_COMPONENT_ID]);
+
+        assert_eq!(
+            vec![
+                ValidationErrorKind::MissingComponent(POSITION_COMPONENT_ID),
+                ValidationErrorKind::MissingComponent(ENTITY_ACL_COMPONENT_ID),
+                ValidationErrorKind::UnknownComponent(METADATA_COMPONENT_ID),
+            ],
+            kinds(validator.check_entity(&entity(1, &[METADATA_COMPONENT_ID])))
+        );
+    }
+
+    #[test]
+    fn read_errors_stop_validation() {
+        let entities = vec![
+            Ok(entity(1, &[])),
+            Err(SnapshotError::InvalidData("Corrupt".to_owned())),
+        ];
+        match Validator::with_known_components(vec![]).check_entities(entities) {
+            Err(SnapshotError::InvalidData(_)) => {}
+            other => panic!("Expected InvalidData, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_are_displayed_with_their_entity() {
+        let error = ValidationError {
+            entity_id: EntityId::new(3),
+            kind: ValidationErrorKind::MissingComponent(POSITION_COMPONENT_ID),
+        };
+        assert_eq!(
+            "Entity 3: missing required improbable.Position (54)",
+            error.to_string()
+        );
+    }
+}
@@ -0,0 +1,502 @@
+//! A local cache of the entities and components the worker has checked out.
+//!
+//! A [`View`] is kept up to date by passing it every [`OpList`] received from the
+//! connection. It tracks which entities and components are in the worker's view, the
+//! latest data of every registered component type, and the worker's authority over each
+//! component.
+//!
+//! # Examples
+//!
+//! ```
+//! use spatialos_sdk::worker::{
+//!     mock::MockOpList, view::View, Authority, EntityId,
+//! };
+//!
+//! let mut view = View::new();
+//!
+//! let entity_id = EntityId::new(1);
+//! view.process(&MockOpList::new().with_add_entity(entity_id).into_op_list());
+//! assert!(view.contains_entity(entity_id));
+//!
+//! // Authority is only tracked for components that have been added to the entity.
+//! view.process(
+//!     &MockOpList::new()
+//!         .with_authority_change(entity_id, 54, Authority::Authoritative)
+//!         .into_op_list(),
+//! );
+//! assert_eq!(None, view.authority(entity_id, 54));
+//! ```
+
+use crate::worker::{
+    component::{Component, ComponentData, ComponentId, UserHandle},
+    entity::Entity,
+    entity_builder::POSITION_COMPONENT_ID,
+    op::*,
+    query::{self, EntityQuery, LocalQueryResult, QueryableEntity},
+    Authority, EntityId,
+};
+use std::{
+    any::Any,
+    collections::{hash_map, HashMap},
+    sync::Arc,
+};
+
+/// An entity/component cache maintained from ops.
+///
+/// Component data is only cached for component types that have been registered with
+/// [`register`](#method.register). The presence of components and the worker's authority
+/// over them is tracked for every component, registered or not.
+///
+/// Component data is stored behind an `Arc`, so it can be cheaply shared with other
+/// systems. Updates are applied with [`ComponentData::merge`], which clones the data first
+/// if it is still shared.
+#[derive(Default)]
+pub struct View {
+    entities: HashMap<EntityId, EntityState>,
+    storages: HashMap<ComponentId, Box<dyn ComponentStorage>>,
+}
+
+#[derive(Debug, Default)]
+struct EntityState {
+    // The components that are currently in view, along with the worker's authority over them.
+    components: HashMap<ComponentId, Authority>,
+}
+
+impl View {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Starts caching the data of components of type `C`.
+    ///
+    /// Only data received after registering is cached, so components should be
+    /// registered before the first op list is processed.
+    pub fn register<C>(&mut self)
+    where
+        C: Component + ComponentData<C> + Clone + 'static,
+        C::Update: Clone,
+    {
+        self.storages
+            .entry(C::ID)
+            .or_insert_with(|| Box::new(Storage::<C>::default()));
+    }
+
+    /// Applies every op in `op_list` to the view, in order.
+    pub fn process(&mut self, op_list: &OpList) {
+        for op in op_list {
+            self.process_op(&op);
+        }
+    }
+
+    /// Applies a single op to the view. Ops that don't affect the view are ignored.
+    pub fn process_op(&mut self, op: &WorkerOp<'_>) {
+        match op {
+            WorkerOp::AddEntity(op) => {
+                self.entities.entry(op.entity_id).or_default();
+            }
+            WorkerOp::RemoveEntity(op) => {
+                if let Some(entity) = self.entities.remove(&op.entity_id) {
+                    for component_id in entity.components.keys() {
+                        if let Some(storage) = self.storages.get_mut(component_id) {
+                            storage.remove(op.entity_id);
+                        }
+                    }
+                }
+            }
+            WorkerOp::AddComponent(op) => {
+                self.entities
+                    .entry(op.entity_id)
+                    .or_default()
+                    .components
+                    .entry(op.component_id)
+                    .or_insert(Authority::NotAuthoritative);
+                if let Some(storage) = self.storages.get_mut(&op.component_id) {
+                    storage.add(op);
+                }
+            }
+            WorkerOp::RemoveComponent(op) => {
+                if let Some(entity) = self.entities.get_mut(&op.entity_id) {
+                    entity.components.remove(&op.component_id);
+                }
+                if let Some(storage) = self.storages.get_mut(&op.component_id) {
+                    storage.remove(op.entity_id);
+                }
+            }
+            WorkerOp::ComponentUpdate(op) => {
+                if let Some(storage) = self.storages.get_mut(&op.component_id) {
+                    storage.update(op);
+                }
+            }
+            WorkerOp::AuthorityChange(op) => {
+                let authority = self
+                    .entities
+                    .get_mut(&op.entity_id)
+                    .and_then(|entity| entity.components.get_mut(&op.component_id));
+                if let Some(authority) = authority {
+                    *authority = op.authority;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn contains_entity(&self, entity_id: EntityId) -> bool {
+        self.entities.contains_key(&entity_id)
+    }
+
+    /// Returns an iterator over all entities in the view, in arbitrary order.
+    pub fn entities(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.entities.keys().cloned()
+    }
+
+    /// Returns `true` if the entity is in view and has a component with the given ID.
+    pub fn has_component(&self, entity_id: EntityId, component_id: ComponentId) -> bool {
+        self.entities
+            .get(&entity_id)
+            .is_some_and(|entity| entity.components.contains_key(&component_id))
+    }
+
+    /// Returns the cached data of component `C` on the entity.
+    ///
+    /// Returns `None` if the entity doesn't have the component, or if `C` hasn't been
+    /// registered.
+    pub fn get<C: Component + 'static>(&self, entity_id: EntityId) -> Option<&C> {
+        self.storage::<C>()
+            .and_then(|storage| storage.data.get(&entity_id))
+            .map(|data| &**data)
+    }
+
+    /// Returns a shared reference to the cached data of component `C` on the entity.
+    ///
+    /// This is the same as [`get`](#method.get), except that the data can be kept after the
+    /// view is updated.
+    pub fn get_arc<C: Component + 'static>(&self, entity_id: EntityId) -> Option<Arc<C>> {
+        self.storage::<C>()
+            .and_then(|storage| storage.data.get(&entity_id))
+            .cloned()
+    }
+
+    /// Returns an iterator over all entities that have cached data for component `C`.
+    pub fn entities_with<C: Component + 'static>(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.storage::<C>()
+            .into_iter()
+            .flat_map(|storage| storage.data.keys().cloned())
+    }
+
+    /// Returns an iterator over all entities that have cached data for component `C`,
+    /// along with the data.
+    pub fn iter<C: Component + 'static>(&self) -> impl Iterator<Item = (EntityId, &C)> + '_ {
+        self.storage::<C>()
+            .into_iter()
+            .flat_map(|storage| storage.data.iter().map(|(id, data)| (*id, &**data)))
+    }
+
+    /// Returns the worker's authority over a component, or `None` if the component isn't
+    /// in view.
+    pub fn authority(&self, entity_id: EntityId, component_id: ComponentId) -> Option<Authority> {
+        self.entities
+            .get(&entity_id)
+            .and_then(|entity| entity.components.get(&component_id))
+            .cloned()
+    }
+
+    /// Returns `true` if the worker has authority over component `C` on the entity,
+    /// including when authority loss is imminent.
+    pub fn is_authoritative<C: Component>(&self, entity_id: EntityId) -> bool {
+        self.authority(entity_id, C::ID)
+            .is_some_and(Authority::has_authority)
+    }
+
+    /// Evaluates `query` against the entities in the view, without sending it to the runtime.
+    ///
+    /// Evaluating a `Sphere` constraint requires `improbable.Position` to be registered with
+    /// the view. Snapshot results only include the components whose data is cached.
+    pub fn query(&self, query: &EntityQuery) -> Result<LocalQueryResult<Entity>, String> {
+        query.evaluate(self.entities.keys().map(|&entity_id| {
+            (
+                entity_id,
+                ViewEntity {
+                    view: self,
+                    entity_id,
+                },
+            )
+        }))
+    }
+
+    fn storage<C: Component + 'static>(&self) -> Option<&Storage<C>> {
+        self.storages
+            .get(&C::ID)
+            .and_then(|storage| storage.as_any().downcast_ref::<Storage<C>>())
+    }
+}
+
+// An entity in a view, as seen by the query evaluator.
+struct ViewEntity<'a> {
+    view: &'a View,
+    entity_id: EntityId,
+}
+
+impl<'a> QueryableEntity for ViewEntity<'a> {
+    type Projection = Entity;
+
+    fn has_component(&self, component_id: ComponentId) -> bool {
+        self.view.has_component(self.entity_id, component_id)
+    }
+
+    fn position(&self) -> Result<Option<(f64, f64, f64)>, String> {
+        if !self.has_component(POSITION_COMPONENT_ID) {
+            return Ok(None);
+        }
+
+        let handle = self
+            .view
+            .storages
+            .get(&POSITION_COMPONENT_ID)
+            .and_then(|storage| storage.handle(self.entity_id))
+            .ok_or_else(|| {
+                format!(
+                    "The position of entity {} isn't cached. Is improbable.Position registered with the view?",
+                    self.entity_id.id
+                )
+            })?;
+        query::decode_position(&handle.serialize_data()?).map(Some)
+    }
+
+    fn project(&self, component_ids: &[ComponentId]) -> Result<Entity, String> {
+        let mut entity = Entity::new();
+        for component_id in component_ids {
+            if let Some(handle) = self
+                .view
+                .storages
+                .get(component_id)
+                .and_then(|storage| storage.handle(self.entity_id))
+            {
+                entity.add_handle(handle)?;
+            }
+        }
+        Ok(entity)
+    }
+}
+
+trait ComponentStorage {
+    fn add(&mut self, op: &AddComponentOp<'_>);
+    fn update(&mut self, op: &ComponentUpdateOp<'_>);
+    fn remove(&mut self, entity_id: EntityId);
+    fn handle(&self, entity_id: EntityId) -> Option<UserHandle>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+struct Storage<C> {
+    data: HashMap<EntityId, Arc<C>>,
+}
+
+impl<C> Default for Storage<C> {
+    fn default() -> Self {
+        Storage {
+            data: HashMap::new(),
+        }
+    }
+}
+
+impl<C> ComponentStorage for Storage<C>
+where
+    C: Component + ComponentData<C> + Clone + 'static,
+    C::Update: Clone,
+{
+    fn add(&mut self, op: &AddComponentOp<'_>) {
+        if let Some(data) = op.get::<C>() {
+            self.data.insert(op.entity_id, Arc::new(data.clone()));
+        }
+    }
+
+    fn update(&mut self, op: &ComponentUpdateOp<'_>) {
+        if let (hash_map::Entry::Occupied(mut entry), Some(update)) =
+            (self.data.entry(op.entity_id), op.get::<C>())
+        {
+            Arc::make_mut(entry.get_mut()).merge(update.clone());
+        }
+    }
+
+    fn remove(&mut self, entity_id: EntityId) {
+        self.data.remove(&entity_id);
+    }
+
+    fn handle(&self, entity_id: EntityId) -> Option<UserHandle> {
+        self.data
+            .get(&entity_id)
+            .map(|data| UserHandle::component_data::<C>((**data).clone()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::worker::{
+        mock::{
+            test::{Counter, CounterUpdate},
+            MockOpList,
+        },
+        query::{Query, ResultType},
+    };
+
+    fn view() -> View {
+        let mut view = View::new();
+        view.register::<Counter>();
+        view
+    }
+
+    #[test]
+    fn components_are_added_updated_and_removed() {
+        let mut view = view();
+        let entity_id = EntityId::new(1);
+
+        view.process(
+            &MockOpList::new()
+                .with_add_entity(entity_id)
+                .with_add_component(entity_id, Counter { value: 1 })
+                .into_op_list(),
+        );
+        assert_eq!(Some(&Counter { value: 1 }), view.get::<Counter>(entity_id));
+        let before_update = view.get_arc::<Counter>(entity_id).unwrap();
+
+        view.process(
+            &MockOpList::new()
+                .with_component_update::<Counter>(entity_id, CounterUpdate { value: Some(4) })
+                .with_component_update::<Counter>(entity_id, CounterUpdate { value: None })
+                .into_op_list(),
+        );
+        assert_eq!(Some(&Counter { value: 4 }), view.get::<Counter>(entity_id));
+        assert_eq!(1, before_update.value);
+
+        view.process(
+            &MockOpList::new()
+                .with_remove_component(entity_id, Counter::ID)
+                .into_op_list(),
+        );
+        assert!(view.contains_entity(entity_id));
+        assert!(!view.has_component(entity_id, Counter::ID));
+        assert_eq!(None, view.get::<Counter>(entity_id));
+    }
+
+    #[test]
+    fn authority_is_tracked_per_entity_and_component() {
+        let mut view = view();
+        let first = EntityId::new(1);
+        let second = EntityId::new(2);
+
+        view.process(
+            &MockOpList::new()
+                .with_add_entity(first)
+                .with_add_entity(second)
+                .with_add_component(first, Counter { value: 1 })
+                .with_add_component(second, Counter { value: 2 })
+                .with_authority_change(first, Counter::ID, Authority::Authoritative)
+                .into_op_list(),
+        );
+        assert!(view.is_authoritative::<Counter>(first));
+        assert!(!view.is_authoritative::<Counter>(second));
+
+        view.process(
+            &MockOpList::new()
+                .with_authority_change(first, Counter::ID, Authority::AuthorityLossImminent)
+                .into_op_list(),
+        );
+        assert!(view.is_authoritative::<Counter>(first));
+        assert_eq!(
+            Some(Authority::AuthorityLossImminent),
+            view.authority(first, Counter::ID)
+        );
+
+        let mut with_counter = view.entities_with::<Counter>().collect::<Vec<_>>();
+        with_counter.sort();
+        assert_eq!(vec![first, second], with_counter);
+
+        view.process(&MockOpList::new().with_remove_entity(first).into_op_list());
+        assert!(!view.contains_entity(first));
+        assert!(!view.is_authoritative::<Counter>(first));
+        assert_eq!(
+            vec![second],
+            view.entities_with::<Counter>().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn authority_changes_for_components_not_in_view_are_ignored() {
+        let mut view = view();
+        let in_view = EntityId::new(1);
+        let out_of_view = EntityId::new(2);
+
+        view.process(
+            &MockOpList::new()
+                .with_add_entity(in_view)
+                .with_authority_change(in_view, Counter::ID, Authority::Authoritative)
+                .with_authority_change(out_of_view, Counter::ID, Authority::Authoritative)
+                .into_op_list(),
+        );
+
+        assert!(!view.contains_entity(out_of_view));
+        assert!(!view.has_component(in_view, Counter::ID));
+        assert_eq!(None, view.authority(in_view, Counter::ID));
+        assert_eq!(vec![in_view], view.entities().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unregistered_components_are_tracked_without_data() {
+        let mut view = View::new();
+        let entity_id = EntityId::new(1);
+
+        view.process(
+            &MockOpList::new()
+                .with_add_entity(entity_id)
+                .with_add_component(entity_id, Counter { value: 1 })
+                .into_op_list(),
+        );
+
+        assert!(view.has_component(entity_id, Counter::ID));
+        assert_eq!(None, view.get::<Counter>(entity_id));
+    }
+
+    #[test]
+    fn queries_are_evaluated_against_the_view() {
+        let mut view = view();
+        let first = EntityId::new(1);
+        let second = EntityId::new(2);
+
+        view.process(
+            &MockOpList::new()
+                .with_add_entity(first)
+                .with_add_entity(second)
+                .with_add_component(first, Counter { value: 1 })
+                .into_op_list(),
+        );
+
+        match view
+            .query(&EntityQuery::new(
+                Query::has::<Counter>(),
+                ResultType::Count,
+            ))
+            .unwrap()
+        {
+            LocalQueryResult::Count(count) => assert_eq!(1, count),
+            other => panic!("Expected a count result, got {:?}", other),
+        }
+        match view
+            .query(&EntityQuery::new(
+                Query::has::<Counter>(),
+                ResultType::snapshot_of::<Counter>(),
+            ))
+            .unwrap()
+        {
+            LocalQueryResult::Snapshot(entities) => {
+                assert_eq!(1, entities.len());
+                assert_eq!(first, entities[0].0);
+                assert_eq!(Some(&Counter { value: 1 }), entities[0].1.get::<Counter>());
+            }
+            other => panic!("Expected a snapshot result, got {:?}", other),
+        }
+    }
+}
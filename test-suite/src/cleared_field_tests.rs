@@ -0,0 +1,56 @@
+use crate::generated::example::*;
+use spatialos_sdk::worker::component::{Component, ComponentData};
+use std::collections::BTreeMap;
+
+fn populated() -> CollectionTest {
+    let mut names = BTreeMap::new();
+    names.insert(1, "one".to_owned());
+    CollectionTest {
+        maybe_value: Some(1),
+        values: vec![1, 2],
+        names,
+    }
+}
+
+#[test]
+fn merging_empty_values_clears_fields() {
+    let mut data = populated();
+    data.merge(CollectionTestUpdate {
+        maybe_value: Some(None),
+        values: Some(Vec::new()),
+        names: Some(BTreeMap::new()),
+    });
+
+    assert_eq!(None, data.maybe_value);
+    assert!(data.values.is_empty());
+    assert!(data.names.is_empty());
+}
+
+#[test]
+fn merging_unset_fields_leaves_data_unchanged() {
+    let mut data = populated();
+    data.merge(CollectionTestUpdate::default());
+
+    assert_eq!(Some(1), data.maybe_value);
+    assert_eq!(vec![1, 2], data.values);
+    assert_eq!(1, data.names.len());
+}
+
+#[test]
+fn cleared_fields_are_serialized_correctly() {
+    let update = CollectionTestUpdate {
+        maybe_value: Some(None),
+        values: Some(Vec::new()),
+        names: None,
+    };
+
+    let serialized = CollectionTest::to_update(&update).unwrap();
+    assert!(serialized.is_field_cleared(1));
+    assert!(serialized.is_field_cleared(2));
+    assert!(!serialized.is_field_cleared(3));
+
+    let deserialized = CollectionTest::from_update(&serialized).unwrap();
+    assert_eq!(Some(None), deserialized.maybe_value);
+    assert_eq!(Some(Vec::new()), deserialized.values);
+    assert_eq!(None, deserialized.names);
+}
@@ -0,0 +1,51 @@
+use crate::generated::example::*;
+use spatialos_sdk::worker::{
+    commands::CommandParameters,
+    component::{Command, Component},
+    connection::Connection,
+    mock::{MockConnection, MockOpList},
+    op::StatusCode,
+    requests::TrackedConnection,
+    EntityId,
+};
+
+#[test]
+fn command_requests_round_trip_through_component_enum() {
+    let request = ExampleTestCommand::into_request(CommandData { value: 5 });
+    assert_eq!(
+        ExampleTestCommand::INDEX,
+        Example::get_request_command_index(&request)
+    );
+
+    let data = ExampleTestCommand::get_request(&request).unwrap();
+    assert_eq!(5, data.value);
+}
+
+#[test]
+fn command_responses_are_resolved_with_the_command_response_type() {
+    let mut connection = TrackedConnection::new(MockConnection::new("TestWorker"));
+    let entity_id = EntityId::new(1);
+
+    let mut response = connection.send_command::<ExampleTestCommand>(
+        entity_id,
+        CommandData { value: 1 },
+        CommandParameters::new(),
+    );
+
+    let sent = &connection.connection().command_requests[0];
+    assert_eq!(Example::ID, sent.component_id);
+    assert_eq!(ExampleTestCommand::INDEX, sent.command_index);
+    let request_id = sent.request_id;
+
+    connection
+        .connection_mut()
+        .push_op_list(MockOpList::new().with_command_response::<Example>(
+            request_id,
+            entity_id,
+            StatusCode::Success(ExampleTestCommand::into_response(CommandData { value: 2 })),
+        ));
+    connection.get_op_list(0);
+
+    let data = response.try_take().unwrap().unwrap();
+    assert_eq!(2, data.value);
+}
@@ -0,0 +1,76 @@
+use crate::generated::example::*;
+use spatialos_sdk::worker::{
+    component::{Component, ComponentUpdate, Event},
+    connection::Connection,
+    mock::{MockConnection, MockOpList},
+    op::WorkerOp,
+    EntityId,
+};
+
+#[test]
+fn merging_updates_appends_events() {
+    let mut update = ExampleUpdate {
+        x: Some(1.0),
+        test_event: vec![CommandData { value: 1 }],
+    };
+    update.merge(ExampleUpdate {
+        x: None,
+        test_event: vec![CommandData { value: 2 }],
+    });
+
+    assert_eq!(Some(1.0), update.x);
+    assert_eq!(
+        vec![1, 2],
+        ExampleTestEvent::get(&update)
+            .iter()
+            .map(|event| event.value)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn events_are_exposed_on_component_update_ops() {
+    let mut connection = MockConnection::new("TestWorker");
+    connection.push_op_list(MockOpList::new().with_component_update::<Example>(
+        EntityId::new(1),
+        ExampleUpdate {
+            x: None,
+            test_event: vec![CommandData { value: 3 }],
+        },
+    ));
+
+    let op_list = connection.get_op_list(0);
+    let mut received = Vec::new();
+    for op in &op_list {
+        if let WorkerOp::ComponentUpdate(op) = op {
+            received.extend(
+                op.events::<ExampleTestEvent>()
+                    .iter()
+                    .map(|event| event.value),
+            );
+        }
+    }
+
+    assert_eq!(vec![3], received);
+}
+
+#[test]
+fn events_are_serialized_correctly() {
+    let update = ExampleUpdate {
+        x: Some(2.0),
+        test_event: vec![CommandData { value: 4 }, CommandData { value: 5 }],
+    };
+
+    let serialized = Example::to_update(&update).unwrap();
+    let deserialized = Example::from_update(&serialized).unwrap();
+
+    assert_eq!(Some(2.0), deserialized.x);
+    assert_eq!(
+        vec![4, 5],
+        deserialized
+            .test_event
+            .iter()
+            .map(|event| event.value)
+            .collect::<Vec<_>>()
+    );
+}
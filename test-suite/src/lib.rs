@@ -1,7 +1,19 @@
 #[rustfmt::skip]
 pub mod generated;
 
+#[cfg(test)]
+pub mod cleared_field_tests;
+#[cfg(test)]
+pub mod command_tests;
+#[cfg(test)]
+pub mod component_event_tests;
 #[cfg(test)]
 pub mod entity_builder_tests;
 #[cfg(test)]
+pub mod query_tests;
+#[cfg(test)]
+pub mod schema_value_tests;
+#[cfg(test)]
+pub mod serde_tests;
+#[cfg(test)]
 pub mod snapshot_integration_tests;
@@ -0,0 +1,28 @@
+use crate::generated::{example::Rotate, improbable::*};
+use spatialos_sdk::worker::{
+    component::Component,
+    query::{Query, QueryConstraint, ResultType},
+};
+
+#[test]
+fn snapshot_result_type_is_derived_from_component_types() {
+    assert_eq!(
+        ResultType::Snapshot(vec![Position::ID, Rotate::ID]),
+        ResultType::snapshot_of::<(Position, Rotate)>()
+    );
+    assert_eq!(
+        ResultType::Snapshot(vec![Position::ID]),
+        ResultType::snapshot_of::<Position>()
+    );
+}
+
+#[test]
+fn component_constraints_use_component_ids() {
+    assert_eq!(
+        QueryConstraint::And(vec![
+            QueryConstraint::Component(Position::ID),
+            QueryConstraint::Not(Box::new(QueryConstraint::Component(Rotate::ID))),
+        ]),
+        Query::has::<Position>() & !Query::has::<Rotate>()
+    );
+}
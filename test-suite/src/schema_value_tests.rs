@@ -0,0 +1,93 @@
+use crate::generated::example::*;
+use spatialos_sdk::worker::{
+    component::Component,
+    schema_value::{SchemaDefinitions, SchemaUpdateValue, SchemaValue},
+};
+use std::collections::BTreeMap;
+
+fn definitions() -> SchemaDefinitions {
+    SchemaDefinitions::from_json(include_str!(
+        "../../spatialos-sdk-code-generator/data/test.sb.json"
+    ))
+    .unwrap()
+}
+
+fn rotate() -> Rotate {
+    Rotate {
+        angle: 90.0,
+        center: Vector3d {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        },
+        radius: 5.0,
+    }
+}
+
+#[test]
+fn component_data_is_decoded_by_field_id() {
+    let definitions = definitions();
+    let data = Rotate::to_data(&rotate()).unwrap();
+
+    let value = definitions
+        .decode_component_data(Rotate::ID, &data)
+        .unwrap();
+
+    assert_eq!(Some(&SchemaValue::Double(90.0)), value.field(1));
+    assert_eq!(
+        Some(&SchemaValue::Double(2.0)),
+        value.field(2).and_then(|center| center.field(2))
+    );
+    assert_eq!(Some(&SchemaValue::Double(5.0)), value.field(3));
+}
+
+#[test]
+fn component_data_round_trips_through_schema_values() {
+    let definitions = definitions();
+    let data = Rotate::to_data(&rotate()).unwrap();
+    let value = definitions
+        .decode_component_data(Rotate::ID, &data)
+        .unwrap();
+
+    let encoded = definitions
+        .encode_component_data(Rotate::ID, &value)
+        .unwrap();
+    let decoded = Rotate::from_data(&encoded).unwrap();
+
+    assert_eq!(90.0, decoded.angle);
+    assert_eq!(3.0, decoded.center.z);
+    assert_eq!(5.0, decoded.radius);
+}
+
+#[test]
+fn component_updates_only_contain_set_fields() {
+    let definitions = definitions();
+    let mut fields = BTreeMap::new();
+    fields.insert(1, SchemaValue::Float(2.0));
+    let value = SchemaUpdateValue {
+        fields,
+        events: BTreeMap::new(),
+    };
+
+    let update = definitions
+        .encode_component_update(Example::ID, &value)
+        .unwrap();
+    assert_eq!(Some(2.0), Example::from_update(&update).unwrap().x);
+    assert_eq!(
+        value,
+        definitions
+            .decode_component_update(Example::ID, &update)
+            .unwrap()
+    );
+}
+
+#[test]
+fn mismatched_values_are_rejected() {
+    let definitions = definitions();
+    let mut fields = BTreeMap::new();
+    fields.insert(1, SchemaValue::String("not a float".to_owned()));
+
+    assert!(definitions
+        .encode_component_data(Example::ID, &SchemaValue::Object(fields))
+        .is_err());
+}
@@ -0,0 +1,68 @@
+use crate::generated::example::*;
+use spatialos_sdk::worker::EntityId;
+use std::collections::BTreeMap;
+
+#[test]
+fn component_data_round_trips_through_json() {
+    let mut names = BTreeMap::new();
+    names.insert(1, "one".to_owned());
+    let data = CollectionTest {
+        maybe_value: None,
+        values: vec![1, 2],
+        names,
+    };
+
+    let json = serde_json::to_string(&data).unwrap();
+    assert_eq!(
+        r#"{"maybe_value":null,"values":[1,2],"names":{"1":"one"}}"#,
+        json
+    );
+
+    let copy: CollectionTest = serde_json::from_str(&json).unwrap();
+    assert_eq!(data.maybe_value, copy.maybe_value);
+    assert_eq!(data.values, copy.values);
+    assert_eq!(data.names, copy.names);
+}
+
+#[test]
+fn entity_ids_and_enums_are_serialized_as_values() {
+    let json = serde_json::to_string(&EntityIdTest {
+        eid: EntityId::new(7),
+    })
+    .unwrap();
+    assert_eq!(r#"{"eid":7}"#, json);
+
+    let json = serde_json::to_string(&EnumTestComponent {
+        test: TestEnum::SECOND,
+    })
+    .unwrap();
+    assert_eq!(r#"{"test":"SECOND"}"#, json);
+}
+
+#[test]
+fn updates_only_contain_set_fields_and_events() {
+    let update = CollectionTestUpdate {
+        maybe_value: Some(None),
+        ..CollectionTestUpdate::default()
+    };
+    let json = serde_json::to_string(&update).unwrap();
+    assert_eq!(r#"{"maybe_value":null}"#, json);
+
+    let copy: CollectionTestUpdate = serde_json::from_str(&json).unwrap();
+    assert_eq!(Some(None), copy.maybe_value);
+    assert_eq!(None, copy.values);
+    assert_eq!(None, copy.names);
+
+    let update: ExampleUpdate = serde_json::from_str(r#"{"test_event":[{"value":2}]}"#).unwrap();
+    assert_eq!(None, update.x);
+    assert_eq!(2, update.test_event[0].value);
+}
+
+#[test]
+fn command_requests_are_tagged_with_the_command_name() {
+    let request = ExampleCommandRequest::TestCommand(CommandData { value: 1 });
+    let json = serde_json::to_string(&request).unwrap();
+    assert_eq!(r#"{"TestCommand":{"value":1}}"#, json);
+
+    let ExampleCommandRequest::TestCommand(_) = serde_json::from_str(&json).unwrap();
+}
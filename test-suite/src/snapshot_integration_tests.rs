@@ -1,17 +1,21 @@
 use approx;
 use spatialos_sdk::worker::{entity::Entity, snapshot::*, EntityId};
-use std::env;
 
-use crate::generated::improbable::*;
+use crate::generated::{
+    example::{Rotate, Vector3d},
+    improbable::*,
+};
+use spatialos_sdk::worker::component::{Component, ComponentId, ComponentRegistry};
 use spatialos_sdk::worker::entity_builder::EntityBuilder;
+use spatialos_sdk::worker::internal::schema::SchemaComponentData;
+use spatialos_sdk::worker::query::{EntityQuery, LocalQueryResult, Query, ResultType};
+use spatialos_sdk::worker::schema_value::SchemaDefinitions;
 
 #[test]
 pub fn writing_invalid_entity_returns_error() {
-    let snapshot_path = env::temp_dir().join("test2.snapshot");
-
     let entity = Entity::new();
 
-    let error = SnapshotOutputStream::new(snapshot_path)
+    let error = SnapshotOutputStream::in_memory()
         .expect("Error")
         .write_entity(EntityId::new(1), &entity);
 
@@ -20,23 +24,23 @@ pub fn writing_invalid_entity_returns_error() {
 
 #[test]
 pub fn create_and_read_snapshot() {
-    let snapshot_path = env::temp_dir().join("test.snapshot");
-
     let entity = get_test_entity().expect("Error");
 
-    {
-        SnapshotOutputStream::new(snapshot_path.clone())
-            .expect("Error")
+    let bytes = {
+        let mut snapshot = SnapshotOutputStream::in_memory().expect("Error");
+        snapshot
             .write_entity(EntityId::new(1), &entity)
             .expect("Error");
-    }
+        snapshot.into_bytes().expect("Error")
+    };
 
     {
-        let mut snapshot = SnapshotInputStream::new(snapshot_path).expect("Error");
+        let mut snapshot = SnapshotInputStream::from_reader(&bytes[..]).expect("Error");
 
         assert!(snapshot.has_next());
 
-        let entity = snapshot.read_entity().expect("Error");
+        let (id, entity) = snapshot.read_entity().expect("Error");
+        assert_eq!(EntityId::new(1), id);
 
         let position = entity.get::<Position>();
         assert!(position.is_some());
@@ -56,6 +60,338 @@ pub fn create_and_read_snapshot() {
     }
 }
 
+#[test]
+pub fn snapshot_can_be_iterated() {
+    let mut snapshot = SnapshotOutputStream::in_memory().expect("Error");
+    for id in 1..=3 {
+        let entity = get_test_entity().expect("Error");
+        snapshot
+            .write_entity(EntityId::new(id), &entity)
+            .expect("Error");
+    }
+    let bytes = snapshot.into_bytes().expect("Error");
+
+    let ids = SnapshotInputStream::from_reader(&bytes[..])
+        .expect("Error")
+        .map(|result| result.expect("Error").0)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![EntityId::new(1), EntityId::new(2), EntityId::new(3)],
+        ids
+    );
+}
+
+#[test]
+pub fn snapshot_converts_to_and_from_json() {
+    let definitions = SchemaDefinitions::from_json(include_str!(
+        "../../spatialos-sdk-code-generator/data/test.sb.json"
+    ))
+    .expect("Error");
+    let document = serde_json::json!({
+        "entities": [{
+            "id": 4,
+            "components": {
+                "example.Rotate": {
+                    "angle": 90.0,
+                    "center": { "x": 1.0, "y": 2.0, "z": 3.0 },
+                    "radius": 5.0,
+                },
+            },
+        }],
+    });
+
+    let bytes = {
+        let entities = json::snapshot_from_json(&definitions, &document).expect("Error");
+        let mut snapshot = SnapshotOutputStream::in_memory_with_registry(&ComponentRegistry::new())
+            .expect("Error");
+        for entity in &entities {
+            snapshot.write_serialized(entity).expect("Error");
+        }
+        snapshot.into_bytes().expect("Error")
+    };
+
+    let (_, entity) = SnapshotInputStream::from_reader(&bytes[..])
+        .expect("Error")
+        .read_entity()
+        .expect("Error");
+    approx::assert_abs_diff_eq!(90.0, entity.get::<Rotate>().expect("Error").angle);
+
+    let mut snapshot =
+        SnapshotInputStream::from_reader_with_registry(&bytes[..], &ComponentRegistry::new())
+            .expect("Error");
+    let entities = snapshot.serialized_entities();
+    assert_eq!(
+        document,
+        json::snapshot_to_json(&definitions, entities).expect("Error")
+    );
+}
+
+#[test]
+pub fn validation_reports_duplicate_entity_ids() {
+    let bytes = {
+        let mut snapshot = SnapshotOutputStream::in_memory().expect("Error");
+        for id in &[1, 2, 1] {
+            let entity = get_test_entity().expect("Error");
+            snapshot
+                .write_entity(EntityId::new(*id), &entity)
+                .expect("Error");
+        }
+        snapshot.into_bytes().expect("Error")
+    };
+
+    let mut snapshot =
+        SnapshotInputStream::from_reader_with_registry(&bytes[..], &ComponentRegistry::new())
+            .expect("Error");
+    let report = validation::Validator::new()
+        .check_entities(snapshot.serialized_entities())
+        .expect("Error");
+    assert_eq!(3, report.entity_count);
+    assert_eq!(
+        vec![validation::ValidationError {
+            entity_id: EntityId::new(1),
+            kind: validation::ValidationErrorKind::DuplicateEntityId,
+        }],
+        report.errors
+    );
+}
+
+#[test]
+pub fn diff_reports_changed_fields_by_name() {
+    let definitions = SchemaDefinitions::from_json(include_str!(
+        "../../spatialos-sdk-code-generator/data/test.sb.json"
+    ))
+    .expect("Error");
+    // Round trips the entities through an in-memory snapshot.
+    let write_snapshot = |document: serde_json::Value| {
+        let mut snapshot = SnapshotOutputStream::in_memory_with_registry(&ComponentRegistry::new())
+            .expect("Error");
+        for entity in json::snapshot_from_json(&definitions, &document).expect("Error") {
+            snapshot.write_serialized(&entity).expect("Error");
+        }
+        let bytes = snapshot.into_bytes().expect("Error");
+
+        SnapshotInputStream::from_reader_with_registry(&bytes[..], &ComponentRegistry::new())
+            .expect("Error")
+            .serialized_entities()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Error")
+    };
+    let rotate = |angle: f64| {
+        serde_json::json!({
+            "angle": angle,
+            "center": { "x": 1.0, "y": 2.0, "z": 3.0 },
+            "radius": 5.0,
+        })
+    };
+
+    let old = write_snapshot(serde_json::json!({
+        "entities": [{ "id": 1, "components": { "example.Rotate": rotate(0.0) } }],
+    }));
+    let new = write_snapshot(serde_json::json!({
+        "entities": [
+            { "id": 1, "components": { "example.Rotate": rotate(1.5) } },
+            { "id": 2, "components": {} },
+        ],
+    }));
+
+    let diff = diff::diff_entities(&definitions, old, new).expect("Error");
+    assert_eq!(vec![EntityId::new(2)], diff.added_entities);
+    assert_eq!(
+        vec![diff::FieldChange {
+            component: "example.Rotate".to_owned(),
+            path: "angle".to_owned(),
+            old: Some(serde_json::json!(0.0)),
+            new: Some(serde_json::json!(1.5)),
+        }],
+        diff.changed_entities[0].changed_fields
+    );
+}
+
+#[test]
+pub fn transformer_rewrites_component_fields() {
+    let definitions = || {
+        SchemaDefinitions::from_json(include_str!(
+            "../../spatialos-sdk-code-generator/data/test.sb.json"
+        ))
+        .expect("Error")
+    };
+    let input = {
+        let entities = [1, 2]
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "id": id,
+                    "components": { "example.Rotate": { "angle": 0.0, "radius": 5.0 } },
+                })
+            })
+            .collect::<Vec<_>>();
+        let document = serde_json::json!({ "entities": entities });
+        let mut snapshot = SnapshotOutputStream::in_memory_with_registry(&ComponentRegistry::new())
+            .expect("Error");
+        for entity in json::snapshot_from_json(&definitions(), &document).expect("Error") {
+            snapshot.write_serialized(&entity).expect("Error");
+        }
+        snapshot.into_bytes().expect("Error")
+    };
+
+    let mut reader =
+        SnapshotInputStream::from_reader_with_registry(&input[..], &ComponentRegistry::new())
+            .expect("Error");
+    let mut writer =
+        SnapshotOutputStream::in_memory_with_registry(&ComponentRegistry::new()).expect("Error");
+
+    let summary = transform::SnapshotTransformer::new(definitions())
+        .with_component_transform(Rotate::ID, |fields| {
+            fields.insert("angle".to_owned(), serde_json::json!(3.0));
+            Ok(())
+        })
+        .with_filter(|entity| entity.id != EntityId::new(2))
+        .transform_entities(reader.serialized_entities(), |entity| {
+            writer.write_serialized(entity)
+        })
+        .expect("Error");
+    assert_eq!(2, summary.entities_read);
+    assert_eq!(1, summary.entities_written);
+
+    let output = writer.into_bytes().expect("Error");
+    let entities = SnapshotInputStream::from_reader(&output[..])
+        .expect("Error")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Error");
+    assert_eq!(1, entities.len());
+    let rotate = entities[0].1.get::<Rotate>().expect("Error");
+    approx::assert_abs_diff_eq!(3.0, rotate.angle);
+    approx::assert_abs_diff_eq!(5.0, rotate.radius);
+}
+
+#[test]
+pub fn transformer_rewrites_acl_entries_of_dropped_and_remapped_components() {
+    const REMAPPED_ROTATE_ID: ComponentId = 1501;
+
+    let input = {
+        let mut builder = EntityBuilder::new(0.0, 0.0, 0.0, "RustWorker");
+        builder.set_persistent("RustWorker");
+        builder.add_component(
+            Rotate {
+                angle: 1.5,
+                center: Vector3d {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                radius: 5.0,
+            },
+            "RustWorker",
+        );
+        let mut snapshot = SnapshotOutputStream::in_memory().expect("Error");
+        snapshot
+            .write_entity(EntityId::new(1), &builder.build().expect("Error"))
+            .expect("Error");
+        snapshot.into_bytes().expect("Error")
+    };
+
+    let mut reader =
+        SnapshotInputStream::from_reader_with_registry(&input[..], &ComponentRegistry::new())
+            .expect("Error");
+    let mut entities = Vec::new();
+    let definitions = SchemaDefinitions::from_json(include_str!(
+        "../../spatialos-sdk-code-generator/data/test.sb.json"
+    ))
+    .expect("Error");
+    transform::SnapshotTransformer::new(definitions)
+        .with_dropped_component(Persistence::ID)
+        .with_remapped_component(Rotate::ID, REMAPPED_ROTATE_ID)
+        .transform_entities(reader.serialized_entities(), |entity| {
+            entities.push(entity.clone());
+            Ok(())
+        })
+        .expect("Error");
+
+    let known_components = ComponentRegistry::global()
+        .component_ids()
+        .chain(Some(REMAPPED_ROTATE_ID))
+        .collect::<Vec<_>>();
+    let report = validation::Validator::with_known_components(known_components)
+        .check_entities(entities.iter().cloned().map(Ok))
+        .expect("Error");
+    assert!(report.is_valid(), "{:?}", report.errors);
+
+    let acl = entities[0]
+        .components
+        .iter()
+        .find(|component| component.id == EntityAcl::ID)
+        .expect("Error");
+    let mut data = SchemaComponentData::new();
+    data.fields_mut()
+        .merge_from_buffer(&acl.data)
+        .expect("Error");
+    let acl = EntityAcl::from_data(&data).expect("Error");
+    assert_eq!(
+        vec![Position::ID, REMAPPED_ROTATE_ID],
+        acl.component_write_acl.keys().cloned().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+pub fn queries_are_evaluated_against_positions_locally() {
+    let entity_at = |x: f64, rotate: Option<Rotate>| {
+        let mut builder = EntityBuilder::new(x, 0.0, 0.0, "RustWorker");
+        if let Some(rotate) = rotate {
+            builder.add_component(rotate, "RustWorker");
+        }
+        builder.build().expect("Error")
+    };
+    let rotate = Rotate {
+        angle: 1.5,
+        center: Vector3d {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        radius: 5.0,
+    };
+    let entities = [
+        (EntityId::new(1), entity_at(0.0, None)),
+        (EntityId::new(2), entity_at(100.0, Some(rotate.clone()))),
+        (EntityId::new(3), entity_at(10.0, Some(rotate))),
+    ];
+    let nearby = Query::within_sphere((5.0, 0.0, 0.0), 5.0);
+
+    match EntityQuery::new(nearby.clone(), ResultType::Count)
+        .evaluate(entities.iter().map(|(id, entity)| (*id, entity)))
+        .expect("Error")
+    {
+        LocalQueryResult::Count(count) => assert_eq!(2, count),
+        other => panic!("Expected a count result, got {:?}", other),
+    }
+
+    let serialized = entities
+        .iter()
+        .map(|(id, entity)| SerializedEntity::from_entity(*id, entity));
+    match EntityQuery::new(
+        nearby & Query::has::<Rotate>(),
+        ResultType::snapshot_of::<Rotate>(),
+    )
+    .evaluate_serialized(serialized)
+    .expect("Error")
+    {
+        LocalQueryResult::Snapshot(matched) => {
+            assert_eq!(1, matched.len());
+            assert_eq!(EntityId::new(3), matched[0].0);
+            assert_eq!(
+                vec![Rotate::ID],
+                matched[0]
+                    .1
+                    .components
+                    .iter()
+                    .map(|component| component.id)
+                    .collect::<Vec<_>>()
+            );
+        }
+        other => panic!("Expected a snapshot result, got {:?}", other),
+    }
+}
+
 fn get_test_entity() -> Result<Entity, String> {
     let mut builder = EntityBuilder::new(10.0, -10.0, 0.0, "RustWorker");
     builder.set_persistent("RustWorker");